@@ -5,10 +5,10 @@ use std::ffi::CStr;
 
 use lognplot::net::TcpClient;
 
-const RESULT_OK: u32 = 0;
-const RESULT_ERR_OTHER: u32 = 1;
-const RESULT_ERR_INVALID_CLIENT_PTR: u32 = 2;
-const RESULT_ERR_INVALID_ARGUMENT: u32 = 3;
+pub const RESULT_OK: u32 = 0;
+pub const RESULT_ERR_OTHER: u32 = 1;
+pub const RESULT_ERR_INVALID_CLIENT_PTR: u32 = 2;
+pub const RESULT_ERR_INVALID_ARGUMENT: u32 = 3;
 
 #[no_mangle]
 pub extern "C" fn lognplot_client_new(address: *const c_char) -> *mut TcpClient {
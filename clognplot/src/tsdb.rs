@@ -1,9 +1,11 @@
 //! TSDB API
 use libc::c_char;
 use lognplot::time::TimeStamp;
-use lognplot::tsdb::{Observation, Sample, TsDb, TsDbHandle};
+use lognplot::tsdb::{LookupMode, Observation, Sample, TsDb, TsDbHandle};
 use std::ffi::CStr;
 
+use super::client::{RESULT_ERR_INVALID_ARGUMENT, RESULT_ERR_OTHER, RESULT_OK};
+
 #[no_mangle]
 pub extern "C" fn lognplot_tsdb_new() -> *mut TsDbHandle {
     let handle = TsDb::default().into_handle();
@@ -44,3 +46,44 @@ pub extern "C" fn lognplot_tsdb_query(db: *mut TsDbHandle) {
         println!("TODO: query");
     }
 }
+
+/// Get the value of a signal at the given time.
+///
+/// The mode selects the previous (0), next (1) or nearest (2) sample,
+/// or interpolates linearly (3). Returns an error when there is no value,
+/// and an invalid argument error when the name is not valid UTF-8.
+#[no_mangle]
+pub extern "C" fn lognplot_tsdb_value_at(
+    db: *mut TsDbHandle,
+    name: *const c_char,
+    t: f64,
+    mode: u32,
+    value: *mut f64,
+) -> u32 {
+    let mode = match mode {
+        0 => LookupMode::Previous,
+        1 => LookupMode::Next,
+        2 => LookupMode::Nearest,
+        3 => LookupMode::Linear,
+        _ => return RESULT_ERR_INVALID_ARGUMENT,
+    };
+
+    if db.is_null() || name.is_null() || value.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let db: &TsDbHandle = unsafe { &*db };
+        let name = match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name) => name,
+            Err(_) => return RESULT_ERR_INVALID_ARGUMENT,
+        };
+
+        if let Some(observation) = db.value_at(name, &TimeStamp::new(t), mode) {
+            unsafe {
+                *value = observation.value.value;
+            }
+            RESULT_OK
+        } else {
+            RESULT_ERR_OTHER
+        }
+    }
+}
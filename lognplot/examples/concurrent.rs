@@ -0,0 +1,148 @@
+use lognplot::time::TimeStamp;
+/// Demo of database performance when used from multiple threads.
+///
+/// Strategy: several writer threads insert batches of points into their
+/// own signal, while a reader thread continuously queries a large signal.
+/// Measure how many points the writers manage to insert.
+///
+/// This is done with the database handle, which only locks the trace
+/// being used, and as a baseline with the whole database behind a
+/// single lock, where queries and insertions wait on each other.
+use lognplot::tsdb::{Observation, Query, Sample, TsDb, TsDbHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+const NUM_WRITERS: usize = 4;
+const NUM_INSERTIONS: usize = 2_000_000;
+const BATCH_SIZE: usize = 100;
+
+/// The ways to share the database between threads.
+#[derive(Clone)]
+enum Database {
+    Handle(TsDbHandle),
+    GlobalLock(Arc<Mutex<TsDb>>),
+}
+
+impl Database {
+    fn add_values(&self, name: &str, samples: Vec<Observation<Sample>>) {
+        match self {
+            Database::Handle(db) => db.add_values(name, samples),
+            Database::GlobalLock(db) => db.lock().unwrap().add_values(name, samples),
+        }
+    }
+
+    fn query(&self, name: &str, query: Query) {
+        match self {
+            Database::Handle(db) => db.query(name, query).unwrap(),
+            Database::GlobalLock(db) => db.lock().unwrap().query(name, query).unwrap(),
+        };
+    }
+}
+
+fn main() {
+    println!("=== Single global lock ===");
+    let global = Database::GlobalLock(Arc::new(Mutex::new(TsDb::default())));
+    let (global_quiet, global_busy) = benchmark(&global);
+
+    println!("=== Database handle ===");
+    let handle = Database::Handle(TsDb::default().into_handle());
+    let (handle_quiet, handle_busy) = benchmark(&handle);
+
+    println!(
+        "Insertion rate while querying: {:.3} mega-points per second with a single global lock, {:.3} with the handle.",
+        global_busy / 1.0e6,
+        handle_busy / 1.0e6
+    );
+    println!(
+        "Compared to the rate without queries: {:.1} % with a single global lock, {:.1} % with the handle.",
+        100.0 * global_busy / global_quiet,
+        100.0 * handle_busy / handle_quiet
+    );
+}
+
+/// Measure the insertion rates without and with queries.
+fn benchmark(db: &Database) -> (f64, f64) {
+    fill_queried_signal(db);
+
+    println!("Without queries:");
+    let quiet_rate = insertions(db, false);
+
+    println!("While querying another signal:");
+    let busy_rate = insertions(db, true);
+
+    (quiet_rate, busy_rate)
+}
+
+fn fill_queried_signal(db: &Database) {
+    let samples = (0..1_000_000)
+        .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(i as f64)))
+        .collect();
+    db.add_values("queried", samples);
+}
+
+/// Insert points from several threads, return the rate in points per second.
+fn insertions(db: &Database, with_queries: bool) -> f64 {
+    let running = Arc::new(AtomicBool::new(true));
+
+    let reader = if with_queries {
+        let db = db.clone();
+        let running = running.clone();
+        Some(thread::spawn(move || {
+            let mut queries = 0;
+            while running.load(Ordering::Relaxed) {
+                // Query all raw samples, like a large zoomed out view:
+                let query = Query::create()
+                    .start(TimeStamp::new(0.0))
+                    .end(TimeStamp::new(1_000_000.0))
+                    .amount(1_000_000)
+                    .build();
+                db.query("queried", query);
+                queries += 1;
+            }
+            queries
+        }))
+    } else {
+        None
+    };
+
+    let t1 = Instant::now();
+    let writers: Vec<_> = (0..NUM_WRITERS)
+        .map(|w| {
+            let db = db.clone();
+            let name = format!("writer{}_{}", w, with_queries);
+            thread::spawn(move || {
+                for batch in 0..NUM_INSERTIONS / BATCH_SIZE {
+                    let samples = (batch * BATCH_SIZE..(batch + 1) * BATCH_SIZE)
+                        .map(|i| {
+                            let ts = TimeStamp::new(i as f64);
+                            Observation::new(ts, Sample::new(i as f64))
+                        })
+                        .collect();
+                    db.add_values(&name, samples);
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    let time_delta = t1.elapsed().as_secs_f64();
+
+    running.store(false, Ordering::Relaxed);
+    if let Some(reader) = reader {
+        let queries = reader.join().unwrap();
+        println!("Performed {} queries.", queries);
+    }
+
+    let total = NUM_WRITERS * NUM_INSERTIONS;
+    let rate = total as f64 / time_delta;
+    println!(
+        "Inserted {} points in {} seconds, {} mega-points per second.",
+        total,
+        time_delta,
+        rate / 1.0e6
+    );
+    rate
+}
@@ -1,34 +1,105 @@
 use lognplot::time::TimeStamp;
 /// Demo of raw database performance.
 ///
-/// Strategy: insert 1 million points, and measure how long it took.
-use lognplot::tsdb::{Observation, Query, Sample, TsDb};
+/// Strategy: insert 1 million points, and measure how long it took,
+/// and how much memory is used. Then do the same with a noisy signal,
+/// which compresses worse, with batches, and with larger nodes in the tree.
+///
+/// The amount of points can be given as argument.
+use lognplot::tsdb::{Fanout, Observation, Query, Sample, TsDb};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
+/// Allocator which keeps track of the amount of memory in use.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const BATCH_SIZE: usize = 10_000;
+
 fn main() {
-    let mut db = TsDb::default();
+    let num_insertions = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(1_000_000);
 
-    insertions(&mut db);
+    println!("Single insertions:");
+    let db = insertions(num_insertions, Fanout::default(), 1, ramp);
     do_query(&db);
+
+    println!("Single insertions of a noisy signal:");
+    insertions(num_insertions, Fanout::default(), 1, noise);
+
+    println!("Batches of {} points:", BATCH_SIZE);
+    insertions(num_insertions, Fanout::default(), BATCH_SIZE, ramp);
+
+    let fanout = Fanout::new(64, 16);
+    println!(
+        "Batches of {} points, with fanout {:?}:",
+        BATCH_SIZE, fanout
+    );
+    insertions(num_insertions, fanout, BATCH_SIZE, ramp);
+}
+
+fn ramp(i: usize) -> f64 {
+    i as f64
 }
 
-fn insertions(db: &mut TsDb) {
-    let num_insertions = 1_000_000;
+/// Pseudo random values between 0 and 100, with two decimals.
+fn noise(i: usize) -> f64 {
+    let mut x = i as u64 + 12345;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    (x % 10000) as f64 / 100.0
+}
+
+fn insertions(
+    num_insertions: usize,
+    fanout: Fanout,
+    batch_size: usize,
+    value: fn(usize) -> f64,
+) -> TsDb {
+    let mut db = TsDb::default();
+    db.set_trace_fanout("fu", fanout);
     println!(
         "Created database, now inserting {} data points into a signal.",
         num_insertions
     );
 
-    let t1 = Instant::now();
-    for i in 0..num_insertions {
-        let ts = TimeStamp::new(i as f64);
-        let sample = Sample::new(i as f64);
-        let observation = Observation::new(ts, sample);
-        db.add_value("fu", observation);
+    let memory_before = ALLOCATED.load(Ordering::Relaxed);
+    let mut time_delta = 0.0;
+    let mut i = 0;
+    while i < num_insertions {
+        let end = (i + batch_size).min(num_insertions);
+        let batch: Vec<Observation<Sample>> = (i..end)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(value(i))))
+            .collect();
+        i = end;
+
+        // Only measure the insertion, not the creation of the batch:
+        let t1 = Instant::now();
+        db.add_values("fu", batch);
+        let t2 = Instant::now();
+        time_delta += (t2 - t1).as_secs_f64();
     }
-    let t2 = Instant::now();
-    let time_delta = t2 - t1;
-    let time_delta = time_delta.as_secs_f64();
+    let memory = ALLOCATED.load(Ordering::Relaxed) - memory_before;
 
     println!(
         "Inserted {} points in {} seconds.",
@@ -36,6 +107,12 @@ fn insertions(db: &mut TsDb) {
     );
     let rate = num_insertions as f64 / time_delta;
     println!("That means {} mega-points per second.", rate / 1.0e6);
+    println!(
+        "That is {:.1} ns and {:.1} bytes per point.",
+        time_delta * 1.0e9 / num_insertions as f64,
+        memory as f64 / num_insertions as f64
+    );
+    db
 }
 
 fn do_query(db: &TsDb) {
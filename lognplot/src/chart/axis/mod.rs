@@ -5,9 +5,11 @@
 
 mod date;
 mod options;
+mod time;
 mod util;
 mod value;
 
+pub use time::TimeAxis;
 pub use value::ValueAxis;
 
 type TickLabels = Vec<(f64, String)>;
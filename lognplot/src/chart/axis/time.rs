@@ -0,0 +1,275 @@
+use super::date::calc_date_ticks;
+use super::options::AxisOptions;
+use super::util::{calc_tick_spacing, format_at_scale, get_scale};
+use crate::time::{Duration, TimeSpan, TimeStamp};
+
+/// Tick markers on a time axis.
+pub type TimeTickLabels = Vec<(TimeStamp, String)>;
+
+/// An axis showing time.
+///
+/// The limits are timestamps, so that the axis keeps nanosecond
+/// resolution, also when zoomed in far away from the EPOCH.
+#[derive(Clone)]
+pub struct TimeAxis {
+    pub options: AxisOptions,
+    pub label: Option<String>,
+    begin: TimeStamp,
+    end: TimeStamp,
+}
+
+impl Default for TimeAxis {
+    fn default() -> Self {
+        TimeAxis {
+            options: AxisOptions::default(),
+            label: None,
+            begin: TimeStamp::from_seconds(0),
+            end: TimeStamp::from_seconds(10),
+        }
+    }
+}
+
+impl TimeAxis {
+    pub fn set_limits(&mut self, begin: TimeStamp, end: TimeStamp) {
+        self.begin = begin;
+        self.end = end;
+    }
+
+    /// Take limit values from other axis.
+    pub fn copy_limits(&mut self, other: &Self) {
+        self.set_limits(other.begin.clone(), other.end.clone());
+    }
+
+    pub fn begin(&self) -> &TimeStamp {
+        &self.begin
+    }
+
+    pub fn end(&self) -> &TimeStamp {
+        &self.end
+    }
+
+    pub fn contains(&self, t: &TimeStamp) -> bool {
+        (&self.begin <= t) && (t <= &self.end)
+    }
+
+    /// Get the time selected by this axis!
+    pub fn timespan(&self) -> TimeSpan {
+        TimeSpan::new(self.begin.clone(), self.end.clone())
+    }
+
+    pub fn domain(&self) -> Duration {
+        &self.end - &self.begin
+    }
+
+    /// Zoom the axis by a certain percentage, optionally centered around some time.
+    pub fn zoom(&mut self, amount: f64, around: Option<TimeStamp>) {
+        let domain = self.domain().as_secs_f64();
+        if (domain < 1.0e-8) && (amount < 0.0) {
+            return;
+        }
+
+        // Stay well within the range of the timestamps:
+        if (domain > 1.0e9) && (amount > 0.0) {
+            return;
+        }
+
+        let left_percent = if let Some(around) = around {
+            if self.begin < around && around < self.end {
+                (&around - &self.begin).as_secs_f64() / domain
+            } else {
+                0.5
+            }
+        } else {
+            0.5
+        };
+        let right_percent = 1.0 - left_percent;
+
+        let step = domain * amount * 2.0;
+        let begin = self.begin.clone() - Duration::from_secs_f64(step * left_percent);
+        let end = self.end.clone() + Duration::from_secs_f64(step * right_percent);
+
+        if begin < end {
+            self.set_limits(begin, end);
+        }
+    }
+
+    /// Perform a relative panning based on the scale of the axis.
+    pub fn pan_relative(&mut self, amount: f64) {
+        let step = Duration::from_secs_f64(self.domain().as_secs_f64() * amount);
+        self.pan_absolute(step);
+    }
+
+    /// Pan an absolute amount.
+    ///
+    /// Panning stops at the ends of the range of the timestamps,
+    /// keeping the domain of the axis.
+    pub fn pan_absolute(&mut self, step: Duration) {
+        let step = step
+            .min(&TimeStamp::from_nanos(i64::MAX) - &self.end)
+            .max(&TimeStamp::from_nanos(i64::MIN) - &self.begin);
+        let begin = self.begin.clone() + step;
+        let end = self.end.clone() + step;
+        self.set_limits(begin, end);
+    }
+
+    /// Retrieve a good value for a cursor label!
+    pub fn get_cursor_label(&self, ts: &TimeStamp) -> String {
+        let scale = get_scale(self.domain().as_secs_f64());
+        format_time_at_scale(ts, scale - 2)
+    }
+
+    /// Calculate date time tick markers.
+    ///
+    /// This returns an optional prefix (offset)
+    /// and a set of tick labels.
+    pub fn calc_date_tiks(&self, n_ticks: usize) -> (Option<String>, TimeTickLabels) {
+        let begin = self.begin.to_seconds();
+        // If time in some range between 1973 and 2096, use data time stuff:
+        if 1.0e8 < begin && begin < 4.0e9 {
+            let (prefix, labels) = calc_date_ticks(&self.begin, &self.end, n_ticks);
+            (Some(prefix), labels)
+        } else {
+            (None, self.calc_tiks(n_ticks))
+        }
+    }
+
+    /// Calculate tick markers, labeled with the amount of seconds.
+    fn calc_tiks(&self, n_ticks: usize) -> TimeTickLabels {
+        let (scale, ticks) = calc_time_ticks(&self.begin, &self.end, n_ticks);
+        ticks
+            .into_iter()
+            .map(|t| {
+                let label = format_time_at_scale(&t, scale);
+                (t, label)
+            })
+            .collect()
+    }
+}
+
+/// Calculate tick positions between two timestamps.
+///
+/// The ticks are a whole amount of steps away from the EPOCH, calculated
+/// on integer nanoseconds. Returns the scale of the step size as well.
+pub fn calc_time_ticks(
+    begin: &TimeStamp,
+    end: &TimeStamp,
+    n_ticks: usize,
+) -> (i32, Vec<TimeStamp>) {
+    let (scale, tick_step) = calc_tick_spacing((end - begin).as_secs_f64(), n_ticks);
+    let step = Duration::from_secs_f64(tick_step).max(Duration::from_nanos(1));
+
+    let offset = begin.nanos().rem_euclid(step.as_nanos());
+    let mut x = if offset == 0 {
+        begin.clone()
+    } else {
+        begin.clone() + Duration::from_nanos(step.as_nanos() - offset)
+    };
+
+    let mut ticks = vec![];
+    while &x < end {
+        ticks.push(x.clone());
+        x = x + step;
+    }
+    (scale, ticks)
+}
+
+/// Format a timestamp as amount of seconds, with the precision
+/// given by the scale.
+///
+/// Sub-second digits are taken from the integer nanoseconds, so
+/// they are exact, also for large timestamps.
+fn format_time_at_scale(ts: &TimeStamp, scale: i32) -> String {
+    if scale > 0 {
+        format_at_scale(ts.to_seconds(), scale)
+    } else {
+        let digits = ((-scale + 1) as u32).min(9);
+        let unit = 10i128.pow(9 - digits);
+        let nanos = i128::from(ts.nanos());
+        let rounded = (nanos.abs() + unit / 2) / unit;
+        let fraction_scale = 10i128.pow(digits);
+        let sign = if nanos < 0 && rounded != 0 { "-" } else { "" };
+        format!(
+            "{}{}.{:0width$}",
+            sign,
+            rounded / fraction_scale,
+            rounded % fraction_scale,
+            width = digits as usize
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_time_at_scale, TimeAxis};
+    use crate::time::{Duration, TimeStamp};
+
+    #[test]
+    fn tick_calculation() {
+        let mut axis = TimeAxis::default();
+        axis.set_limits(TimeStamp::new(1.5), TimeStamp::new(3.3));
+        let (prefix, ticks) = axis.calc_date_tiks(7);
+        assert!(prefix.is_none());
+
+        let expected_ticks: Vec<(TimeStamp, String)> = (0..9)
+            .map(|i| {
+                let t = TimeStamp::from_nanos(1_600_000_000 + i * 200_000_000);
+                (t, format!("{:.1}", 1.6 + i as f64 * 0.2))
+            })
+            .collect();
+        assert_eq!(expected_ticks, ticks);
+    }
+
+    #[test]
+    fn pan_to_end_of_time() {
+        let mut axis = TimeAxis::default();
+        axis.set_limits(TimeStamp::new(0.0), TimeStamp::new(1.0e9));
+        for _ in 0..20 {
+            axis.pan_relative(1.0);
+        }
+        assert_eq!(&TimeStamp::from_nanos(i64::MAX), axis.end());
+        assert_eq!(Duration::from_secs(1_000_000_000), axis.domain());
+
+        for _ in 0..40 {
+            axis.pan_relative(-1.0);
+        }
+        assert_eq!(&TimeStamp::from_nanos(i64::MIN), axis.begin());
+        assert_eq!(Duration::from_secs(1_000_000_000), axis.domain());
+    }
+
+    #[test]
+    fn nanosecond_ticks() {
+        // Zoomed in to a few nanoseconds, far away from the EPOCH:
+        let begin = TimeStamp::from_seconds(5_000_000_000);
+        let mut axis = TimeAxis::default();
+        axis.set_limits(begin.clone(), begin.clone() + Duration::from_nanos(10));
+        let (_, ticks) = axis.calc_date_tiks(5);
+        assert_eq!(5, ticks.len());
+        assert_eq!(begin, ticks[0].0);
+        assert_eq!("5000000000.000000000", ticks[0].1);
+        assert_eq!("5000000000.000000002", ticks[1].1);
+    }
+
+    #[test]
+    fn zoom_and_pan() {
+        let mut axis = TimeAxis::default();
+        axis.zoom(-0.25, None);
+        assert_eq!(&TimeStamp::new(2.5), axis.begin());
+        assert_eq!(&TimeStamp::new(7.5), axis.end());
+
+        axis.zoom(-0.25, Some(TimeStamp::new(3.5)));
+        assert_eq!(&TimeStamp::new(3.0), axis.begin());
+        assert_eq!(&TimeStamp::new(5.5), axis.end());
+
+        axis.pan_relative(1.0);
+        assert_eq!(&TimeStamp::new(5.5), axis.begin());
+        assert_eq!(&TimeStamp::new(8.0), axis.end());
+    }
+
+    #[test]
+    fn time_formatting() {
+        assert_eq!("1.50", format_time_at_scale(&TimeStamp::new(1.5), -1));
+        assert_eq!("-2.25", format_time_at_scale(&TimeStamp::new(-2.25), -1));
+        assert_eq!("0.0", format_time_at_scale(&TimeStamp::from_nanos(-1), 0));
+        assert_eq!("12", format_time_at_scale(&TimeStamp::new(12.3), 1));
+    }
+}
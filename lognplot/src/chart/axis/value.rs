@@ -1,10 +1,8 @@
-use super::date::calc_date_ticks;
 use super::options::AxisOptions;
 use super::TickLabels;
 use crate::geometry::Range;
-use crate::time::{TimeSpan, TimeStamp};
 
-use super::util::{calc_tick_spacing, ceil_to_multiple_of, format_at_scale, get_scale};
+use super::util::{calc_tick_spacing, ceil_to_multiple_of, format_at_scale};
 
 #[derive(Clone)]
 pub struct ValueAxis {
@@ -43,16 +41,6 @@ impl ValueAxis {
         self.range.end()
     }
 
-    pub fn contains(&self, t: &TimeStamp) -> bool {
-        self.range.contains(t.amount)
-    }
-
-    /// Get the time selected by this axis!
-    pub fn timespan(&self) -> TimeSpan {
-        // TODO: temp hack?
-        TimeSpan::new(TimeStamp::new(self.begin()), TimeStamp::new(self.end()))
-    }
-
     pub fn domain(&self) -> f64 {
         self.end() - self.begin()
     }
@@ -102,32 +90,9 @@ impl ValueAxis {
         self.set_limits(begin, end);
     }
 
-    /// Retrieve a good value for a cursor label!
-    pub fn get_cursor_label(&self, ts: &TimeStamp) -> String {
-        let domain = self.range.end() - self.range.begin();
-        let scale = get_scale(domain);
-        let t = ts.amount;
-        format_at_scale(t, scale - 2)
-    }
-
     pub fn calc_tiks(&self, n_ticks: usize) -> TickLabels {
         calc_tiks(self.range.begin(), self.range.end(), n_ticks)
     }
-
-    /// Calculate date time tick markers.
-    ///
-    /// This returns an optional prefix (offset)
-    /// and a set of tick labels.
-    pub fn calc_date_tiks(&self, n_ticks: usize) -> (Option<String>, TickLabels) {
-        let begin = self.range.begin();
-        // If time in some range between 1973 and 2096, use data time stuff:
-        if 1.0e8 < begin && begin < 4.0e9 {
-            let (prefix, labels) = calc_date_ticks(begin, self.range.end(), n_ticks);
-            (Some(prefix), labels)
-        } else {
-            (None, calc_tiks(begin, self.range.end(), n_ticks))
-        }
-    }
 }
 
 /// Calculate the proper major tick and minor ticks for
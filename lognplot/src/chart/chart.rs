@@ -1,18 +1,20 @@
 //! Chart functionality!
 
-use super::axis::ValueAxis;
+use super::axis::{TimeAxis, ValueAxis};
 use super::curve::Curve;
+use super::text_track::TextTrack;
 use super::Cursor;
 use crate::geometry::Range;
-use crate::time::{TimeSpan, TimeStamp};
-use crate::tsdb::Summary;
+use crate::time::{Duration, TimeSpan, TimeStamp};
+use crate::tsdb::{Snapshot, Statistic, Summary};
+use std::sync::Arc;
 
 /// A single 2D-chart
 pub struct Chart {
     /// An optional title for the plot
     pub title: Option<String>,
 
-    pub x_axis: ValueAxis,
+    pub x_axis: TimeAxis,
     pub y_axis: ValueAxis,
 
     /// To show grid or not.
@@ -21,6 +23,9 @@ pub struct Chart {
     /// The curves in the plot
     pub curves: Vec<Curve>,
 
+    /// Text tracks, stacked below the plot.
+    pub text_tracks: Vec<TextTrack>,
+
     /// The position of the cursor.
     pub cursor: Option<Cursor>,
 
@@ -29,19 +34,28 @@ pub struct Chart {
 
     /// Horizontal cursor 2 for measuring
     pub cursor2: Option<TimeStamp>,
+
+    /// Statistics of aggregated values to show at the cursor.
+    pub statistics: Vec<Statistic>,
+
+    /// When paused, the snapshot of the database shown by the curves.
+    snapshot: Option<Arc<Snapshot>>,
 }
 
 impl Default for Chart {
     fn default() -> Self {
         Chart {
             title: None,
-            x_axis: ValueAxis::default(),
+            x_axis: TimeAxis::default(),
             y_axis: ValueAxis::default(),
             grid: true,
             curves: vec![],
+            text_tracks: vec![],
             cursor: None,
             cursor1: None,
             cursor2: None,
+            statistics: vec![Statistic::Mean, Statistic::Min, Statistic::Max],
+            snapshot: None,
         }
     }
 }
@@ -61,13 +75,54 @@ impl Chart {
     }
 
     /// Drop a new curve into the mix!
-    pub fn add_curve(&mut self, curve: Curve) {
+    pub fn add_curve(&mut self, mut curve: Curve) {
+        curve.data.set_snapshot(self.snapshot.clone());
         self.curves.push(curve);
     }
 
-    /// Remove all curves from this plot.
+    /// Add a text track below the plot.
+    pub fn add_text_track(&mut self, mut track: TextTrack) {
+        track.set_snapshot(self.snapshot.clone());
+        self.text_tracks.push(track);
+    }
+
+    /// Keep showing the data of the given snapshot of the database,
+    /// while new data is still added to the database.
+    ///
+    /// Other charts on the same database are not affected.
+    pub fn pause(&mut self, snapshot: Snapshot) {
+        self.set_snapshot(Some(Arc::new(snapshot)));
+    }
+
+    /// Show the latest data again.
+    pub fn resume(&mut self) {
+        self.set_snapshot(None);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    fn set_snapshot(&mut self, snapshot: Option<Arc<Snapshot>>) {
+        for curve in &mut self.curves {
+            curve.data.set_snapshot(snapshot.clone());
+        }
+        for track in &mut self.text_tracks {
+            track.set_snapshot(snapshot.clone());
+        }
+        self.snapshot = snapshot;
+    }
+
+    /// Remove the curve or text track showing the given signal.
+    pub fn remove_curve(&mut self, name: &str) {
+        self.curves.retain(|c| c.name() != name);
+        self.text_tracks.retain(|t| t.name() != name);
+    }
+
+    /// Remove all curves and text tracks from this plot.
     pub fn clear_curves(&mut self) {
         self.curves.clear();
+        self.text_tracks.clear();
         self.cursor1 = None;
         self.cursor2 = None;
     }
@@ -84,8 +139,19 @@ impl Chart {
         }
     }
 
+    /// The time span between the two cursors, when both are set.
+    pub fn cursor_timespan(&self) -> Option<TimeSpan> {
+        let cursor1 = self.cursor1.clone()?;
+        let cursor2 = self.cursor2.clone()?;
+        if cursor1 <= cursor2 {
+            Some(TimeSpan::new(cursor1, cursor2))
+        } else {
+            Some(TimeSpan::new(cursor2, cursor1))
+        }
+    }
+
     /// Zoom horizontally.
-    pub fn zoom_horizontal(&mut self, amount: f64, around: Option<f64>) {
+    pub fn zoom_horizontal(&mut self, amount: f64, around: Option<TimeStamp>) {
         self.x_axis.zoom(amount, around);
     }
 
@@ -99,7 +165,7 @@ impl Chart {
         self.x_axis.pan_relative(amount);
     }
 
-    pub fn pan_horizontal_absolute(&mut self, amount: f64) {
+    pub fn pan_horizontal_absolute(&mut self, amount: Duration) {
         self.x_axis.pan_absolute(amount);
     }
 
@@ -124,7 +190,7 @@ impl Chart {
     pub fn zoom_to_last(&mut self, tail_duration: f64) {
         if let Some(summary) = self.data_summary(None) {
             let end = summary.timespan.end;
-            let begin = end.clone() - tail_duration;
+            let begin = end.clone() - Duration::from_secs_f64(tail_duration);
             let timespan = TimeSpan::new(begin, end);
             self.fit_x_axis_to_timespan(&timespan);
         }
@@ -151,13 +217,14 @@ impl Chart {
     }
 
     pub fn fit_x_axis_to_timespan(&mut self, timespan: &TimeSpan) {
-        let mut domain = timespan.end.amount - timespan.start.amount;
-        if domain.abs() < 1.0e-18 {
-            domain = 1.0;
+        let mut domain = &timespan.end - &timespan.start;
+        if domain.as_nanos() == 0 {
+            domain = Duration::from_secs(1);
         }
 
-        let minimum = timespan.start.amount - domain * 0.05;
-        let maximum = timespan.end.amount + domain * 0.05;
+        let margin = Duration::from_nanos(domain.as_nanos() / 20);
+        let minimum = timespan.start.clone() - margin;
+        let maximum = timespan.end.clone() + margin;
         self.x_axis.set_limits(minimum, maximum);
     }
 
@@ -167,6 +234,11 @@ impl Chart {
             .curves
             .iter()
             .filter_map(|c| c.data_summary(timespan))
+            .chain(
+                self.text_tracks
+                    .iter()
+                    .filter_map(|t| t.data_summary(timespan)),
+            )
             .collect();
         ChartDataSummary::from_summaries(&summaries)
     }
@@ -183,6 +255,7 @@ impl Chart {
 
     pub fn has_signal(&self, name: &str) -> bool {
         self.curves.iter().any(|c| c.name() == name)
+            || self.text_tracks.iter().any(|t| t.name() == name)
     }
 }
 
@@ -201,10 +274,20 @@ impl ChartDataSummary {
 
             for summary in summaries {
                 timespan.extend_to_include_span(summary.timespan());
-                if let Summary::Value(value_summary) = summary {
-                    let min_value = value_summary.metrics().min;
-                    let max_value = value_summary.metrics().max;
-                    metrics.push((min_value, max_value));
+                match summary {
+                    Summary::Value(value_summary) => {
+                        let min_value = value_summary.metrics().min;
+                        let max_value = value_summary.metrics().max;
+                        metrics.push((min_value, max_value));
+                    }
+                    Summary::Vector(vector_summary) => {
+                        let vector_metrics = vector_summary.metrics();
+                        for index in 0..vector_metrics.len() {
+                            let component = vector_metrics.component(index).unwrap();
+                            metrics.push((component.min, component.max));
+                        }
+                    }
+                    _ => {}
                 }
             }
 
@@ -223,3 +306,40 @@ impl ChartDataSummary {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Chart;
+    use crate::chart::{Curve, CurveData};
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Observation, Sample, TsDb};
+
+    #[test]
+    fn pause_single_chart() {
+        let db = TsDb::default().into_handle();
+        let add = |t: f64| db.add_value("foo", Observation::new(TimeStamp::new(t), Sample::new(t)));
+        add(1.0);
+
+        let curve = || Curve::new(CurveData::trace("foo", db.clone()), "red");
+        let mut paused = Chart::default();
+        paused.add_curve(curve());
+        let mut live = Chart::default();
+        live.add_curve(curve());
+        paused.pause(db.snapshot());
+        add(2.0);
+
+        // Only the paused chart keeps showing the old data:
+        assert!(paused.is_paused());
+        assert_eq!(Some(TimeStamp::new(1.0)), paused.get_last_timestamp());
+        assert_eq!(Some(TimeStamp::new(2.0)), live.get_last_timestamp());
+
+        // Curves added while paused show the same moment:
+        paused.add_curve(curve());
+        db.delete("foo");
+        assert_eq!(Some(TimeStamp::new(1.0)), paused.get_last_timestamp());
+        assert_eq!(None, live.get_last_timestamp());
+
+        paused.resume();
+        assert_eq!(None, paused.get_last_timestamp());
+    }
+}
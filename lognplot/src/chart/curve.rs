@@ -1,9 +1,11 @@
 use crate::geometry::Point;
-use crate::style::{Color, Stroke};
+use crate::style::{Color, Stroke, CATEGORY10_COLORS};
 use crate::time::{TimeSpan, TimeStamp};
-use crate::tsdb::{Aggregation, Observation, Sample, SampleMetrics, Summary};
+use crate::tsdb::{Aggregation, CallSpan, LastValue, LookupMode, Observation, Sample};
+use crate::tsdb::{QuantileSketch, SampleMetrics, SharedTrack, Snapshot, Summary};
 use crate::tsdb::{Query, QueryResult, RangeQueryResult, TsDbHandle};
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// A single curve with some stroke styling.
 #[derive(Debug, Clone)]
@@ -20,6 +22,12 @@ pub enum CurveData {
     Trace {
         name: String,
         db: TsDbHandle,
+
+        /// When paused, the data of the database at the moment of pausing.
+        snapshot: Option<Arc<Snapshot>>,
+
+        /// The component to plot of a vector trace, or all when None.
+        component: Option<usize>,
         // TODO: cache database queries in some data structure?
     },
 
@@ -41,6 +49,18 @@ impl CurveData {
         CurveData::Trace {
             name: name.to_string(),
             db,
+            snapshot: None,
+            component: None,
+        }
+    }
+
+    /// Plot a single component of a vector trace.
+    pub fn trace_component(name: &str, component: usize, db: TsDbHandle) -> Self {
+        CurveData::Trace {
+            name: name.to_string(),
+            db,
+            snapshot: None,
+            component: Some(component),
         }
     }
 }
@@ -53,6 +73,35 @@ impl CurveData {
         }
     }
 
+    /// The selected component of a vector trace, if any.
+    pub fn component(&self) -> Option<usize> {
+        match self {
+            CurveData::Points(..) => None,
+            CurveData::Trace { component, .. } => *component,
+        }
+    }
+
+    /// Show the data of a snapshot of the database, instead of the
+    /// latest data, or the latest data again when None.
+    pub fn set_snapshot(&mut self, snapshot: Option<Arc<Snapshot>>) {
+        if let CurveData::Trace { snapshot: s, .. } = self {
+            *s = snapshot;
+        }
+    }
+
+    /// The trace of a trace curve, from the snapshot when paused.
+    fn track(&self) -> Option<SharedTrack> {
+        match self {
+            CurveData::Points(..) => None,
+            CurveData::Trace {
+                name,
+                snapshot: Some(snapshot),
+                ..
+            } => snapshot.track(name),
+            CurveData::Trace { name, db, .. } => db.track(name),
+        }
+    }
+
     /// Pull data in for drawing the graph.
     pub fn query(&self, timespan: &TimeSpan, amount: usize) -> Option<QueryResult> {
         match self {
@@ -68,15 +117,72 @@ impl CurveData {
             }
 
             // In case of a trace, query database for points.
-            CurveData::Trace { name, db } => {
+            CurveData::Trace { .. } => {
                 // Time for time series database benefit
                 // TODO: cache results?
                 let query = Query::create().amount(amount).span(&timespan).build();
-                db.query(name, query)
+                Some(self.track()?.read().unwrap().query(query))
             }
         }
     }
 
+    /// Pull in the calls of a profile trace.
+    pub fn query_calls(&self, timespan: &TimeSpan, amount: usize) -> Option<Vec<CallSpan>> {
+        self.track()?.read().unwrap().query_calls(timespan, amount)
+    }
+
+    /// The name of the trace and its database, for trace data.
+    pub fn trace_source(&self) -> Option<(&str, &TsDbHandle)> {
+        match self {
+            CurveData::Points(..) => None,
+            CurveData::Trace { name, db, .. } => Some((name, db)),
+        }
+    }
+
+    /// The snapshot shown instead of the latest data, if paused.
+    pub fn snapshot(&self) -> Option<&Arc<Snapshot>> {
+        match self {
+            CurveData::Points(..) => None,
+            CurveData::Trace { snapshot, .. } => snapshot.as_ref(),
+        }
+    }
+
+    /// The value of a value trace, or of the selected component of a
+    /// vector trace, at the given time, interpolated between the
+    /// surrounding observations.
+    pub fn value_at(&self, timestamp: &TimeStamp) -> Option<f64> {
+        match (
+            self.observation_at(timestamp, LookupMode::Linear)?,
+            self.component(),
+        ) {
+            (LastValue::Value(observation), None) => Some(observation.value.value),
+            (LastValue::Vector(observation), Some(component)) => {
+                observation.value.values.get(component).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// The observation of the trace at the given time.
+    pub fn observation_at(&self, timestamp: &TimeStamp, mode: LookupMode) -> Option<LastValue> {
+        self.track()?
+            .read()
+            .unwrap()
+            .observation_at(timestamp, mode)
+    }
+
+    /// Sketch the distribution of the values of a value trace.
+    ///
+    /// Returns None for points and vector traces.
+    pub fn quantile_sketch(&self, timespan: &TimeSpan) -> Option<QuantileSketch> {
+        match self {
+            CurveData::Trace {
+                component: None, ..
+            } => self.track()?.read().unwrap().quantile_sketch(timespan),
+            _ => None,
+        }
+    }
+
     fn summary(&self, timespan: Option<&TimeSpan>) -> Option<Summary> {
         match &self {
             CurveData::Points(points) => {
@@ -86,7 +192,14 @@ impl CurveData {
                     point_summary(points)
                 }
             }
-            CurveData::Trace { name, db } => db.summary(name, timespan),
+            CurveData::Trace { component, .. } => {
+                match (self.track()?.read().unwrap().summary(timespan)?, component) {
+                    (Summary::Vector(summary), Some(component)) => {
+                        Some(Summary::Value(summary.component(*component)?))
+                    }
+                    (summary, _) => Some(summary),
+                }
+            }
         }
     }
 }
@@ -123,7 +236,10 @@ fn point_summary(points: &[Point]) -> Option<Summary> {
 
 impl Curve {
     pub fn new(data: CurveData, color: &str) -> Self {
-        let stroke = Stroke::from_str(color).unwrap();
+        let stroke = Stroke::from_str(color).unwrap_or_else(|err| {
+            warn!("{}, using the default color", err);
+            Stroke::default()
+        });
         let legend = None;
 
         Self {
@@ -137,17 +253,62 @@ impl Curve {
         self.stroke.color.clone()
     }
 
+    /// The color of a component of a vector curve.
+    ///
+    /// When all components are drawn, each one gets its own color
+    /// from the palette, starting at the color of the curve.
+    pub fn component_color(&self, component: usize) -> Color {
+        if component == 0 || self.data.component().is_some() {
+            return self.color();
+        }
+
+        let palette: Vec<Color> = CATEGORY10_COLORS
+            .iter()
+            .map(|c| Color::from_str(c).expect("Palette colors are valid"))
+            .collect();
+        let start = palette
+            .iter()
+            .position(|c| *c == self.stroke.color)
+            .unwrap_or(palette.len() - 1);
+        palette[(start + component) % palette.len()].clone()
+    }
+
     pub fn name(&self) -> String {
         self.data.name()
     }
 
+    /// The text to show in the legend.
+    pub fn label(&self) -> String {
+        if let Some(legend) = &self.legend {
+            legend.clone()
+        } else if let Some(component) = self.data.component() {
+            format!("{}[{}]", self.name(), component)
+        } else {
+            self.name()
+        }
+    }
+
+    pub fn value_at(&self, timestamp: &TimeStamp) -> Option<f64> {
+        self.data.value_at(timestamp)
+    }
+
     /// Retrieve a data summary of this curve.
     pub fn data_summary(&self, timespan: Option<&TimeSpan>) -> Option<Summary> {
         self.data.summary(timespan)
     }
 
+    /// Sketch the distribution of the values of this curve, for quantiles.
+    pub fn quantile_sketch(&self, timespan: &TimeSpan) -> Option<QuantileSketch> {
+        self.data.quantile_sketch(timespan)
+    }
+
     /// Pull data in for drawing the graph.
     pub fn query(&self, timespan: &TimeSpan, amount: usize) -> Option<QueryResult> {
         self.data.query(timespan, amount)
     }
+
+    /// Pull in the calls for drawing a call stack.
+    pub fn query_calls(&self, timespan: &TimeSpan, amount: usize) -> Option<Vec<CallSpan>> {
+        self.data.query_calls(timespan, amount)
+    }
 }
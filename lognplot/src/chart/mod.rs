@@ -6,10 +6,14 @@
 mod axis;
 mod chart;
 mod curve;
+mod range_statistics;
+mod text_track;
 
-pub use axis::ValueAxis;
+pub use axis::{TimeAxis, ValueAxis};
 pub use chart::Chart;
 pub use curve::{Curve, CurveData};
+pub use range_statistics::{range_statistics_columns, RangeStatistics};
+pub use text_track::TextTrack;
 
 use crate::geometry::Size;
 use crate::render::Canvas;
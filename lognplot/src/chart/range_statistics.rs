@@ -0,0 +1,216 @@
+//! Statistics of the curves of a chart, over the range between its cursors.
+
+use super::Curve;
+use crate::style::Color;
+use crate::time::TimeSpan;
+use crate::tsdb::{Aggregation, QuantileSketch, Sample, SampleMetrics, Statistic, Summary};
+
+/// Statistics of a single curve over a range of time.
+#[derive(Debug, Clone)]
+pub struct RangeStatistics {
+    /// Name of the curve, with the component for vector traces.
+    pub name: String,
+    pub color: Color,
+    pub count: usize,
+
+    /// The selected statistics, with their values when known.
+    pub values: Vec<(Statistic, Option<f64>)>,
+
+    /// Seconds from the start to the end of the range.
+    pub delta_t: f64,
+
+    /// Change of the value from the start to the end of the range,
+    /// when the values at both ends are known.
+    pub delta_value: Option<f64>,
+}
+
+/// Titles of the values in a row with the given statistics.
+pub fn range_statistics_columns(statistics: &[Statistic]) -> Vec<String> {
+    let mut columns = vec!["Signal".to_owned(), "Count".to_owned()];
+    columns.extend(statistics.iter().map(|statistic| statistic.to_string()));
+    columns.extend(vec![
+        "Δt".to_owned(),
+        "Δvalue".to_owned(),
+        "Slope".to_owned(),
+    ]);
+    columns
+}
+
+impl RangeStatistics {
+    /// Determine the given statistics of a curve over the given time span.
+    ///
+    /// A curve of a vector trace gives statistics for each component.
+    /// The values are only sketched when a quantile is asked for.
+    pub fn of_curve(curve: &Curve, timespan: &TimeSpan, statistics: &[Statistic]) -> Vec<Self> {
+        let delta_t = (&timespan.end - &timespan.start).as_secs_f64();
+        match curve.data_summary(Some(timespan)) {
+            Some(Summary::Value(aggregation)) => {
+                let delta_value = match (
+                    curve.value_at(&timespan.start),
+                    curve.value_at(&timespan.end),
+                ) {
+                    (Some(start), Some(end)) => Some(end - start),
+                    _ => None,
+                };
+                let sketch = if statistics.iter().any(|statistic| statistic.needs_sketch()) {
+                    curve.quantile_sketch(timespan)
+                } else {
+                    None
+                };
+                vec![Self::new(
+                    curve.name(),
+                    curve.color(),
+                    &aggregation,
+                    statistics,
+                    sketch.as_ref(),
+                    delta_t,
+                    delta_value,
+                )]
+            }
+            Some(Summary::Vector(aggregation)) => (0..aggregation.metrics().len())
+                .filter_map(|index| {
+                    let component = aggregation.component(index)?;
+                    let name = format!("{}[{}]", curve.name(), index);
+                    Some(Self::new(
+                        name,
+                        curve.color(),
+                        &component,
+                        statistics,
+                        None,
+                        delta_t,
+                        None,
+                    ))
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn new(
+        name: String,
+        color: Color,
+        aggregation: &Aggregation<Sample, SampleMetrics>,
+        statistics: &[Statistic],
+        sketch: Option<&QuantileSketch>,
+        delta_t: f64,
+        delta_value: Option<f64>,
+    ) -> Self {
+        let metrics = aggregation.metrics();
+        let values = statistics
+            .iter()
+            .map(|statistic| (*statistic, statistic.value(metrics, sketch)))
+            .collect();
+        RangeStatistics {
+            name,
+            color,
+            count: aggregation.count,
+            values,
+            delta_t,
+            delta_value,
+        }
+    }
+
+    /// The value of one of the selected statistics.
+    pub fn value(&self, statistic: Statistic) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(selected, _)| *selected == statistic)
+            .and_then(|(_, value)| *value)
+    }
+
+    /// Average change of the value per second over the range.
+    pub fn slope(&self) -> Option<f64> {
+        let delta_value = self.delta_value?;
+        if self.delta_t > 0.0 {
+            Some(delta_value / self.delta_t)
+        } else {
+            None
+        }
+    }
+
+    /// The statistics as text, in the order of `range_statistics_columns`.
+    pub fn to_row(&self) -> Vec<String> {
+        let optional = |value: Option<f64>| value.map_or_else(|| "-".to_owned(), format_value);
+        let mut row = vec![self.name.clone(), self.count.to_string()];
+        row.extend(self.values.iter().map(|(_, value)| optional(*value)));
+        row.push(format!("{} s", format_value(self.delta_t)));
+        row.push(optional(self.delta_value));
+        row.push(optional(self.slope()));
+        row
+    }
+}
+
+/// Format a value with a few significant digits.
+fn format_value(value: f64) -> String {
+    let magnitude = value.abs();
+    if value == 0.0 {
+        "0".to_owned()
+    } else if (1.0e-3..1.0e5).contains(&magnitude) {
+        let decimals = (3 - magnitude.log10().floor() as i32).clamp(0, 6) as usize;
+        format!("{:.*}", decimals, value)
+    } else {
+        format!("{:.3e}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_value, range_statistics_columns, RangeStatistics};
+    use crate::chart::{Curve, CurveData};
+    use crate::time::{TimeSpan, TimeStamp};
+    use crate::tsdb::{Observation, Sample, Statistic, TsDb, Vector};
+
+    #[test]
+    fn ramp() {
+        let db = TsDb::default().into_handle();
+        let ramp = (0..=100)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(2.0 * i as f64)))
+            .collect();
+        db.add_values("ramp", ramp);
+        let vectors = (0..=100)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Vector::new(vec![1.0, i as f64])))
+            .collect();
+        db.add_vectors("vec", &[], vectors);
+
+        let timespan = TimeSpan::new(TimeStamp::new(10.0), TimeStamp::new(20.5));
+        let selected = [
+            Statistic::Min,
+            Statistic::Max,
+            Statistic::Mean,
+            Statistic::Quantile(0.5),
+        ];
+        let curve = Curve::new(CurveData::trace("ramp", db.clone()), "red");
+        let statistics = RangeStatistics::of_curve(&curve, &timespan, &selected);
+        assert_eq!(1, statistics.len());
+        let statistics = &statistics[0];
+        assert_eq!(11, statistics.count);
+        assert_eq!(Some(20.0), statistics.value(Statistic::Min));
+        assert_eq!(Some(40.0), statistics.value(Statistic::Max));
+        assert_eq!(Some(30.0), statistics.value(Statistic::Mean));
+        let median = statistics.value(Statistic::Quantile(0.5)).unwrap();
+        assert!((median - 30.0).abs() < 0.3);
+        assert_eq!(None, statistics.value(Statistic::StdDev));
+        assert_eq!(Some(21.0), statistics.delta_value);
+        assert_eq!(Some(2.0), statistics.slope());
+        let columns = range_statistics_columns(&selected);
+        assert_eq!(columns.len(), statistics.to_row().len());
+
+        let curve = Curve::new(CurveData::trace("vec", db), "blue");
+        let statistics = RangeStatistics::of_curve(&curve, &timespan, &selected);
+        assert_eq!(2, statistics.len());
+        assert_eq!("vec[1]", statistics[1].name);
+        assert_eq!(Some(15.0), statistics[1].value(Statistic::Mean));
+        assert_eq!(None, statistics[1].value(Statistic::Quantile(0.5)));
+        assert_eq!(None, statistics[1].slope());
+    }
+
+    #[test]
+    fn value_formatting() {
+        assert_eq!("0", format_value(0.0));
+        assert_eq!("3.142", format_value(std::f64::consts::PI));
+        assert_eq!("-1234", format_value(-1234.25));
+        assert_eq!("0.01250", format_value(0.0125));
+        assert_eq!("1.000e-6", format_value(1.0e-6));
+        assert_eq!("1.000e9", format_value(1.0e9));
+    }
+}
@@ -1,15 +1,61 @@
+use crate::style::Color;
 use crate::time::TimeSpan;
-use crate::tsdb::{CountMetrics, Query, RangeQueryResult, Text, TsDbHandle};
+use crate::tsdb::{CountMetrics, Query, RangeQueryResult, SharedTrack, Snapshot, Summary};
+use crate::tsdb::{Text, TsDbHandle};
+use std::str::FromStr;
+use std::sync::Arc;
 
 /// A single track with textual events.
+///
+/// Text tracks are drawn below the plot area of a chart,
+/// sharing the time axis of the chart.
+#[derive(Debug, Clone)]
 pub struct TextTrack {
     name: String,
     db: TsDbHandle,
+
+    /// When paused, the data of the database at the moment of pausing.
+    snapshot: Option<Arc<Snapshot>>,
+
+    color: Color,
 }
 
 impl TextTrack {
-    pub fn new(name: String, db: TsDbHandle) -> Self {
-        TextTrack { name, db }
+    /// Create a new text track, drawn in the given color.
+    ///
+    /// When the color is not recognized, the default color is used.
+    pub fn new(name: String, db: TsDbHandle, color: &str) -> Self {
+        let color = Color::from_str(color).unwrap_or_else(|err| {
+            warn!("{}, using the default color", err);
+            Color::default()
+        });
+        TextTrack {
+            name,
+            db,
+            snapshot: None,
+            color,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn color(&self) -> Color {
+        self.color.clone()
+    }
+
+    /// Show the data of a snapshot of the database, instead of the
+    /// latest data, or the latest data again when None.
+    pub fn set_snapshot(&mut self, snapshot: Option<Arc<Snapshot>>) {
+        self.snapshot = snapshot;
+    }
+
+    fn track(&self) -> Option<SharedTrack> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.track(&self.name),
+            None => self.db.track(&self.name),
+        }
     }
 
     pub fn query(
@@ -17,8 +63,12 @@ impl TextTrack {
         timespan: &TimeSpan,
         amount: usize,
     ) -> Option<RangeQueryResult<Text, CountMetrics>> {
-        let query = Query::create().amount(amount).span(&timespan).build();
-        let result = self.db.query_text(&self.name, query);
-        result.inner
+        let query = Query::create().amount(amount).span(timespan).build();
+        self.track()?.read().unwrap().query_text(query)
+    }
+
+    /// Retrieve a data summary of this track.
+    pub fn data_summary(&self, timespan: Option<&TimeSpan>) -> Option<Summary> {
+        self.track()?.read().unwrap().summary(timespan)
     }
 }
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::net::TcpStream;
 
 use super::payload::SampleBatch;
+use crate::time::TimeStamp;
+use crate::tsdb::{LogRecord, Metadata};
 
 /// A TCP client to send logging events over TCP.
 pub struct TcpClient {
@@ -26,6 +29,17 @@ impl TcpClient {
         self.write_sample_batch(payload)
     }
 
+    /// Transmit a single sample, with a nanosecond resolution timestamp.
+    pub fn send_sample_at(
+        &mut self,
+        name: &str,
+        timestamp: &TimeStamp,
+        value: f64,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_sample_at(name.to_owned(), timestamp, value);
+        self.write_sample_batch(payload)
+    }
+
     /// Transmit a batch of samples.
     pub fn send_samples(&mut self, name: &str, samples: Vec<(f64, f64)>) -> std::io::Result<()> {
         let payload = SampleBatch::new_samples(name.to_owned(), samples);
@@ -52,6 +66,48 @@ impl TcpClient {
         self.write_sample_batch(payload)
     }
 
+    /// Send a single event, described by key/value attributes.
+    pub fn send_event(
+        &mut self,
+        name: &str,
+        timestamp: f64,
+        attributes: HashMap<String, String>,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_event(name.to_owned(), timestamp, attributes);
+        self.write_sample_batch(payload)
+    }
+
+    /// Send a single vector value, with a value for each component.
+    pub fn send_vector(
+        &mut self,
+        name: &str,
+        timestamp: f64,
+        components: Vec<String>,
+        values: Vec<f64>,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_vector(name.to_owned(), timestamp, components, values);
+        self.write_sample_batch(payload)
+    }
+
+    /// Send a single log record
+    pub fn send_log(
+        &mut self,
+        name: &str,
+        timestamp: f64,
+        record: LogRecord,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_log(name.to_owned(), timestamp, record);
+        self.write_sample_batch(payload)
+    }
+
+    /// Set the metadata of a signal, such as its unit.
+    ///
+    /// Only the fields which are set are changed.
+    pub fn send_metadata(&mut self, name: &str, metadata: Metadata) -> std::io::Result<()> {
+        let payload = SampleBatch::new_metadata(name.to_owned(), metadata);
+        self.write_sample_batch(payload)
+    }
+
     fn write_sample_batch(&mut self, payload: SampleBatch) -> std::io::Result<()> {
         // Encode data
         let data = serde_cbor::to_vec(&payload).unwrap();
@@ -2,8 +2,11 @@ use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 
-use crate::time::TimeStamp;
-use crate::tsdb::{Observation, ProfileEvent, Sample, Text, TsDbHandle};
+use crate::time::{Duration, TimeStamp};
+use crate::tsdb::{
+    Event, LogLevel, LogRecord, Metadata, Observation, ProfileEvent, Sample, Text, TsDbHandle,
+    Vector,
+};
 
 /// A chunk of data at fixed sample rate.
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,7 +23,24 @@ impl SampleBatch {
     pub fn new_sample(name: String, t: f64, value: f64) -> Self {
         SampleBatch {
             name,
-            payload: SamplePayload::Single { t, value },
+            payload: SamplePayload::Single {
+                t: PayloadTime::Seconds { t },
+                value,
+            },
+        }
+    }
+
+    /// Create a new sample batch with a single sample, at a timestamp
+    /// with full nanosecond resolution.
+    pub fn new_sample_at(name: String, timestamp: &TimeStamp, value: f64) -> Self {
+        SampleBatch {
+            name,
+            payload: SamplePayload::Single {
+                t: PayloadTime::Nanos {
+                    t_ns: timestamp.nanos(),
+                },
+                value,
+            },
         }
     }
 
@@ -35,7 +55,7 @@ impl SampleBatch {
         SampleBatch {
             name,
             payload: SamplePayload::Sampled {
-                t: t0,
+                t: PayloadTime::Seconds { t: t0 },
                 dt,
                 data: values,
             },
@@ -45,7 +65,69 @@ impl SampleBatch {
     pub fn new_text(name: String, t: f64, text: String) -> Self {
         SampleBatch {
             name,
-            payload: SamplePayload::Text { t, text },
+            payload: SamplePayload::Text {
+                t: PayloadTime::Seconds { t },
+                text,
+            },
+        }
+    }
+
+    pub fn new_event(name: String, t: f64, attributes: HashMap<String, String>) -> Self {
+        SampleBatch {
+            name,
+            payload: SamplePayload::Event {
+                t: PayloadTime::Seconds { t },
+                attributes,
+            },
+        }
+    }
+
+    /// Create a new sample batch with a single log record.
+    pub fn new_log(name: String, t: f64, record: LogRecord) -> Self {
+        SampleBatch {
+            name,
+            payload: SamplePayload::Log {
+                t: PayloadTime::Seconds { t },
+                level: record.level,
+                target: record.target,
+                message: record.message,
+                fields: record.fields,
+            },
+        }
+    }
+
+    /// Create a new sample batch with a single vector value.
+    pub fn new_vector(name: String, t: f64, components: Vec<String>, values: Vec<f64>) -> Self {
+        SampleBatch {
+            name,
+            payload: SamplePayload::Vector {
+                t: PayloadTime::Seconds { t },
+                components,
+                values,
+            },
+        }
+    }
+
+    /// Create a batch which sets the metadata of a signal, instead of adding data.
+    pub fn new_metadata(name: String, metadata: Metadata) -> Self {
+        SampleBatch {
+            name,
+            payload: SamplePayload::Metadata {
+                unit: metadata.unit,
+                description: metadata.description,
+                source: metadata.source,
+                attributes: metadata.attributes,
+            },
+        }
+    }
+
+    /// The number of observations in this batch.
+    pub fn size(&self) -> usize {
+        match &self.payload {
+            SamplePayload::Batch { samples } => samples.len(),
+            SamplePayload::Sampled { data, .. } => data.len(),
+            SamplePayload::Metadata { .. } => 0,
+            _ => 1,
         }
     }
 
@@ -57,8 +139,10 @@ impl SampleBatch {
                     .iter()
                     .enumerate()
                     .map(|(index, value)| {
-                        let t2 = t + dt * index as f64;
-                        let timestamp = TimeStamp::new(t2);
+                        // Calculate the offset separately, to retain resolution
+                        // for timestamps far away from the EPOCH:
+                        let offset = Duration::from_secs_f64(dt * index as f64);
+                        let timestamp = t.timestamp() + offset;
                         Observation::new(timestamp, Sample::new(*value))
                     })
                     .collect();
@@ -76,23 +160,52 @@ impl SampleBatch {
                 db.add_values(&self.name, samples);
             }
             SamplePayload::Single { t, value } => {
-                let timestamp = TimeStamp::new(*t);
+                let timestamp = t.timestamp();
                 let value = Observation::new(timestamp, Sample::new(*value));
                 db.add_value(&self.name, value);
             }
             SamplePayload::Text { t, text } => {
-                let timestamp = TimeStamp::new(*t);
+                let timestamp = t.timestamp();
                 let text = Observation::new(timestamp, Text::new(text.to_owned()));
                 db.add_text(&self.name, text);
             }
-            SamplePayload::Event {
-                t: _,
-                attributes: _,
+            SamplePayload::Vector {
+                t,
+                components,
+                values,
             } => {
-                // TODO
+                if components.len() == values.len() {
+                    let timestamp = t.timestamp();
+                    let vector = Observation::new(timestamp, Vector::new(values.clone()));
+                    db.add_vectors(&self.name, components, vec![vector]);
+                } else {
+                    warn!(
+                        "Dropping vector for {} with {} components but {} values",
+                        self.name,
+                        components.len(),
+                        values.len()
+                    );
+                }
+            }
+            SamplePayload::Event { t, attributes } => {
+                let timestamp = t.timestamp();
+                let event = Observation::new(timestamp, Event::new(attributes.clone()));
+                db.add_event(&self.name, event);
+            }
+            SamplePayload::Log {
+                t,
+                level,
+                target,
+                message,
+                fields,
+            } => {
+                let timestamp = t.timestamp();
+                let mut record = LogRecord::new(*level, target.to_owned(), message.to_owned());
+                record.fields = fields.clone();
+                db.add_log(&self.name, Observation::new(timestamp, record));
             }
             SamplePayload::Profile { t, event } => {
-                let timestamp = TimeStamp::new(*t);
+                let timestamp = t.timestamp();
                 let event = match event {
                     ProfileEventPayload::Enter { name } => ProfileEvent::FunctionEnter {
                         name: name.to_owned(),
@@ -102,6 +215,20 @@ impl SampleBatch {
                 let event = Observation::new(timestamp, event);
                 db.add_profile_event(&self.name, event);
             }
+            SamplePayload::Metadata {
+                unit,
+                description,
+                source,
+                attributes,
+            } => {
+                let metadata = Metadata {
+                    unit: unit.clone(),
+                    description: description.clone(),
+                    source: source.clone(),
+                    attributes: attributes.clone(),
+                };
+                db.set_metadata(&self.name, metadata);
+            }
         }
     }
 }
@@ -120,7 +247,8 @@ enum SamplePayload {
     #[serde(rename = "samples")]
     Sampled {
         /// Timestamp of the first sample
-        t: f64,
+        #[serde(flatten)]
+        t: PayloadTime,
 
         /// Spacing in time of the samples.
         dt: f64,
@@ -133,7 +261,8 @@ enum SamplePayload {
     #[serde(rename = "sample")]
     Single {
         /// Timestamp of the sample
-        t: f64,
+        #[serde(flatten)]
+        t: PayloadTime,
 
         /// The sample value
         value: f64,
@@ -142,27 +271,101 @@ enum SamplePayload {
     #[serde(rename = "text")]
     Text {
         /// Timestamp of the text
-        t: f64,
+        #[serde(flatten)]
+        t: PayloadTime,
 
         /// The text itself
         text: String,
     },
 
+    /// A vector of values, observed at the same time.
+    #[serde(rename = "vector")]
+    Vector {
+        /// Timestamp of the vector
+        #[serde(flatten)]
+        t: PayloadTime,
+
+        /// The names of the components
+        components: Vec<String>,
+
+        /// The value of each component
+        values: Vec<f64>,
+    },
+
     #[serde(rename = "event")]
     Event {
         /// Timestamp of the event
-        t: f64,
+        #[serde(flatten)]
+        t: PayloadTime,
 
         attributes: HashMap<String, String>,
     },
 
+    #[serde(rename = "log")]
+    Log {
+        /// Timestamp of the log record
+        #[serde(flatten)]
+        t: PayloadTime,
+
+        /// The severity, one of error, warn, info, debug or trace
+        level: LogLevel,
+
+        /// Where the record came from, for example a module
+        #[serde(default)]
+        target: String,
+
+        message: String,
+
+        #[serde(default)]
+        fields: HashMap<String, String>,
+    },
+
     #[serde(rename = "profile")]
     Profile {
-        t: f64,
+        #[serde(flatten)]
+        t: PayloadTime,
 
         #[serde(flatten)]
         event: ProfileEventPayload,
     },
+
+    /// Information about the signal. Fields which are left out
+    /// keep their previous value.
+    #[serde(rename = "metadata")]
+    Metadata {
+        /// The unit of the values, for example "m/s"
+        unit: Option<String>,
+
+        description: Option<String>,
+
+        /// Where the signal comes from
+        source: Option<String>,
+
+        /// Other information, such as display hints
+        #[serde(default)]
+        attributes: HashMap<String, String>,
+    },
+}
+
+/// The time of an observation.
+///
+/// Senders give either the fractional amount of seconds since the
+/// EPOCH as `t`, or the integer amount of nanoseconds as `t_ns`.
+/// The latter keeps full resolution for any time.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum PayloadTime {
+    Nanos { t_ns: i64 },
+    Seconds { t: f64 },
+}
+
+impl PayloadTime {
+    fn timestamp(&self) -> TimeStamp {
+        match self {
+            PayloadTime::Nanos { t_ns } => TimeStamp::from_nanos(*t_ns),
+            PayloadTime::Seconds { t } => TimeStamp::new(*t),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -181,6 +384,8 @@ enum ProfileEventPayload {
 #[cfg(test)]
 mod tests {
     use super::SampleBatch;
+    use crate::time::{Duration, TimeStamp};
+    use crate::tsdb::{Metadata, Summary, TsDb};
 
     #[test]
     /// Check a simple roundtrip operation (to bytes and back to data)
@@ -190,4 +395,72 @@ mod tests {
         let batch2: SampleBatch = serde_cbor::from_slice(&data).unwrap();
         assert_eq!(batch.name, batch2.name);
     }
+
+    #[test]
+    fn nanosecond_timestamps() {
+        let db = TsDb::default().into_handle();
+        let timestamp = TimeStamp::from_seconds(1_600_000_000) + Duration::from_nanos(1);
+        let batch = SampleBatch::new_sample_at("bla".to_string(), &timestamp, 2.5);
+        let data = serde_cbor::to_vec(&batch).unwrap();
+        let batch2: SampleBatch = serde_cbor::from_slice(&data).unwrap();
+        batch2.to_db(&db);
+
+        // Messages with seconds still work:
+        let batch = SampleBatch::new_sample("bla".to_string(), 1.7e9, 3.5);
+        let data = serde_cbor::to_vec(&batch).unwrap();
+        let batch3: SampleBatch = serde_cbor::from_slice(&data).unwrap();
+        batch3.to_db(&db);
+
+        let summary = db.summary("bla", None).unwrap();
+        assert_eq!(2, summary.count());
+        assert_eq!(&timestamp, &summary.timespan().start);
+        assert_eq!(
+            TimeStamp::from_seconds(1_700_000_000),
+            summary.timespan().end
+        );
+    }
+
+    #[test]
+    fn events() {
+        let mut attributes = std::collections::HashMap::new();
+        attributes.insert("state".to_owned(), "running".to_owned());
+        let batch = SampleBatch::new_event("machine".to_string(), 2.0, attributes);
+        let data = serde_cbor::to_vec(&batch).unwrap();
+        let batch2: SampleBatch = serde_cbor::from_slice(&data).unwrap();
+
+        let db = TsDb::default().into_handle();
+        batch2.to_db(&db);
+        if let Some(Summary::Event(summary)) = db.summary("machine", None) {
+            assert_eq!(1, summary.count);
+            assert_eq!(1, summary.metrics().value_count("state", "running"));
+            assert_eq!(TimeStamp::from_seconds(2), summary.timespan.start);
+        } else {
+            panic!("Expected an event summary");
+        }
+    }
+
+    #[test]
+    fn metadata() {
+        let mut metadata = Metadata::with_unit("rpm");
+        metadata.source = Some("motor controller".to_owned());
+        let batch = SampleBatch::new_metadata("speed".to_string(), metadata.clone());
+        let data = serde_cbor::to_vec(&batch).unwrap();
+        let batch2: SampleBatch = serde_cbor::from_slice(&data).unwrap();
+
+        let db = TsDb::default().into_handle();
+        batch2.to_db(&db);
+        assert_eq!(Some(metadata), db.metadata("speed"));
+
+        // Fields which are left out are not changed:
+        let mut message = std::collections::HashMap::new();
+        message.insert("name", "speed");
+        message.insert("type", "metadata");
+        message.insert("description", "Motor speed");
+        let data = serde_cbor::to_vec(&message).unwrap();
+        let batch3: SampleBatch = serde_cbor::from_slice(&data).unwrap();
+        batch3.to_db(&db);
+        let metadata = db.metadata("speed").unwrap();
+        assert_eq!(Some("rpm".to_owned()), metadata.unit);
+        assert_eq!(Some("Motor speed".to_owned()), metadata.description);
+    }
 }
@@ -96,8 +96,9 @@ fn process_packet(
     // try to decode cbor package:
     match serde_cbor::from_slice::<SampleBatch>(&packet) {
         Ok(batch) => {
-            // let batch: SampleBatch =
-            // println!("DAATAA: {:?}", batch.size());
+            peer_event_sink
+                .unbounded_send(PeerEvent::SamplesReceived(batch.size()))
+                .unwrap();
             batch.to_db(db);
         }
         Err(err) => {
@@ -1,22 +1,18 @@
 //! Functionality to emit a plot to a canvas.
 
 use super::canvas::{HorizontalAnchor, VerticalAnchor};
+use super::chart_data::{ChartData, ChartDataRequest, CursorValue, PIXELS_PER_AGGREGATION};
 use super::transform;
 use super::Canvas;
 use super::{ChartLayout, ChartOptions};
-use crate::chart::{Chart, Cursor, Curve};
+use crate::chart::{range_statistics_columns, Chart, Cursor, Curve};
 use crate::geometry::Point;
 use crate::style::Color;
 use crate::time::TimeStamp;
 use crate::tsdb::{
-    Aggregation, CountMetrics, Observation, ProfileEvent, QueryResult, RangeQueryResult, Sample,
-    SampleMetrics, Text,
+    Aggregation, CallSpan, CountMetrics, Event, EventMetrics, LogMetrics, LogRecord, Observation,
+    QueryResult, RangeQueryResult, Sample, SampleMetrics, Statistic, Text, Vector, VectorMetrics,
 };
-use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::rc::Rc;
-
-use superslice::Ext;
 
 /// Draw the given chart onto the canvas!
 pub fn draw_chart<C>(
@@ -27,7 +23,25 @@ pub fn draw_chart<C>(
 ) where
     C: Canvas,
 {
-    let mut renderer = ChartRenderer::new(chart, canvas, layout, options);
+    let request = ChartDataRequest::new(chart, layout);
+    let data = ChartData::query(chart, &request);
+    draw_chart_with_data(chart, canvas, layout, options, &data);
+}
+
+/// Draw the given chart onto the canvas, using data queried before.
+///
+/// This does not query the database, so drawing never waits on it.
+/// Curves and text tracks without data are not drawn.
+pub fn draw_chart_with_data<C>(
+    chart: &Chart,
+    canvas: &mut C,
+    layout: &mut ChartLayout,
+    options: &ChartOptions,
+    data: &ChartData,
+) where
+    C: Canvas,
+{
+    let mut renderer = ChartRenderer::new(chart, canvas, layout, options, data);
     renderer.draw();
 }
 
@@ -37,10 +51,8 @@ const PIXELS_PER_X_TICK: usize = 100;
 /// How much pixels approximately to have between y ticks.
 const PIXELS_PER_Y_TICK: usize = 60;
 
-/// Divide the width of the plot by this value, and draw at least that many data points.
-const PIXELS_PER_AGGREGATION: usize = 5;
-
-type CurveData = Option<QueryResult>;
+/// Space around the texts in a text track.
+const TEXT_TRACK_PADDING: f64 = 5.0;
 
 /// This struct will be able to render the chart onto a canvas.
 struct ChartRenderer<'a, C>
@@ -59,7 +71,8 @@ where
     // Parameters:
     options: &'a ChartOptions,
 
-    curve_data_cache: HashMap<String, Rc<CurveData>>,
+    // Data to draw, queried before.
+    data: &'a ChartData,
 
     text_track_y: f64,
 }
@@ -73,22 +86,23 @@ where
         canvas: &'a mut C,
         layout: &'a mut ChartLayout,
         options: &'a ChartOptions,
+        data: &'a ChartData,
     ) -> Self {
         ChartRenderer {
             chart,
             canvas,
             layout,
             options,
-            curve_data_cache: HashMap::new(),
+            data,
             text_track_y: 0.0,
         }
     }
 
     fn draw(&mut self) {
-        self.fetch_curve_data();
         self.draw_axis();
         self.draw_box();
         self.draw_curves();
+        self.draw_text_tracks();
         self.draw_cursor();
         self.draw_title();
         self.draw_legend();
@@ -104,7 +118,7 @@ where
         let dy = text_height * 1.3;
 
         for curve in &self.chart.curves {
-            let name = curve.name();
+            let name = curve.label();
             let color = curve.color();
             self.canvas.set_pen(color, 1.0);
             self.canvas
@@ -162,6 +176,8 @@ where
         self.layout.x_axis_legend_height =
             x_labels_max_height + self.options.tick_size * 2.0 + self.options.padding;
         self.layout.info_bar_height = self.canvas.text_size("X").height;
+        self.layout.text_tracks_height =
+            self.chart.text_tracks.len() as f64 * self.text_track_height();
         // println!("X axis height: {}, ticks={:?}", self.layout.x_axis_legend_height, x_ticks);
         self.layout.layout(&self.options);
 
@@ -174,10 +190,6 @@ where
             self.layout.plot_height,
         );
 
-        let x_ticks: Vec<(TimeStamp, String)> = x_ticks
-            .into_iter()
-            .map(|(x, s)| (TimeStamp::new(x), s))
-            .collect();
         self.draw_x_axis(prefix, &x_ticks);
 
         self.draw_y_axis(&y_ticks);
@@ -206,7 +218,8 @@ where
                 .print_text(&p, HorizontalAnchor::Left, VerticalAnchor::Bottom, &prefix);
         }
 
-        let y = self.layout.plot_bottom;
+        // The axis is below the text tracks:
+        let y = self.layout.plot_bottom + self.layout.text_tracks_height;
         // let baseline = vec![
         //     Point::new(self.layout.plot_left, y),
         //     Point::new(self.layout.plot_right, y),
@@ -233,7 +246,13 @@ where
         self.canvas.set_pen(Color::black(), 1.0);
         self.canvas.set_line_width(1.0);
 
-        if let Some(title) = &self.chart.y_axis.label {
+        let title = self
+            .chart
+            .y_axis
+            .label
+            .as_ref()
+            .or(self.data.units.as_ref());
+        if let Some(title) = title {
             let p = Point::new(10.0, self.layout.height / 2.0);
             self.canvas
                 .print_text(&p, HorizontalAnchor::Left, VerticalAnchor::Middle, title);
@@ -317,7 +336,7 @@ where
         }
 
         if let (Some(cur1), Some(cur2)) = (&self.chart.cursor1, &self.chart.cursor2) {
-            let dt: f64 = (cur1.amount - cur2.amount).abs();
+            let dt: f64 = cur1.distance(&cur2).as_secs_f64();
             let F = if dt > 1e-10 {
                 let F: f64 = 1.0 / dt;
                 format!("{} Hz", F)
@@ -331,6 +350,68 @@ where
             );
             self.canvas
                 .print_text(&p, HorizontalAnchor::Right, VerticalAnchor::Bottom, &text);
+
+            self.draw_range_statistics();
+        }
+    }
+
+    /// Draw a table with statistics of the curves between the cursors,
+    /// in the lower left corner of the plot.
+    fn draw_range_statistics(&mut self) {
+        let statistics = &self.data.range_statistics;
+        let header = match statistics.first() {
+            // The rows may have been queried for other statistics than
+            // the currently selected ones.
+            Some(row) => {
+                let selected: Vec<Statistic> = row.values.iter().map(|(s, _)| *s).collect();
+                range_statistics_columns(&selected)
+            }
+            None => return,
+        };
+        let column_count = header.len();
+        let mut rows = vec![(None, header)];
+        for row in statistics.iter() {
+            rows.push((Some(row.color.clone()), row.to_row()));
+        }
+
+        let padding = 3.0;
+        let square_size: f64 = self.canvas.text_size("X").height;
+        let row_height = square_size + padding;
+        let mut column_widths = vec![0.0_f64; column_count];
+        for (_, row) in &rows {
+            for (width, text) in column_widths.iter_mut().zip(row) {
+                *width = width.max(self.canvas.text_size(text).width);
+            }
+        }
+
+        let width = square_size
+            + column_widths.iter().sum::<f64>()
+            + padding * (column_widths.len() as f64 + 2.0);
+        let height = row_height * rows.len() as f64 + padding;
+        let x = self.layout.plot_left + self.options.padding;
+        let mut y = self.layout.plot_bottom - self.options.padding - height;
+
+        self.canvas.set_pen(Color::white(), 1.0);
+        self.canvas.fill_rect(x, y, width, height);
+        self.canvas.set_pen(Color::black(), 1.0);
+        self.canvas.draw_rect(x, y, width, height);
+
+        for (color, row) in rows {
+            if let Some(color) = color {
+                self.canvas.set_pen(color, 1.0);
+                self.canvas
+                    .fill_rect(x + padding, y + padding, square_size, square_size);
+            }
+
+            self.canvas.set_pen(Color::black(), 1.0);
+            let mut column_x = x + square_size + padding * 2.0;
+            for (text, column_width) in row.iter().zip(&column_widths) {
+                let p = Point::new(column_x, y + padding + square_size / 2.0);
+                self.canvas
+                    .print_text(&p, HorizontalAnchor::Left, VerticalAnchor::Middle, text);
+                column_x += column_width + padding;
+            }
+            y += row_height;
         }
     }
 
@@ -338,7 +419,7 @@ where
     fn draw_cursor_line(&mut self, cursor: &TimeStamp, draw_label: bool) {
         let x = self.x_domain_to_pixel(&cursor);
         let top = Point::new(x, self.layout.plot_top);
-        let bottom = Point::new(x, self.layout.plot_bottom);
+        let bottom = Point::new(x, self.layout.plot_bottom + self.layout.text_tracks_height);
         let points = vec![top, bottom];
 
         self.canvas.set_pen(Color::black(), 1.0);
@@ -358,70 +439,32 @@ where
         }
     }
 
-    fn get_cursor_values(
-        &self,
-        cursor: &Cursor,
-    ) -> Vec<(Option<(TimeStamp, f64)>, Vec<String>, Color)> {
-        let mut values = vec![];
-        for curve in &self.chart.curves {
-            if let Some(curve_data) = self.query_curve_data(&curve).borrow() {
-                match curve_data {
-                    QueryResult::Value(value_data) => match value_data {
-                        RangeQueryResult::Aggregations(aggregations) => {
-                            if let Some(a) = find_closest_aggregation(&aggregations, &cursor.0) {
-                                let ts = a.timespan.middle_timestamp();
-                                let metrics = a.metrics();
-                                let min = metrics.min;
-                                let mean = metrics.mean();
-                                let max = metrics.max;
-                                let labels = vec![
-                                    format!("mean={}", mean),
-                                    format!("min={}", min),
-                                    format!("max={}", max),
-                                ];
-                                values.push((Some((ts, mean)), labels, curve.color()));
-                            }
-                        }
-                        RangeQueryResult::Observations(observations) => {
-                            if let Some(o) = find_closest_observation(&observations, &cursor.0) {
-                                let ts = o.timestamp.clone();
-                                let value = o.value.value;
-                                let label = format!("{}", value);
-                                values.push((Some((ts, value)), vec![label], curve.color()));
-                            }
-                        }
-                    },
-                    QueryResult::Text(text_data) => {
-                        match text_data {
-                            RangeQueryResult::Aggregations(_aggregations) => {
-                                // TODO
-                            }
-                            RangeQueryResult::Observations(observations) => {
-                                if let Some(o) = find_last_observation(&observations, &cursor.0) {
-                                    let label = o.value.text.clone();
-                                    values.push((None, vec![label], curve.color()));
-                                }
-                            }
-                        }
-                    }
-                    QueryResult::Profile(_profile_data) => {}
-                }
-            }
+    /// The labels of a value at the cursor, with the statistics when
+    /// the curve is drawn aggregated.
+    fn cursor_value_labels(&self, value: &CursorValue) -> Vec<String> {
+        let mut labels = vec![value.label.clone()];
+        if self.data.is_aggregated(&value.curve) {
+            labels.extend(value.statistics.iter().cloned());
         }
-        values
+        labels
     }
 
     /// Draw values around cursor.
     fn draw_cursor_values(&mut self, cursor: &Cursor) {
-        let values = self.get_cursor_values(cursor);
+        let values = &self.data.cursor_values;
         if !values.is_empty() {
+            let labels: Vec<Vec<String>> = values
+                .iter()
+                .map(|value| self.cursor_value_labels(value))
+                .collect();
+
             // Draw circle markers:
-            for (marker, _, color) in values.iter() {
-                if let Some((ts, value)) = marker {
-                    let x = self.x_domain_to_pixel(&ts);
-                    let y = self.y_domain_to_pixel(*value);
+            for value in values {
+                if let Some((ts, y_value)) = &value.marker {
+                    let x = self.x_domain_to_pixel(ts);
+                    let y = self.y_domain_to_pixel(*y_value);
                     let p1 = Point::new(x, y);
-                    self.canvas.set_pen(color.clone(), 1.0);
+                    self.canvas.set_pen(value.color.clone(), 1.0);
                     self.canvas.draw_circle(&p1, 8.0);
                 }
             }
@@ -431,7 +474,7 @@ where
             let mut background_height: f64 = 0.0;
             let square_size: f64 = self.canvas.text_size("X").height;
 
-            for (_, labels, _) in values.iter() {
+            for labels in labels.iter() {
                 for label in labels {
                     let text_size = self.canvas.text_size(&label);
 
@@ -457,9 +500,9 @@ where
 
             // Draw background rectangle and labels
             // let background_width = values.iter().map().max()
-            for (_, labels, color) in values {
+            for (value, labels) in values.iter().zip(labels) {
                 // Draw color:
-                self.canvas.set_pen(color, 1.0);
+                self.canvas.set_pen(value.color.clone(), 1.0);
                 self.canvas
                     .fill_rect(x + padding, y + padding, square_size, square_size);
 
@@ -484,62 +527,71 @@ where
 
     /// Draw the actual curves!
     fn draw_curves(&mut self) {
-        let pixels: usize = self.layout.plot_width as usize;
-
         for curve in &self.chart.curves {
             // trace!("Plotting curve {:?}", curve);
 
             let color = curve.color();
-            if let Some(curve_data) = self.query_curve_data(&curve).borrow() {
-                match curve_data {
-                    QueryResult::Value(value_data) => match value_data {
+            if let Some(result) = self.data.curves.get(&curve.name()) {
+                match &result.data {
+                    QueryResult::Value(value_data) => {
+                        self.draw_value_data(value_data, color);
+                    }
+                    QueryResult::Vector(vector_data) => {
+                        for component in vector_components(vector_data, curve) {
+                            let value_data = vector_data.component(component);
+                            self.draw_value_data(&value_data, curve.component_color(component));
+                        }
+                    }
+                    QueryResult::Text(text_data) => match text_data {
                         RangeQueryResult::Aggregations(aggregations) => {
-                            self.draw_aggregations(aggregations, color);
+                            self.draw_text_aggregations(aggregations, color);
                         }
                         RangeQueryResult::Observations(observations) => {
-                            let draw_markers =
-                                observations.len() < pixels / (PIXELS_PER_AGGREGATION * 5);
-                            self.draw_observations(observations, color, draw_markers);
+                            self.draw_text_observations(observations, color);
                         }
                     },
-                    QueryResult::Text(text_data) => match text_data {
+                    QueryResult::Event(event_data) => match event_data {
                         RangeQueryResult::Aggregations(aggregations) => {
-                            self.draw_text_aggregations(aggregations, color);
+                            self.draw_event_aggregations(aggregations, color);
                         }
                         RangeQueryResult::Observations(observations) => {
-                            self.draw_text_observations(observations, color);
+                            self.draw_event_observations(observations, color);
                         }
                     },
-                    QueryResult::Profile(profile_data) => {
-                        match profile_data {
-                            RangeQueryResult::Observations(observations) => {
-                                self.draw_profile_observations(observations, color);
-                            }
-                            RangeQueryResult::Aggregations(_aggregations) => {
-                                // TODO!
-                            }
+                    QueryResult::Log(log_data) => match log_data {
+                        RangeQueryResult::Aggregations(aggregations) => {
+                            self.draw_log_aggregations(aggregations, color);
                         }
+                        RangeQueryResult::Observations(observations) => {
+                            self.draw_log_observations(observations, color);
+                        }
+                    },
+                    QueryResult::Profile(_profile_data) => {
+                        self.draw_call_stack(&result.calls, color);
                     }
                 }
             }
         }
     }
 
-    /// Fetch curve data from backing data store.
-    fn fetch_curve_data(&mut self) {
-        let timespan = self.chart.x_axis.timespan();
+    /// Draw the result of a query on a scalar trace.
+    fn draw_value_data(
+        &mut self,
+        value_data: &RangeQueryResult<Sample, SampleMetrics>,
+        color: Color,
+    ) {
         let pixels: usize = self.layout.plot_width as usize;
-        let point_count = pixels / PIXELS_PER_AGGREGATION;
-        for curve in &self.chart.curves {
-            let data = curve.query(&timespan, point_count);
-            self.curve_data_cache.insert(curve.name(), Rc::new(data));
+        match value_data {
+            RangeQueryResult::Aggregations(aggregations) => {
+                self.draw_aggregations(aggregations, color);
+            }
+            RangeQueryResult::Observations(observations) => {
+                let draw_markers = observations.len() < pixels / (PIXELS_PER_AGGREGATION * 5);
+                self.draw_observations(observations, color, draw_markers);
+            }
         }
     }
 
-    fn query_curve_data(&self, curve: &Curve) -> Rc<CurveData> {
-        self.curve_data_cache[&curve.name()].clone()
-    }
-
     /// Draw single observations.
     fn draw_observations(
         &mut self,
@@ -726,17 +778,85 @@ where
         self.draw_texts_in_track(texts, color);
     }
 
+    /// Draw log records as texts with their level and message.
+    fn draw_log_observations(&mut self, observations: &[Observation<LogRecord>], color: Color) {
+        let texts = observations
+            .iter()
+            .map(|o| {
+                let x = self.x_domain_to_pixel(&o.timestamp);
+                (x, format!("{} {}", o.value.level, o.value.message))
+            })
+            .collect();
+        self.draw_texts_in_track(texts, color);
+    }
+
+    /// Draw aggregated log records as the amount of records,
+    /// and the most severe level.
+    fn draw_log_aggregations(
+        &mut self,
+        aggregations: &[Aggregation<LogRecord, LogMetrics>],
+        color: Color,
+    ) {
+        let texts = aggregations
+            .iter()
+            .map(|a| {
+                let x = self.x_domain_to_pixel(&a.timespan.start);
+                let metrics = a.metrics();
+                let text = if let Some(level) = metrics.max_level() {
+                    format!("{} ({})", metrics.count, level)
+                } else {
+                    metrics.count.to_string()
+                };
+                (x, text)
+            })
+            .collect();
+        self.draw_texts_in_track(texts, color);
+    }
+
+    /// Draw the text tracks of the chart below the plot area.
+    fn draw_text_tracks(&mut self) {
+        let track_height = self.text_track_height();
+
+        for (index, track) in self.chart.text_tracks.iter().enumerate() {
+            let track_y = self.layout.plot_bottom + track_height * (index as f64 + 0.5);
+            let texts = match self.data.text_tracks.get(track.name()) {
+                Some(RangeQueryResult::Observations(observations)) => observations
+                    .iter()
+                    .map(|o| (self.x_domain_to_pixel(&o.timestamp), o.value.text.clone()))
+                    .collect(),
+                Some(RangeQueryResult::Aggregations(aggregations)) => aggregations
+                    .iter()
+                    .map(|a| {
+                        let x = self.x_domain_to_pixel(&a.timespan.start);
+                        (x, a.metrics().count.to_string())
+                    })
+                    .collect(),
+                None => vec![],
+            };
+            self.draw_texts_at(texts, track.color(), track_y);
+        }
+    }
+
+    /// The height of a single text track.
+    fn text_track_height(&self) -> f64 {
+        self.canvas.text_size("X").height + TEXT_TRACK_PADDING * 3.0
+    }
+
     /// Helper function to draw a sequence of texts between two lines.
     /// Also proceed to the next text track slot.
     fn draw_texts_in_track(&mut self, texts: Vec<(f64, String)>, color: Color) {
+        let track_y = self.layout.plot_bottom - 20.0 - self.text_track_y;
+        self.text_track_y += self.text_track_height();
+        self.draw_texts_at(texts, color, track_y);
+    }
+
+    /// Draw a sequence of texts between two lines around the given y position.
+    fn draw_texts_at(&mut self, texts: Vec<(f64, String)>, color: Color, track_y: f64) {
         self.canvas.set_pen(color, 1.0);
         self.canvas.set_line_width(2.0);
 
         let text_height = self.canvas.text_size("X").height;
-        let padding = 5.0;
-
-        let track_y = self.layout.plot_bottom - 20.0 - self.text_track_y;
-        self.text_track_y += text_height + padding * 3.0;
+        let padding = TEXT_TRACK_PADDING;
 
         let track_left = self.layout.plot_left;
         let track_right = self.layout.plot_right;
@@ -820,15 +940,114 @@ where
         }
     }
 
-    fn draw_profile_observations(
+    /// Draw events as markers with their attributes.
+    fn draw_event_observations(&mut self, observations: &[Observation<Event>], color: Color) {
+        let markers = observations
+            .iter()
+            .map(|o| (self.x_domain_to_pixel(&o.timestamp), o.value.to_string()))
+            .collect();
+        self.draw_markers(markers, color);
+    }
+
+    /// Draw aggregated events as markers with the amount of events.
+    fn draw_event_aggregations(
         &mut self,
-        _observations: &[Observation<ProfileEvent>],
+        aggregations: &[Aggregation<Event, EventMetrics>],
         color: Color,
     ) {
-        self.canvas.set_pen(color, 1.0);
-        self.canvas.set_line_width(2.0);
+        let markers = aggregations
+            .iter()
+            .map(|a| {
+                let x = self.x_domain_to_pixel(&a.timespan.middle_timestamp());
+                (x, a.metrics().count.to_string())
+            })
+            .collect();
+        self.draw_markers(markers, color);
+    }
 
-        // TODO!
+    /// Draw a marker line over the plot at each x position, with a label.
+    fn draw_markers(&mut self, markers: Vec<(f64, String)>, color: Color) {
+        let marker_size = 6.0;
+        let padding = 3.0;
+        let top = self.layout.plot_top;
+        let bottom = self.layout.plot_bottom;
+
+        self.canvas.set_line_width(1.0);
+        let end_markers = markers
+            .iter()
+            .skip(1)
+            .map(|m| m.0)
+            .chain(std::iter::once(self.layout.plot_right));
+
+        for ((x, label), end_x) in markers.iter().zip(end_markers) {
+            self.canvas.set_pen(color.clone(), 0.4);
+            self.canvas
+                .draw_line(&[Point::new(*x, top), Point::new(*x, bottom)]);
+
+            // A triangle pointing down at the top of the plot:
+            self.canvas.set_pen(color.clone(), 1.0);
+            self.canvas.fill_polygon(&[
+                Point::new(x - marker_size, top),
+                Point::new(x + marker_size, top),
+                Point::new(*x, top + marker_size * 1.5),
+            ]);
+
+            let max_text_width = end_x - x - padding * 2.0;
+            let point = Point::new(x + padding, top + marker_size * 1.5 + padding);
+            self.draw_dotted_text(
+                &point,
+                HorizontalAnchor::Left,
+                VerticalAnchor::Top,
+                label,
+                max_text_width,
+            );
+        }
+    }
+
+    /// Draw the calls of a profile trace as a flame chart.
+    ///
+    /// Outermost calls are drawn at the bottom, with the
+    /// calls made by them stacked on top.
+    fn draw_call_stack(&mut self, calls: &[CallSpan], color: Color) {
+        let text_height = self.canvas.text_size("X").height;
+        let padding = 3.0;
+        let bar_height = text_height + padding * 2.0;
+        let levels = calls.iter().map(|c| c.depth + 1).max().unwrap_or(0);
+
+        let bottom = self.layout.plot_bottom - 20.0 - self.text_track_y;
+        self.text_track_y += levels as f64 * bar_height + padding;
+
+        self.canvas.set_line_width(1.0);
+        for call in calls {
+            let x1 = self
+                .x_domain_to_pixel(&call.timespan.start)
+                .max(self.layout.plot_left);
+            let x2 = self
+                .x_domain_to_pixel(&call.timespan.end)
+                .min(self.layout.plot_right);
+            let y = bottom - (call.depth + 1) as f64 * bar_height;
+            let width = (x2 - x1).max(1.0);
+
+            self.canvas.set_pen(color.clone(), 0.4);
+            self.canvas.fill_rect(x1, y, width, bar_height);
+            self.canvas.set_pen(color.clone(), 1.0);
+            self.canvas.draw_rect(x1, y, width, bar_height);
+
+            let label = if call.count > 1 {
+                format!("{} ({}x)", call.name, call.count)
+            } else {
+                call.name.clone()
+            };
+            self.canvas.set_pen(Color::black(), 1.0);
+            let point = Point::new(x1 + padding, y + bar_height / 2.0);
+            self.draw_dotted_text(
+                &point,
+                HorizontalAnchor::Left,
+                VerticalAnchor::Middle,
+                label.trim_start(),
+                width - padding * 2.0,
+            );
+        }
     }
 
     /// Transform x-value to pixel/point location.
@@ -842,61 +1061,13 @@ where
     }
 }
 
-/// Find the last observation at the given time in a sorted list of observations.
-fn find_last_observation<'o, V>(
-    observations: &'o [Observation<V>],
-    t: &TimeStamp,
-) -> Option<&'o Observation<V>> {
-    find_last(observations, t, |o| o.timestamp.clone())
-}
-
-// Find the last value before the given timestamp.
-fn find_last<'o, T, F>(things: &'o [T], ts: &TimeStamp, f: F) -> Option<&'o T>
-where
-    F: Fn(&T) -> TimeStamp,
-{
-    let idx = things.lower_bound_by(|a| f(a).partial_cmp(ts).unwrap());
-    if (idx == 0) || (idx > things.len()) {
-        None
-    } else {
-        Some(&things[idx - 1])
-    }
-}
-
-/// Find the closest observation in a sorted list of observations.
-fn find_closest_observation<'o, V>(
-    observations: &'o [Observation<V>],
-    t: &TimeStamp,
-) -> Option<&'o Observation<V>> {
-    find_closest(observations, t, |o| o.timestamp.clone())
-}
-
-/// Find the aggregation which is closest to a certain timestamp
-/// in a sorted list of aggregations.
-fn find_closest_aggregation<'o>(
-    aggregations: &'o [Aggregation<Sample, SampleMetrics>],
-    t: &TimeStamp,
-) -> Option<&'o Aggregation<Sample, SampleMetrics>> {
-    find_closest(aggregations, t, |a| a.timespan.middle_timestamp())
-}
-
-/// Find the thing which is closest to the given timestamp.
-/// The function f must be given to turn a thing into a timestamp.
-fn find_closest<'o, T, F>(things: &'o [T], ts: &TimeStamp, f: F) -> Option<&'o T>
-where
-    F: Fn(&T) -> TimeStamp,
-{
-    let idx = things.lower_bound_by(|a| f(a).partial_cmp(ts).unwrap());
-    if (idx == 0) || (idx >= things.len()) {
-        None
-    } else {
-        // Compare two aggregations
-        let t1 = &things[idx - 1];
-        let t2 = &things[idx];
-        if f(t1).distance(ts) < f(t2).distance(ts) {
-            Some(t1)
-        } else {
-            Some(t2)
-        }
+/// Determine which components of a vector trace to draw for a curve.
+fn vector_components(
+    vector_data: &RangeQueryResult<Vector, VectorMetrics>,
+    curve: &Curve,
+) -> std::ops::Range<usize> {
+    match curve.data.component() {
+        Some(component) => component..component + 1,
+        None => 0..vector_data.components(),
     }
 }
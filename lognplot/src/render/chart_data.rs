@@ -0,0 +1,387 @@
+//! The data drawn in a chart, queried from the database before drawing.
+//!
+//! Queries on large traces can take a while. An interactive program can
+//! run them on worker threads, and draw the latest results it has, so
+//! that drawing never waits on the database.
+
+use super::transform;
+use super::ChartLayout;
+use crate::chart::{Chart, Curve, RangeStatistics, TextTrack};
+use crate::style::Color;
+use crate::time::{TimeSpan, TimeStamp};
+use crate::tsdb::{
+    CallSpan, CountMetrics, LastValue, LookupMode, QuantileSketch, QueryResult, RangeQueryResult,
+    SampleMetrics, Statistic, Summary, Text, TsDbHandle,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Divide the width of the plot by this value, and draw at least that many data points.
+pub const PIXELS_PER_AGGREGATION: usize = 5;
+
+/// Calls shorter than this amount of pixels are merged in a call stack.
+const CALL_STACK_PIXELS: usize = 3;
+
+/// The results of the queries for drawing a chart.
+#[derive(Debug, Default)]
+pub struct ChartData {
+    /// Data of the curves, by curve name.
+    pub curves: HashMap<String, CurveResult>,
+
+    /// Data of the text tracks, by track name.
+    pub text_tracks: HashMap<String, RangeQueryResult<Text, CountMetrics>>,
+
+    /// Values of the curves at the cursor.
+    pub cursor_values: Vec<CursorValue>,
+
+    /// Statistics of the curves between the two cursors.
+    pub range_statistics: Vec<RangeStatistics>,
+
+    /// The units of the curves, shown when the y axis has no label.
+    pub units: Option<String>,
+}
+
+/// The data of a single curve.
+#[derive(Debug)]
+pub struct CurveResult {
+    pub data: QueryResult,
+
+    /// The calls of a profile trace, empty for other traces.
+    pub calls: Vec<CallSpan>,
+}
+
+/// The value of a curve at the cursor.
+#[derive(Clone, Debug)]
+pub struct CursorValue {
+    /// Name of the curve.
+    pub curve: String,
+
+    /// Where to mark the value, for numeric values.
+    pub marker: Option<(TimeStamp, f64)>,
+
+    pub label: String,
+
+    /// The selected statistics of the data around the cursor.
+    pub statistics: Vec<String>,
+
+    pub color: Color,
+}
+
+impl ChartData {
+    /// Run all queries for drawing the chart, and wait for them.
+    pub fn query(chart: &Chart, request: &ChartDataRequest) -> Self {
+        ChartData {
+            curves: chart
+                .curves
+                .iter()
+                .filter_map(|curve| Some((curve.name(), request.query_curve(curve)?)))
+                .collect(),
+            text_tracks: chart
+                .text_tracks
+                .iter()
+                .filter_map(|track| {
+                    Some((track.name().to_owned(), request.query_text_track(track)?))
+                })
+                .collect(),
+            cursor_values: request.query_cursor_values(&chart.curves),
+            range_statistics: request.query_range_statistics(&chart.curves),
+            units: ChartDataRequest::query_units(&chart.curves),
+        }
+    }
+
+    /// Drop the data of a removed curve or text track.
+    pub fn remove(&mut self, name: &str) {
+        self.curves.remove(name);
+        self.text_tracks.remove(name);
+        self.cursor_values.retain(|value| value.curve != name);
+        self.range_statistics
+            .retain(|row| row.name != name && !row.name.starts_with(&format!("{}[", name)));
+    }
+
+    /// Test if the data of the given curve is aggregated.
+    pub fn is_aggregated(&self, curve: &str) -> bool {
+        matches!(
+            self.curves.get(curve).map(|result| &result.data),
+            Some(QueryResult::Value(RangeQueryResult::Aggregations(_)))
+                | Some(QueryResult::Vector(RangeQueryResult::Aggregations(_)))
+        )
+    }
+}
+
+/// What to query for drawing a chart.
+///
+/// When a part of the request changes, only the data depending on that
+/// part has to be queried again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartDataRequest {
+    /// The visible time span.
+    pub timespan: TimeSpan,
+
+    /// The width of the plot in pixels.
+    pub pixels: usize,
+
+    /// The time of the cursor, and the time span around it of which
+    /// statistics are shown, when the cursor is in view.
+    pub cursor: Option<(TimeStamp, TimeSpan)>,
+
+    /// The time span between the two cursors.
+    pub range: Option<TimeSpan>,
+
+    pub statistics: Vec<Statistic>,
+}
+
+impl ChartDataRequest {
+    /// Determine what to query for drawing the given chart.
+    pub fn new(chart: &Chart, layout: &ChartLayout) -> Self {
+        let cursor = chart
+            .cursor
+            .as_ref()
+            .filter(|(timestamp, _)| chart.x_axis.contains(timestamp))
+            .map(|(timestamp, _)| {
+                // Statistics are shown of about a single aggregation around the cursor:
+                let margin = transform::x_pixels_to_domain(
+                    layout,
+                    &chart.x_axis,
+                    PIXELS_PER_AGGREGATION as f64 / 2.0,
+                );
+                let near = TimeSpan::new(timestamp.clone() - margin, timestamp.clone() + margin);
+                (timestamp.clone(), near)
+            });
+        ChartDataRequest {
+            timespan: chart.x_axis.timespan(),
+            pixels: layout.plot_width as usize,
+            cursor,
+            range: chart.cursor_timespan(),
+            statistics: chart.statistics.clone(),
+        }
+    }
+
+    /// Test if the curves and text tracks must be queried again.
+    pub fn view_changed(&self, other: &Self) -> bool {
+        self.timespan != other.timespan || self.pixels != other.pixels
+    }
+
+    /// Test if the values at the cursor must be queried again.
+    pub fn cursor_changed(&self, other: &Self) -> bool {
+        self.cursor != other.cursor || self.statistics != other.statistics
+    }
+
+    /// Test if the statistics between the cursors must be queried again.
+    pub fn range_changed(&self, other: &Self) -> bool {
+        self.range != other.range || self.statistics != other.statistics
+    }
+
+    /// Number of data points to query for each curve.
+    pub fn point_count(&self) -> usize {
+        self.pixels / PIXELS_PER_AGGREGATION
+    }
+
+    /// Query the data of a curve.
+    pub fn query_curve(&self, curve: &Curve) -> Option<CurveResult> {
+        let data = curve.query(&self.timespan, self.point_count())?;
+        let calls = match data {
+            QueryResult::Profile(_) => curve
+                .query_calls(&self.timespan, self.pixels / CALL_STACK_PIXELS)
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+        Some(CurveResult { data, calls })
+    }
+
+    /// Query the data of a text track.
+    pub fn query_text_track(
+        &self,
+        track: &TextTrack,
+    ) -> Option<RangeQueryResult<Text, CountMetrics>> {
+        track.query(&self.timespan, self.point_count())
+    }
+
+    /// Query the values of the curves at the cursor.
+    pub fn query_cursor_values(&self, curves: &[Curve]) -> Vec<CursorValue> {
+        let (timestamp, near) = match &self.cursor {
+            Some(cursor) => cursor,
+            None => return vec![],
+        };
+
+        let mut values = vec![];
+        for curve in curves {
+            let name = curve.name();
+            match curve.data.observation_at(timestamp, LookupMode::Linear) {
+                Some(LastValue::Value(o)) => {
+                    let value = o.value.value;
+                    let statistics = match curve.data_summary(Some(near)) {
+                        Some(Summary::Value(summary)) => {
+                            let needs_sketch = self.statistics.iter().any(|s| s.needs_sketch());
+                            let sketch = if needs_sketch {
+                                curve.quantile_sketch(near)
+                            } else {
+                                None
+                            };
+                            statistic_labels(summary.metrics(), sketch.as_ref(), &self.statistics)
+                        }
+                        _ => vec![],
+                    };
+                    values.push(CursorValue {
+                        curve: name,
+                        marker: Some((o.timestamp, value)),
+                        label: format!("{}", value),
+                        statistics,
+                        color: curve.color(),
+                    });
+                }
+                Some(LastValue::Vector(o)) => {
+                    let summary = curve.data_summary(Some(near));
+                    let components = match curve.data.component() {
+                        Some(component) => component..component + 1,
+                        None => 0..o.value.len(),
+                    };
+                    for component in components {
+                        if let Some(value) = o.value.values.get(component) {
+                            let metrics = match &summary {
+                                Some(Summary::Value(summary)) => Some(summary.metrics().clone()),
+                                Some(Summary::Vector(summary)) => summary
+                                    .component(component)
+                                    .map(|summary| summary.metrics().clone()),
+                                _ => None,
+                            };
+                            let statistics = match metrics {
+                                Some(metrics) => statistic_labels(&metrics, None, &self.statistics),
+                                None => vec![],
+                            };
+                            values.push(CursorValue {
+                                curve: name.clone(),
+                                marker: Some((o.timestamp.clone(), *value)),
+                                label: format!("[{}] {}", component, value),
+                                statistics,
+                                color: curve.component_color(component),
+                            });
+                        }
+                    }
+                }
+                Some(LastValue::Profile(_)) | None => {}
+                Some(other) => {
+                    values.push(CursorValue {
+                        curve: name,
+                        marker: None,
+                        label: other.to_string(),
+                        statistics: vec![],
+                        color: curve.color(),
+                    });
+                }
+            }
+        }
+        values
+    }
+
+    /// Query the selected statistics of the curves between the two cursors.
+    pub fn query_range_statistics(&self, curves: &[Curve]) -> Vec<RangeStatistics> {
+        match &self.range {
+            Some(timespan) => curves
+                .iter()
+                .flat_map(|curve| RangeStatistics::of_curve(curve, timespan, &self.statistics))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Query the units of the curves, as a label for the y axis.
+    pub fn query_units(curves: &[Curve]) -> Option<String> {
+        // Group the curves by database, to look up their units at once:
+        let mut sources: Vec<(&TsDbHandle, Vec<&str>)> = vec![];
+        let mut units: Vec<String> = vec![];
+        for curve in curves {
+            if let Some((name, db)) = curve.data.trace_source() {
+                if let Some(snapshot) = curve.data.snapshot() {
+                    units.extend(snapshot.units(&[name]));
+                    continue;
+                }
+                match sources.iter_mut().find(|(other, _)| Arc::ptr_eq(other, db)) {
+                    Some((_, names)) => names.push(name),
+                    None => sources.push((db, vec![name])),
+                }
+            }
+        }
+        units.extend(sources.into_iter().flat_map(|(db, names)| db.units(&names)));
+        units.sort();
+        units.dedup();
+        if units.is_empty() {
+            None
+        } else {
+            Some(format!("[{}]", units.join(", ")))
+        }
+    }
+}
+
+/// Labels with the given statistics of aggregated values.
+fn statistic_labels(
+    metrics: &SampleMetrics,
+    sketch: Option<&QuantileSketch>,
+    statistics: &[Statistic],
+) -> Vec<String> {
+    statistics
+        .iter()
+        .filter_map(|statistic| {
+            let value = statistic.value(metrics, sketch)?;
+            Some(format!("{}={}", statistic, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChartData, ChartDataRequest};
+    use crate::chart::{Chart, Curve, CurveData};
+    use crate::geometry::Size;
+    use crate::render::{ChartLayout, ChartOptions};
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Observation, Sample, TsDb};
+
+    #[test]
+    fn query_parts_of_chart() {
+        let db = TsDb::default().into_handle();
+        let samples = (0..1000)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(i as f64)))
+            .collect();
+        db.add_values("foo", samples);
+        let samples = vec![
+            Observation::new(TimeStamp::new(0.0), Sample::new(1.0)),
+            Observation::new(TimeStamp::new(1000.0), Sample::new(2.0)),
+        ];
+        db.add_values("bar", samples);
+
+        let mut chart = Chart::default();
+        chart.add_curve(Curve::new(CurveData::trace("foo", db.clone()), "red"));
+        chart.add_curve(Curve::new(CurveData::trace("bar", db.clone()), "blue"));
+        chart.autoscale();
+        let mut layout = ChartLayout::new(Size::new(500.0, 300.0));
+        layout.layout(&ChartOptions::default());
+
+        // Without cursors, only the curves are queried:
+        let request = ChartDataRequest::new(&chart, &layout);
+        let data = ChartData::query(&chart, &request);
+        assert_eq!(2, data.curves.len());
+        assert!(data.is_aggregated("foo"));
+        assert!(!data.is_aggregated("bar"));
+        assert!(data.cursor_values.is_empty());
+        assert!(data.range_statistics.is_empty());
+
+        // Moving the cursor does not change the view:
+        chart.cursor = Some((TimeStamp::new(500.0), 0.0));
+        chart.cursor1 = Some(TimeStamp::new(0.0));
+        chart.cursor2 = Some(TimeStamp::new(1000.0));
+        let moved = ChartDataRequest::new(&chart, &layout);
+        assert!(!moved.view_changed(&request));
+        assert!(moved.cursor_changed(&request));
+        assert!(moved.range_changed(&request));
+
+        let mut data = ChartData::query(&chart, &moved);
+        assert_eq!(2, data.cursor_values.len());
+        assert_eq!(2, data.range_statistics.len());
+
+        // Removed curves leave no data behind:
+        data.remove("foo");
+        assert_eq!(1, data.curves.len());
+        assert_eq!(1, data.cursor_values.len());
+        assert_eq!(1, data.range_statistics.len());
+    }
+}
@@ -11,6 +11,7 @@ pub struct ChartLayout {
     pub title_height: f64,
     pub x_axis_legend_height: f64,
     pub info_bar_height: f64,
+    pub text_tracks_height: f64,
     pub plot_top: f64,
     pub plot_left: f64,
     pub plot_bottom: f64,
@@ -28,6 +29,7 @@ impl ChartLayout {
             x_axis_legend_height: 60.0,
             title_height: 0.0,
             info_bar_height: 10.0,
+            text_tracks_height: 0.0,
             height: size.height,
             plot_top: 0.0,
             plot_left: 0.0,
@@ -47,7 +49,10 @@ impl ChartLayout {
         self.plot_top = options.padding + self.title_height;
         self.plot_left = self.y_axis_legend_width;
         self.plot_bottom = self.height
-            - (self.x_axis_legend_height + options.padding * 2.0 + self.info_bar_height);
+            - (self.x_axis_legend_height
+                + options.padding * 2.0
+                + self.info_bar_height
+                + self.text_tracks_height);
         self.plot_right = self.width - options.padding;
         self.plot_height = self.plot_bottom - self.plot_top;
         self.plot_width = self.plot_right - self.plot_left;
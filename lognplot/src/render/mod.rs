@@ -3,6 +3,7 @@
 
 mod canvas;
 mod chart;
+mod chart_data;
 mod layout;
 mod options;
 mod svg_output;
@@ -14,7 +15,8 @@ mod cairo_canvas;
 // re-exports
 
 pub use canvas::Canvas;
-pub use chart::draw_chart;
+pub use chart::{draw_chart, draw_chart_with_data};
+pub use chart_data::{ChartData, ChartDataRequest, CursorValue, CurveResult};
 pub use layout::ChartLayout;
 pub use options::ChartOptions;
 pub use svg_output::SvgOutput;
@@ -1,28 +1,23 @@
 //! Transformations from pixels to axis values.
 
 use super::ChartLayout;
-use crate::chart::ValueAxis;
-use crate::time::TimeStamp;
+use crate::chart::{TimeAxis, ValueAxis};
+use crate::time::{Duration, TimeStamp};
 
-/// Calculate how many domain values a covered by the given amount of pixels.
-pub fn x_pixels_to_domain(layout: &ChartLayout, axis: &ValueAxis, pixels: f64) -> f64 {
-    let domain = axis.domain();
+/// Calculate how much time is covered by the given amount of pixels.
+pub fn x_pixels_to_domain(layout: &ChartLayout, axis: &TimeAxis, pixels: f64) -> Duration {
+    let domain = axis.domain().as_secs_f64();
     if layout.plot_width < 1.0 {
-        0.0
+        Duration::from_nanos(0)
     } else {
         let a = domain / layout.plot_width;
-        pixels * a
+        Duration::from_secs_f64(pixels * a)
     }
 }
 
-pub fn x_pixel_to_domain(pixel: f64, axis: &ValueAxis, layout: &ChartLayout) -> f64 {
-    let domain = axis.domain();
-    if layout.plot_width < 1.0 {
-        0.0
-    } else {
-        let a = domain / layout.plot_width;
-        a * (pixel - layout.plot_left) + axis.begin()
-    }
+/// Take an x pixel and transform it to a timestamp on the given axis.
+pub fn x_pixel_to_domain(pixel: f64, axis: &TimeAxis, layout: &ChartLayout) -> TimeStamp {
+    axis.begin().clone() + x_pixels_to_domain(layout, axis, pixel - layout.plot_left)
 }
 
 /// Take an y pixel and transform it to a domain value on the given axis.
@@ -36,11 +31,12 @@ pub fn y_pixel_to_domain(pixel: f64, axis: &ValueAxis, layout: &ChartLayout) ->
     }
 }
 
-pub fn x_domain_to_pixel(t: &TimeStamp, axis: &ValueAxis, layout: &ChartLayout) -> f64 {
-    let x = t.amount;
-    let domain = axis.domain();
+pub fn x_domain_to_pixel(t: &TimeStamp, axis: &TimeAxis, layout: &ChartLayout) -> f64 {
+    // Calculate relative to the axis begin, to keep precision:
+    let x = (t - axis.begin()).as_secs_f64();
+    let domain = axis.domain().as_secs_f64();
     let a = (layout.plot_width) / domain;
-    let x_pixel = a * (x - axis.begin()) + layout.plot_left;
+    let x_pixel = a * x + layout.plot_left;
     clip(x_pixel, layout.plot_left, layout.plot_right)
 }
 
@@ -68,14 +64,14 @@ mod tests {
     use super::super::{ChartLayout, ChartOptions};
     use super::{x_domain_to_pixel, x_pixel_to_domain};
     use super::{y_domain_to_pixel, y_pixel_to_domain};
-    use crate::chart::ValueAxis;
+    use crate::chart::{TimeAxis, ValueAxis};
     use crate::geometry::Size;
-    use crate::time::TimeStamp;
+    use crate::time::{Duration, TimeStamp};
 
     #[test]
     fn x_axis_roundtrips() {
-        let mut axis = ValueAxis::default();
-        axis.set_limits(10.0, 1000.0);
+        let mut axis = TimeAxis::default();
+        axis.set_limits(TimeStamp::new(10.0), TimeStamp::new(1000.0));
         let size = Size::new(500.0, 500.0);
         let options = ChartOptions::default();
         let mut layout = ChartLayout::new(size);
@@ -86,7 +82,24 @@ mod tests {
         let pixel = x_domain_to_pixel(&value, &axis, &layout);
         let value2 = x_pixel_to_domain(pixel, &axis, &layout);
 
-        assert_almost_eq(value.amount, value2, 1.0e-9);
+        assert_almost_eq(0.0, value.distance(&value2).as_secs_f64(), 1.0e-9);
+    }
+
+    #[test]
+    fn x_axis_precision() {
+        // A microsecond wide view, far away from the EPOCH:
+        let mut axis = TimeAxis::default();
+        let begin = TimeStamp::from_seconds(1_600_000_000);
+        axis.set_limits(begin.clone(), begin.clone() + Duration::from_micros(1));
+        let size = Size::new(500.0, 500.0);
+        let options = ChartOptions::default();
+        let mut layout = ChartLayout::new(size);
+        layout.layout(&options);
+
+        let value = begin + Duration::from_nanos(500);
+        let pixel = x_domain_to_pixel(&value, &axis, &layout);
+        assert_almost_eq(layout.plot_left + layout.plot_width / 2.0, pixel, 1.0e-6);
+        assert_eq!(value, x_pixel_to_domain(pixel, &axis, &layout));
     }
 
     #[test]
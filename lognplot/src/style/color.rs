@@ -1,7 +1,15 @@
 use std::str::FromStr;
 
+/// category10 color wheel
+///
+/// See also: https://matplotlib.org/users/dflt_style_changes.html#colors-in-default-property-cycle
+pub const CATEGORY10_COLORS: &[&str] = &[
+    "#1F77B4", "#FF7F0E", "#2CA02C", "#D62728", "#9467BD", "#8C564B", "#E377C2", "#7F7F7F",
+    "#BCBD22", "#17BECF",
+];
+
 /// Color indication
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Color {
     r: u8,
     g: u8,
@@ -60,10 +68,12 @@ impl FromStr for Color {
             if s.len() != 7 {
                 return Err(format!("Color code {} must have 7 digits", s));
             }
-            let r = u8::from_str_radix(&s[1..3], 16).unwrap();
-            let g = u8::from_str_radix(&s[3..5], 16).unwrap();
-            let b = u8::from_str_radix(&s[5..7], 16).unwrap();
-            (r, g, b)
+            let digits = |range: std::ops::Range<usize>| {
+                s.get(range)
+                    .and_then(|d| u8::from_str_radix(d, 16).ok())
+                    .ok_or_else(|| format!("Color code {} is not hexadecimal", s))
+            };
+            (digits(1..3)?, digits(3..5)?, digits(5..7)?)
         } else {
             match s {
                 "red" => (255, 0, 0),
@@ -79,3 +89,19 @@ impl FromStr for Color {
         Ok(Self::new(r, g, b))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Color;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(Ok(Color::new(0x1f, 0x77, 0xb4)), Color::from_str("#1F77B4"));
+        assert_eq!(Ok(Color::red()), Color::from_str("red"));
+        assert!(Color::from_str("#12345").is_err());
+        assert!(Color::from_str("#12345z").is_err());
+        assert!(Color::from_str("#1234é").is_err());
+        assert!(Color::from_str("purple").is_err());
+    }
+}
@@ -4,5 +4,5 @@
 mod color;
 mod stroke;
 
-pub use color::Color;
+pub use color::{Color, CATEGORY10_COLORS};
 pub use stroke::Stroke;
@@ -14,6 +14,12 @@ impl Stroke {
     }
 }
 
+impl Default for Stroke {
+    fn default() -> Self {
+        Self::new(Color::default(), 1.0)
+    }
+}
+
 impl FromStr for Stroke {
     type Err = String;
 
@@ -1,5 +1,138 @@
-/// A datatype for presenting a certain time duration.
+//! A datatype for presenting a certain time duration.
+//!
+//! A duration is a length of time, not fixed on the global time scale.
+//! It is stored as an integer amount of nanoseconds, and can be negative.
+//! Arithmetic saturates at the ends of the range, instead of overflowing.
 
-struct Duration {
-    attos: u64,
+use serde::{Deserialize, Serialize};
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Duration {
+    nanos: i64,
+}
+
+impl Duration {
+    pub fn from_nanos(nanos: i64) -> Self {
+        Self { nanos }
+    }
+
+    pub fn from_micros(micros: i64) -> Self {
+        Self::from_nanos(micros * 1_000)
+    }
+
+    pub fn from_millis(millis: i64) -> Self {
+        Self::from_nanos(millis * 1_000_000)
+    }
+
+    pub fn from_secs(seconds: i64) -> Self {
+        Self::from_nanos(seconds * NANOS_PER_SECOND)
+    }
+
+    /// Create a duration from a fractional amount of seconds,
+    /// rounded to the nearest nanosecond.
+    pub fn from_secs_f64(seconds: f64) -> Self {
+        Self::from_nanos((seconds * 1.0e9).round() as i64)
+    }
+
+    pub fn as_nanos(&self) -> i64 {
+        self.nanos
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        let seconds = self.nanos.div_euclid(NANOS_PER_SECOND);
+        let nanos = self.nanos.rem_euclid(NANOS_PER_SECOND);
+        seconds as f64 + nanos as f64 * 1.0e-9
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::from_nanos(self.nanos.saturating_abs())
+    }
+}
+
+impl std::ops::Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_add(other.nanos))
+    }
+}
+
+impl std::ops::Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(other.nanos))
+    }
+}
+
+impl std::ops::Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_neg())
+    }
+}
+
+impl std::ops::Mul<i64> for Duration {
+    type Output = Duration;
+
+    fn mul(self, factor: i64) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_mul(factor))
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Duration::from_nanos(duration.as_nanos() as i64)
+    }
+}
+
+impl From<chrono::Duration> for Duration {
+    fn from(duration: chrono::Duration) -> Self {
+        let seconds = duration.num_seconds();
+        let rest = duration - chrono::Duration::seconds(seconds);
+        let nanos = rest.num_nanoseconds().expect("Less than a second fits");
+        Duration::from_secs(seconds) + Duration::from_nanos(nanos)
+    }
+}
+
+impl std::fmt::Display for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} s", self.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Duration;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Duration::from_millis(1500), Duration::from_secs_f64(1.5));
+        assert_eq!(Duration::from_micros(-3), Duration::from_nanos(-3_000));
+        assert_eq!(-2.25, Duration::from_millis(-2250).as_secs_f64());
+        assert_eq!(
+            Duration::from_millis(20),
+            std::time::Duration::from_millis(20).into()
+        );
+        assert_eq!(
+            Duration::from_nanos(-1_500_000_001),
+            chrono::Duration::nanoseconds(-1_500_000_001).into()
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let d1 = Duration::from_secs(3);
+        let d2 = Duration::from_millis(500);
+        assert_eq!(Duration::from_millis(3500), d1 + d2);
+        assert_eq!(Duration::from_millis(2500), d1 - d2);
+        assert_eq!(Duration::from_millis(-500), -d2);
+        assert_eq!(Duration::from_secs(6), d1 * 2);
+        assert!(d2 < d1);
+    }
 }
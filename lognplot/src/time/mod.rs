@@ -1,8 +1,9 @@
-// mod duration;
+mod duration;
 mod resolution;
 mod timespan;
 mod timestamp;
 
+pub use duration::Duration;
 pub use resolution::Resolution;
 pub use timespan::TimeSpan;
 pub use timestamp::TimeStamp;
@@ -1,9 +1,10 @@
 use super::TimeStamp;
+use serde::{Deserialize, Serialize};
 
 /// A timespan is a period between two moments in time.
 /// It differs from a duration, in the sense that a duration
 /// is not fixed on the global time scale.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct TimeSpan {
     pub start: TimeStamp,
     pub end: TimeStamp,
@@ -20,7 +21,7 @@ impl TimeSpan {
 
     /// Retrieve the timestamp in the middle of this span.
     pub fn middle_timestamp(&self) -> TimeStamp {
-        TimeStamp::new((self.start.amount + self.end.amount) / 2.0)
+        TimeStamp::from_nanos((self.start.nanos() + self.end.nanos()) / 2)
     }
 
     pub fn extend_to_include(&mut self, time_point: &TimeStamp) {
@@ -1,53 +1,164 @@
 //! Time stamp data type.
 //!
-//! The best timestamp is the NTP timestamp. It's centered around 1 january 1900 (EPOCH) which is represented by 0.
-//! Furthermore, it has 64 bits for the amount of seconds since then (signed, so + and -)
-//! Next to that is has 64 bits for the fractional second, reaching pretty precise timestamps, but not infinite precise.
+//! A timestamp is an integer amount of nanoseconds since the unix
+//! EPOCH (1 january 1970). Using an integer instead of a floating
+//! point amount of seconds gives a fixed nanosecond resolution, also
+//! for absolute times far away from the EPOCH.
+//! The 64 bits range covers roughly the years 1678 up to 2262.
+//! Arithmetic saturates at the ends of this range, instead of overflowing.
 
+use super::Duration;
 use super::TimeModifiers;
-use std::cmp::Ordering;
+use chrono::TimeZone;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq)]
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct TimeStamp {
-    pub amount: f64,
+    nanos: i64,
 }
 
 impl TimeStamp {
-    pub fn new(amount: f64) -> Self {
-        Self { amount }
+    /// Create a timestamp from a fractional amount of seconds.
+    ///
+    /// The timestamp is rounded to the nearest nanosecond.
+    pub fn new(seconds: f64) -> Self {
+        Self::from_nanos((seconds * 1.0e9).round() as i64)
     }
 
     /// Create a timestamp from some amount of seconds.
     pub fn from_seconds(seconds: isize) -> Self {
-        Self::new(seconds as f64)
+        Self::from_nanos(seconds as i64 * NANOS_PER_SECOND)
+    }
+
+    pub fn from_nanos(nanos: i64) -> Self {
+        Self { nanos }
+    }
+
+    /// The amount of nanoseconds since EPOCH.
+    pub fn nanos(&self) -> i64 {
+        self.nanos
+    }
+
+    /// The amount of seconds since EPOCH, as floating point value.
+    ///
+    /// Note that this loses precision for large values.
+    pub fn to_seconds(&self) -> f64 {
+        Duration::from_nanos(self.nanos).as_secs_f64()
     }
 
     /// Return absolute distance in time between two timestamps.
-    pub fn distance(&self, other: &Self) -> f64 {
-        (self.amount - other.amount).abs()
+    pub fn distance(&self, other: &Self) -> Duration {
+        (self - other).abs()
+    }
+
+    /// Convert into a date and time.
+    pub fn to_datetime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let seconds = self.nanos.div_euclid(NANOS_PER_SECOND);
+        let nanos = self.nanos.rem_euclid(NANOS_PER_SECOND);
+        chrono::Utc.timestamp_opt(seconds, nanos as u32).single()
     }
 }
 
 impl TimeModifiers for TimeStamp {
     fn add_millis(&self, amount: isize) -> Self {
-        Self::new(self.amount + (amount as f64) * 1.0e-3)
+        self.clone() + Duration::from_millis(amount as i64)
     }
 
     fn add_nanos(&self, amount: isize) -> Self {
-        Self::new(self.amount + (amount as f64) * 1.0e-9)
+        self.clone() + Duration::from_nanos(amount as i64)
     }
 }
 
-impl PartialOrd for TimeStamp {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.amount.partial_cmp(&other.amount)
+impl From<f64> for TimeStamp {
+    fn from(seconds: f64) -> Self {
+        TimeStamp::new(seconds)
     }
 }
 
-impl std::ops::Sub<f64> for TimeStamp {
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for TimeStamp {
+    fn from(datetime: chrono::DateTime<Tz>) -> Self {
+        let seconds = datetime.timestamp();
+        let nanos = i64::from(datetime.timestamp_subsec_nanos());
+        TimeStamp::from_nanos(seconds * NANOS_PER_SECOND + nanos)
+    }
+}
+
+impl std::ops::Add<Duration> for TimeStamp {
     type Output = TimeStamp;
 
-    fn sub(self, other: f64) -> TimeStamp {
-        TimeStamp::new(self.amount - other)
+    fn add(self, other: Duration) -> TimeStamp {
+        TimeStamp::from_nanos(self.nanos.saturating_add(other.as_nanos()))
+    }
+}
+
+impl std::ops::Sub<Duration> for TimeStamp {
+    type Output = TimeStamp;
+
+    fn sub(self, other: Duration) -> TimeStamp {
+        TimeStamp::from_nanos(self.nanos.saturating_sub(other.as_nanos()))
+    }
+}
+
+impl std::ops::Sub for TimeStamp {
+    type Output = Duration;
+
+    fn sub(self, other: TimeStamp) -> Duration {
+        &self - &other
+    }
+}
+
+impl std::ops::Sub for &TimeStamp {
+    type Output = Duration;
+
+    fn sub(self, other: &TimeStamp) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(other.nanos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Duration, TimeStamp};
+
+    #[test]
+    fn nanosecond_resolution() {
+        // Some time in 2020, plus a single nanosecond:
+        let t1 = TimeStamp::from_seconds(1_600_000_000);
+        let t2 = t1.clone() + Duration::from_nanos(1);
+        assert!(t1 < t2);
+        assert_eq!(Duration::from_nanos(1), &t2 - &t1);
+        assert_eq!(t1, t2 - Duration::from_nanos(1));
+    }
+
+    #[test]
+    fn saturating_arithmetic() {
+        let max = TimeStamp::from_nanos(i64::MAX);
+        let min = TimeStamp::from_nanos(i64::MIN);
+        let day = Duration::from_secs(86_400);
+        assert_eq!(max, max.clone() + day);
+        assert_eq!(min, min.clone() - day);
+        assert_eq!(Duration::from_nanos(i64::MAX), &max - &min);
+        assert_eq!(Duration::from_nanos(i64::MIN), &min - &max);
+        assert_eq!(Duration::from_nanos(i64::MAX), min.distance(&max));
+    }
+
+    #[test]
+    fn float_conversions() {
+        assert_eq!(TimeStamp::from_nanos(1_500_000_000), TimeStamp::new(1.5));
+        assert_eq!(-2.25, TimeStamp::new(-2.25).to_seconds());
+        assert_eq!(1.6e9, TimeStamp::from_seconds(1_600_000_000).to_seconds());
+    }
+
+    #[test]
+    fn chrono_conversions() {
+        use chrono::TimeZone;
+        let datetime = chrono::Utc
+            .timestamp_opt(1_600_000_000, 123)
+            .single()
+            .unwrap();
+        let ts = TimeStamp::from(datetime);
+        assert_eq!(1_600_000_000_000_000_123, ts.nanos());
+        assert_eq!(Some(datetime), ts.to_datetime());
     }
 }
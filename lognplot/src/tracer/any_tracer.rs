@@ -1,6 +1,6 @@
 use super::{DbTracer, TcpTracer, Tracer};
 use crate::net::TcpClient;
-use crate::tsdb::TsDbHandle;
+use crate::tsdb::{LogRecord, TsDbHandle};
 use std::time::Instant;
 
 /// Application tracer with multiple data sinks.
@@ -47,4 +47,12 @@ impl Tracer for AnyTracer {
             AnyTracer::Void => {}
         }
     }
+
+    fn log_record(&self, name: &str, timestamp: Instant, record: LogRecord) {
+        match self {
+            AnyTracer::Net(t) => t.log_record(name, timestamp, record),
+            AnyTracer::Db(t) => t.log_record(name, timestamp, record),
+            AnyTracer::Void => {}
+        }
+    }
 }
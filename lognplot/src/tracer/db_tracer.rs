@@ -1,8 +1,8 @@
 //! Trace events straight into a database.
 
 use super::Tracer;
-use crate::time::TimeStamp;
-use crate::tsdb::{Observation, Sample, Text, TsDbHandle};
+use crate::time::{Duration, TimeStamp};
+use crate::tsdb::{LogRecord, Observation, Sample, Text, TsDbHandle};
 use std::time::Instant;
 
 /// A struct which allows recording
@@ -22,8 +22,7 @@ impl DbTracer {
 
     fn get_timestamp(&self, timestamp: Instant) -> TimeStamp {
         let elapsed = timestamp.duration_since(self.gui_start_instant);
-        let elapsed_seconds: f64 = elapsed.as_secs_f64();
-        TimeStamp::new(elapsed_seconds)
+        TimeStamp::from_nanos(0) + Duration::from(elapsed)
     }
 }
 
@@ -40,4 +39,10 @@ impl Tracer for DbTracer {
         let observation = Observation::new(timestamp, Text::new(text));
         self.db.add_text(name, observation);
     }
+
+    fn log_record(&self, name: &str, timestamp: Instant, record: LogRecord) {
+        let timestamp = self.get_timestamp(timestamp);
+        let observation = Observation::new(timestamp, record);
+        self.db.add_log(name, observation);
+    }
 }
@@ -0,0 +1,104 @@
+//! Forward the output of the `log` crate into a tracer.
+
+use super::Tracer;
+use crate::tsdb::LogRecord;
+use log::{LevelFilter, Metadata, Record, SetLoggerError};
+use std::time::Instant;
+
+/// A logger which sends all log records to a tracer.
+///
+/// Records emitted by lognplot itself are not forwarded, since
+/// these could be emitted while the tracer is busy.
+pub struct LogForwarder<T>
+where
+    T: Tracer,
+{
+    tracer: T,
+    name: String,
+    level: LevelFilter,
+}
+
+impl<T> LogForwarder<T>
+where
+    T: Tracer + Send + Sync + 'static,
+{
+    /// Create a logger which logs into the track with the given name.
+    pub fn new(tracer: T, name: &str) -> Self {
+        LogForwarder {
+            tracer,
+            name: name.to_owned(),
+            level: LevelFilter::Trace,
+        }
+    }
+
+    /// Only forward records up to the given level.
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Install this logger as the global logger.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl<T> log::Log for LogForwarder<T>
+where
+    T: Tracer + Send + Sync,
+{
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with("lognplot")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let mut log_record = LogRecord::new(
+                record.level().into(),
+                record.target().to_owned(),
+                record.args().to_string(),
+            );
+            if let Some(file) = record.file() {
+                log_record = log_record.with_field("file", file.to_owned());
+            }
+            if let Some(line) = record.line() {
+                log_record = log_record.with_field("line", line.to_string());
+            }
+            self.tracer
+                .log_record(&self.name, Instant::now(), log_record);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogForwarder;
+    use crate::tracer::AnyTracer;
+    use crate::tsdb::TsDb;
+    use log::Log;
+
+    #[test]
+    fn forward_records() {
+        let db = TsDb::default().into_handle();
+        let forwarder =
+            LogForwarder::new(AnyTracer::new_db(db.clone()), "log").level(log::LevelFilter::Info);
+
+        let record = |level, target| {
+            log::Record::builder()
+                .args(format_args!("motor started"))
+                .level(level)
+                .target(target)
+                .build()
+        };
+        forwarder.log(&record(log::Level::Info, "app::motor"));
+        forwarder.log(&record(log::Level::Debug, "app::motor"));
+        forwarder.log(&record(log::Level::Error, "lognplot::tsdb"));
+
+        let summary = db.quick_summary("log").unwrap();
+        assert_eq!(1, summary.count);
+        assert_eq!("INFO app::motor: motor started", summary.last_value());
+    }
+}
@@ -5,10 +5,12 @@
 
 mod any_tracer;
 mod db_tracer;
+mod log_forwarder;
 mod net_tracer;
 mod tracer;
 
 pub use any_tracer::AnyTracer;
 pub use db_tracer::DbTracer;
+pub use log_forwarder::LogForwarder;
 pub use net_tracer::TcpTracer;
 pub use tracer::Tracer;
@@ -2,6 +2,7 @@
 
 use super::Tracer;
 use crate::net::TcpClient;
+use crate::tsdb::LogRecord;
 use std::sync::Mutex;
 use std::time::Instant;
 
@@ -45,4 +46,17 @@ impl Tracer for TcpTracer {
             error!("Error sending text: {:?}", err);
         }
     }
+
+    fn log_record(&self, name: &str, timestamp: Instant, record: LogRecord) {
+        let elapsed = timestamp.duration_since(self.gui_start_instant);
+        let elapsed_seconds: f64 = elapsed.as_secs_f64();
+        if let Err(err) = self
+            .client
+            .lock()
+            .unwrap()
+            .send_log(name, elapsed_seconds, record)
+        {
+            error!("Error sending log record: {:?}", err);
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::tsdb::LogRecord;
 use std::time::Instant;
 
 /// Client trace interface.
@@ -9,4 +10,9 @@ pub trait Tracer {
 
     /// Log a text
     fn log_text(&self, name: &str, timestamp: Instant, text: String);
+
+    /// Log a log record
+    ///
+    /// Tracers which have no place for log records drop them.
+    fn log_record(&self, _name: &str, _timestamp: Instant, _record: LogRecord) {}
 }
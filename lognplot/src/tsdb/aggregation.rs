@@ -1,9 +1,10 @@
 use super::metrics::Metrics;
 use super::Observation;
 use crate::time::TimeSpan;
+use serde::{Deserialize, Serialize};
 
 /// An aggregation of observations of some type.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aggregation<V, M>
 where
     M: Metrics<V> + From<V>,
@@ -30,7 +31,8 @@ where
 {
     fn from(sample: Observation<V>) -> Self {
         let timespan = TimeSpan::new(sample.timestamp.clone(), sample.timestamp.clone());
-        let metrics = M::from(sample.value);
+        let mut metrics = M::from(sample.value);
+        metrics.created_at(&sample.timestamp);
         Aggregation::new(timespan, metrics, 1)
     }
 }
@@ -57,7 +59,7 @@ where
     fn from(reference: &Aggregation<V, M>) -> Self {
         let timespan = reference.timespan.clone();
         let metrics = reference.metrics.clone();
-        Aggregation::new(timespan, metrics, 1)
+        Aggregation::new(timespan, metrics, reference.count)
     }
 }
 
@@ -103,7 +105,7 @@ where
     }
 
     pub fn include_observation(&mut self, sample: &Observation<V>) {
-        self.metrics.update(&sample.value);
+        self.metrics.update_at(&sample.value, &sample.timestamp);
         self.count += 1;
 
         // Adjust timespan:
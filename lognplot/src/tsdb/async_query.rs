@@ -0,0 +1,157 @@
+//! Queries which run on worker threads, so that the caller is not blocked.
+//!
+//! This is used by the GUI, to keep responding to the user while large
+//! queries on many traces are running.
+
+use super::{QueryResult, SharedTrack};
+use futures::channel::oneshot;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Number of threads running queries.
+const QUERY_WORKERS: usize = 2;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads which run queries.
+///
+/// The threads are started on the first query.
+#[derive(Debug, Default)]
+pub struct QueryPool {
+    jobs: Mutex<Option<mpsc::Sender<Job>>>,
+}
+
+impl QueryPool {
+    /// Query a trace on a worker thread.
+    pub fn query(&self, track: SharedTrack, query: super::Query) -> AsyncQuery {
+        self.run(move || Some(track.read().unwrap().query(query)))
+    }
+
+    /// Run any other query on a worker thread.
+    ///
+    /// The query gives None when it has no result.
+    pub fn run<T, F>(&self, query: F) -> AsyncQuery<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Option<T> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job_cancelled = cancelled.clone();
+        self.spawn(Box::new(move || {
+            // Skip queries which were superseded while waiting:
+            if !job_cancelled.load(Ordering::Relaxed) {
+                if let Some(result) = query() {
+                    let _ = sender.send(result);
+                }
+            }
+        }));
+        AsyncQuery {
+            result: receiver,
+            cancelled,
+        }
+    }
+
+    fn spawn(&self, job: Job) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let sender = jobs.get_or_insert_with(start_workers);
+        if sender.send(job).is_err() {
+            error!("Query workers stopped");
+        }
+    }
+}
+
+fn start_workers() -> mpsc::Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for index in 0..QUERY_WORKERS {
+        let receiver = receiver.clone();
+        std::thread::Builder::new()
+            .name(format!("query-worker-{}", index))
+            .spawn(move || loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    // The pool was dropped.
+                    Err(_) => break,
+                }
+            })
+            .expect("Could not start query worker");
+    }
+    sender
+}
+
+/// The future result of a query running on a worker thread.
+///
+/// Resolves to None when the trace does not exist, or when the query
+/// was cancelled.
+#[derive(Debug)]
+pub struct AsyncQuery<T = QueryResult> {
+    result: oneshot::Receiver<T>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> AsyncQuery<T> {
+    /// A query of which the result is already known.
+    pub fn ready(result: Option<T>) -> Self {
+        let (sender, receiver) = oneshot::channel();
+        if let Some(result) = result {
+            let _ = sender.send(result);
+        }
+        AsyncQuery {
+            result: receiver,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Cancel the query.
+    ///
+    /// A query which is already running still completes, but its
+    /// result is dropped.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<T> Future for AsyncQuery<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.result).poll(cx) {
+            Poll::Ready(result) => {
+                if self.cancelled.load(Ordering::Relaxed) {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(result.ok())
+                }
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Keeps track of the latest query for something, such as a curve.
+///
+/// Each new query supersedes, and so cancels, the previous one.
+#[derive(Debug, Default)]
+pub struct QuerySlot {
+    latest: Option<Arc<AtomicBool>>,
+}
+
+impl QuerySlot {
+    pub fn supersede<T>(&mut self, query: &AsyncQuery<T>) {
+        if let Some(previous) = self.latest.replace(query.cancelled.clone()) {
+            previous.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Cancel the latest query, if any.
+    pub fn cancel(&mut self) {
+        if let Some(latest) = self.latest.take() {
+            latest.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -2,71 +2,163 @@
 //!
 //! The idea is to create leaf nodes and intermediate nodes.
 //! Leaf and intermediate nodes can have multiple child nodes.
+//!
+//! Full leaves, to which no more observations are appended, are
+//! compressed when the type of observations supports this.
+//!
+//! Child nodes are shared between clones of a tree, and copied
+//! only when they are changed. This makes a clone of a tree cheap.
 
+use super::compress::Compress;
+use super::heap_size::HeapSize;
 use super::metrics::Metrics;
 use super::{Aggregation, Observation, RangeQueryResult};
-use crate::time::TimeSpan;
+use crate::time::{TimeSpan, TimeStamp};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::mem::size_of;
+use std::sync::Arc;
 
-/// This is the intermediate level fanout ratio.
+/// This is the default intermediate level fanout ratio.
 /// A higher number yields less overhead (zoom levels)
-const INTERMEDIATE_CHUNK_SIZE: usize = 4;
+const INTERMEDIATE_CHUNK_SIZE: usize = 8;
+
+/// This constant defines the default leaf fanout ratio.
+/// Each leaf contains maximum this number of values.
+/// Leaves are compressed as a whole, so small leaves spend
+/// most of their memory on metrics instead of observations.
+const LEAF_CHUNK_SIZE: usize = 32;
+
+/// The maximum number of children of the nodes in a b-tree.
+///
+/// Larger nodes need less memory and time per observation, but
+/// a query gets more data than requested when zooming in.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fanout {
+    /// Maximum number of observations in a leaf node.
+    pub leaf: usize,
+
+    /// Maximum number of child nodes of an intermediate node.
+    pub intermediate: usize,
+}
 
-/// This constant defines the fanout ratio.
-/// Each leaf contains maximum this number of values
-/// Also, each intermediate node also contains this maximum number
-/// of subchunks.
-const LEAF_CHUNK_SIZE: usize = 8;
+impl Fanout {
+    /// Create a fanout, of at least two children per node.
+    pub fn new(leaf: usize, intermediate: usize) -> Self {
+        Fanout {
+            leaf: leaf.max(2),
+            intermediate: intermediate.max(2),
+        }
+    }
+}
+
+impl Default for Fanout {
+    fn default() -> Self {
+        Fanout::new(LEAF_CHUNK_SIZE, INTERMEDIATE_CHUNK_SIZE)
+    }
+}
 
 /// This implements a b-tree structure.
 ///
 /// The tree structure supports fast lookup
 /// of time ranges.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Btree<V, M>
 where
     M: Metrics<V> + From<V>,
 {
     root: Node<V, M>,
+    fanout: Fanout,
 }
 
 /// Create an empty b-tree
 impl<V, M> Default for Btree<V, M>
 where
-    M: Metrics<V> + From<V> + Clone,
-    V: Clone,
+    M: Metrics<V> + From<V> + Clone + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
     fn default() -> Self {
-        let root = Node::new_leaf();
-        Btree { root }
+        Btree::new(Fanout::default())
     }
 }
 
 impl<V, M> Btree<V, M>
 where
-    M: Metrics<V> + From<V> + Clone,
-    V: Clone,
+    M: Metrics<V> + From<V> + Clone + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
+    /// Create an empty tree with the given fanout.
+    pub fn new(fanout: Fanout) -> Self {
+        let root = Node::new_leaf(fanout);
+        Btree { root, fanout }
+    }
+
+    pub fn fanout(&self) -> Fanout {
+        self.fanout
+    }
+
+    /// The amount of memory in bytes used by the nodes of this tree.
+    pub fn memory(&self) -> usize {
+        size_of::<Node<V, M>>() + self.root.memory()
+    }
+
     /// Append a sample to the tree
     pub fn append_sample(&mut self, observation: Observation<V>) {
         // Strategy, traverse down, until a leaf, and split on the way back upwards if
         // required.
         // Find proper chunk, or create one if required.
 
-        let optionally_root_split = self.root.append_observation(observation);
+        let optionally_root_split = self.root.append_observation(observation, self.fanout);
+        self.grow(optionally_root_split);
+    }
 
+    /// Append a batch of samples, sorted by time, to the tree.
+    ///
+    /// The samples must not be older than the last sample in the tree.
+    /// After filling up the last leaf, full leaves are created at once
+    /// and added to the tree, which results in the same tree as appending
+    /// the samples one by one.
+    pub fn append_samples(&mut self, observations: Vec<Observation<V>>) {
+        let room = self.fanout.leaf - self.root.last_leaf_len();
+        let mut observations = observations.into_iter();
+        for observation in observations.by_ref().take(room) {
+            self.append_sample(observation);
+        }
+
+        loop {
+            let chunk: Vec<Observation<V>> = observations.by_ref().take(self.fanout.leaf).collect();
+            if chunk.is_empty() {
+                break;
+            }
+
+            let leaf = LeafNode::from_observations(chunk);
+            let optionally_root_split = self.root.append_leaf(leaf, self.fanout);
+            self.grow(optionally_root_split);
+        }
+    }
+
+    /// Put a new root above the old root and its new sibling, if any.
+    fn grow(&mut self, optionally_root_split: Option<Node<V, M>>) {
         if let Some(root_sibling) = optionally_root_split {
-            let new_root = Node::new_intermediate();
+            let new_root = Node::new_intermediate(self.fanout);
             let old_root = std::mem::replace(&mut self.root, new_root);
-            self.root.add_child(old_root);
-            self.root.add_child(root_sibling);
+            self.root.add_child(old_root, self.fanout);
+            self.root.add_child(root_sibling, self.fanout);
         }
     }
 
-    /// Bulk import samples.
-    pub fn append_samples(&mut self, samples: Vec<Observation<V>>) {
-        for sample in samples {
-            self.append_sample(sample);
-        }
+    /// Insert a sample which is older than the last sample in the tree.
+    ///
+    /// The sample is inserted in the proper leaf, which is split when
+    /// full. The metrics on the path to the leaf are updated.
+    ///
+    /// Returns false when the sample falls into a pruned part of the tree,
+    /// in which case only the metrics are updated.
+    pub fn insert_sample(&mut self, observation: Observation<V>) -> bool {
+        let (optionally_root_split, stored) =
+            self.root.insert_observation(observation, self.fanout);
+        self.grow(optionally_root_split);
+        stored
     }
 
     /// Query the tree for some data.
@@ -76,7 +168,7 @@ where
     pub fn query_range(&self, timespan: &TimeSpan, max_items: usize) -> RangeQueryResult<V, M> {
         let mut selection = self.root.select_range(timespan);
 
-        while selection.can_enhance() && selection.enhanced_size() < max_items {
+        while selection.can_enhance() && selection.enhanced_size(self.fanout) < max_items {
             selection = selection.enhance(timespan);
         }
 
@@ -104,7 +196,8 @@ where
                 RangeSelectionResult::Nodes(nodes) => {
                     for node in nodes {
                         if let Some(aggregation) = node.metrics() {
-                            if timespan.covers(&aggregation.timespan) {
+                            // Pruned nodes cannot be split up, so take them as a whole.
+                            if timespan.covers(&aggregation.timespan) || node.is_pruned() {
                                 selected_nodes.push(aggregation);
                             } else {
                                 partially_selected_nodes.push(node);
@@ -113,10 +206,7 @@ where
                     }
                 }
                 RangeSelectionResult::Observations(observations) => {
-                    for observation in observations {
-                        selected_observations.push(observation.clone());
-                    }
-                    // selected_observations.extend(observations.iter().map(|o| o.clone()).collect());
+                    selected_observations.extend(observations.into_iter().map(Cow::into_owned));
                 }
             };
         }
@@ -139,6 +229,14 @@ where
         Aggregation::from_aggregations(&all_aggregations)
     }
 
+    /// Visit the data in the given time span, see `RangeVisitor`.
+    pub fn visit_range<R>(&self, timespan: &TimeSpan, visitor: &mut R)
+    where
+        R: RangeVisitor<V, M>,
+    {
+        self.root.visit_range(timespan, visitor)
+    }
+
     /// Get a summary about all data in this tree.
     pub fn summary(&self) -> Option<Aggregation<V, M>> {
         self.root.metrics()
@@ -148,12 +246,139 @@ where
     pub fn to_vec(&self) -> Vec<Observation<V>> {
         self.root.to_vec()
     }
+
+    /// Find the last retained observation at or before the given timestamp.
+    pub fn last_until(&self, timestamp: &TimeStamp) -> Option<Observation<V>> {
+        self.root.last_until(timestamp)
+    }
+
+    /// Find the first retained observation at or after the given timestamp.
+    pub fn first_from(&self, timestamp: &TimeStamp) -> Option<Observation<V>> {
+        self.root.first_from(timestamp)
+    }
+
+    /// Timestamp of the oldest observation which was not pruned.
+    pub fn first_retained_timestamp(&self) -> Option<TimeStamp> {
+        self.root.first_retained_timestamp().cloned()
+    }
+
+    /// Get the metrics of the oldest leaf which can still be pruned.
+    ///
+    /// The most recent leaf is never pruned, since new observations
+    /// are appended to it.
+    pub fn first_leaf_metrics(&self) -> Option<Aggregation<V, M>> {
+        self.root.first_leaf_metrics(true)
+    }
+
+    /// Drop the observations of the oldest leaf, but keep its metrics.
+    ///
+    /// Sub trees which are pruned completely are replaced by their
+    /// aggregated metrics. This way, the data can still be viewed
+    /// in a zoomed out fashion.
+    ///
+    /// Returns the amount of dropped observations.
+    pub fn prune_first_leaf(&mut self) -> usize {
+        self.root.prune_first_leaf(true)
+    }
+
+    /// Take an image of the shape of this tree, including all
+    /// aggregated metrics, but without the observations.
+    pub fn image(&self) -> NodeImage<V, M> {
+        self.root.image()
+    }
+
+    /// Rebuild a tree from an image and the observations in the tree.
+    ///
+    /// The observations are taken from the iterator in order, and
+    /// must be at least as many as the image refers to. The image
+    /// must fit the given fanout.
+    pub fn from_image<I>(
+        image: NodeImage<V, M>,
+        observations: &mut I,
+        fanout: Fanout,
+    ) -> Option<Self>
+    where
+        I: Iterator<Item = Observation<V>>,
+    {
+        let mut root = Node::from_image(image, observations, fanout)?;
+        root.compress_leaves(true);
+        Some(Btree { root, fanout })
+    }
+}
+
+/// The shape of a (sub)tree together with the metrics of each node.
+///
+/// This can be stored next to the raw observations, so that a tree
+/// can be restored without re-calculating all metrics.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum NodeImage<V, M>
+where
+    M: Metrics<V> + From<V>,
+{
+    Intermediate {
+        children: Vec<NodeImage<V, M>>,
+        metrics: Option<Aggregation<V, M>>,
+    },
+    Leaf {
+        size: usize,
+        metrics: Option<Aggregation<V, M>>,
+    },
+    Pruned {
+        size: usize,
+        metrics: Aggregation<V, M>,
+    },
+}
+
+impl<V, M> NodeImage<V, M>
+where
+    M: Metrics<V> + From<V>,
+{
+    /// The amount of observations referred to by this image.
+    pub fn len(&self) -> usize {
+        match self {
+            NodeImage::Intermediate { children, .. } => children.iter().map(|c| c.len()).sum(),
+            NodeImage::Leaf { size, .. } | NodeImage::Pruned { size, .. } => *size,
+        }
+    }
+
+    /// The amount of observations which were pruned.
+    pub fn pruned_len(&self) -> usize {
+        match self {
+            NodeImage::Intermediate { children, .. } => {
+                children.iter().map(|c| c.pruned_len()).sum()
+            }
+            NodeImage::Leaf { .. } => 0,
+            NodeImage::Pruned { size, .. } => *size,
+        }
+    }
+}
+
+/// Receives the data in a time span of the tree.
+///
+/// Sub trees which lie completely inside the time span are offered
+/// as a whole, so that the visitor can use their metrics instead of
+/// going through all observations.
+pub trait RangeVisitor<V, M>
+where
+    M: Metrics<V> + From<V>,
+{
+    /// Take a sub tree as a whole. Return false to visit its parts instead.
+    fn aggregation(&mut self, aggregation: &Aggregation<V, M>) -> bool;
+
+    /// Take a sub tree of which the observations were dropped.
+    ///
+    /// Such a sub tree cannot be split, so it is taken as a whole,
+    /// even when it lies only partially inside the time span.
+    fn pruned(&mut self, aggregation: &Aggregation<V, M>);
+
+    /// Take a single observation.
+    fn observation(&mut self, observation: &Observation<V>);
 }
 
 /// This is a sort of B+ tree data structure
 /// to store a sequence of sample along with some
 /// metrics about those samples.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum Node<V, M>
 where
     M: Metrics<V> + From<V>,
@@ -163,31 +388,42 @@ where
 
     /// A leaf chunk with some samples in it.
     Leaf(LeafNode<V, M>),
+
+    /// A full leaf chunk, with its samples in compressed form.
+    Compressed(CompressedLeafNode<V, M>),
+
+    /// A chunk of which the samples were dropped, only the
+    /// aggregated metrics are left.
+    Pruned(Aggregation<V, M>),
     // TODO: in future support on disk node?
 }
 
 impl<V, M> Default for Node<V, M>
 where
-    M: Metrics<V> + Clone + From<V>,
-    V: Clone,
+    M: Metrics<V> + Clone + From<V> + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
     fn default() -> Self {
-        Node::new_leaf()
+        Node::new_leaf(Fanout::default())
     }
 }
 
 /// Intermediate node
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct InternalNode<V, M>
 where
     M: Metrics<V> + From<V>,
 {
-    children: Vec<Node<V, M>>,
+    children: Vec<Arc<Node<V, M>>>,
     metrics: Option<Aggregation<V, M>>,
+
+    /// Memory used by the child nodes, kept up to date so that the
+    /// memory of a tree is known without visiting all of its nodes.
+    children_memory: usize,
 }
 
 /// Leaf node type
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct LeafNode<V, M>
 where
     M: Metrics<V> + From<V>,
@@ -196,41 +432,273 @@ where
     metrics: Option<Aggregation<V, M>>,
 }
 
+/// Leaf node with compressed observations
+#[derive(Clone, Debug)]
+struct CompressedLeafNode<V, M>
+where
+    M: Metrics<V> + From<V>,
+{
+    data: Vec<u8>,
+    size: usize,
+
+    /// Observations which were inserted later, in order of arrival.
+    ///
+    /// These are kept apart, so that a late observation does not
+    /// require compressing the whole leaf again.
+    inserted: Vec<Observation<V>>,
+
+    metrics: Aggregation<V, M>,
+}
+
 impl<V, M> Node<V, M>
 where
-    M: Metrics<V> + Clone + From<V>,
-    V: Clone,
+    M: Metrics<V> + Clone + From<V> + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
-    fn new_intermediate() -> Self {
-        Node::Intermediate(InternalNode::new())
+    fn new_intermediate(fanout: Fanout) -> Self {
+        Node::Intermediate(InternalNode::new(fanout))
     }
 
-    fn new_leaf() -> Self {
-        Node::Leaf(LeafNode::new())
+    fn new_leaf(fanout: Fanout) -> Self {
+        Node::Leaf(LeafNode::new(fanout))
     }
 
     /// Test if this chunk is full
-    fn _is_full(&self) -> bool {
+    fn _is_full(&self, fanout: Fanout) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.is_full(fanout),
+            Node::Intermediate(internal) => internal.is_full(fanout),
+            Node::Compressed(..) | Node::Pruned(..) => true,
+        }
+    }
+
+    fn is_pruned(&self) -> bool {
+        matches!(self, Node::Pruned(..))
+    }
+
+    fn is_leaf(&self) -> bool {
+        matches!(self, Node::Leaf(..) | Node::Compressed(..))
+    }
+
+    /// The memory used by this node on the heap, including its children.
+    fn memory(&self) -> usize {
+        match self {
+            Node::Intermediate(internal) => internal.memory(),
+            Node::Leaf(leaf) => leaf.memory(),
+            Node::Compressed(leaf) => leaf.memory(),
+            Node::Pruned(aggregation) => aggregation.heap_size(),
+        }
+    }
+
+    /// Compress a leaf, to which no more observations will be appended.
+    fn compress(&mut self) {
+        if let Node::Leaf(leaf) = self {
+            if leaf.metrics.is_some() {
+                if let Some(data) = V::compress(&leaf.observations) {
+                    *self = Node::Compressed(CompressedLeafNode {
+                        data,
+                        size: leaf.observations.len(),
+                        inserted: vec![],
+                        metrics: leaf.metrics.take().unwrap(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Compress all leaves, except the last leaf.
+    fn compress_leaves(&mut self, is_last: bool) {
         match self {
-            Node::Leaf(leaf) => leaf.is_full(),
-            Node::Intermediate(internal) => internal.is_full(),
+            Node::Intermediate(internal) => {
+                let count = internal.children.len();
+                for index in 0..count {
+                    internal.update_child(index, |child| {
+                        child.compress_leaves(is_last && index + 1 == count)
+                    });
+                }
+            }
+            Node::Leaf(..) if !is_last => self.compress(),
+            _ => {}
         }
     }
 
-    fn add_child(&mut self, child: Node<V, M>) {
+    fn add_child(&mut self, child: Node<V, M>, fanout: Fanout) {
         match self {
-            Node::Intermediate(internal_node) => internal_node.add_child(child),
+            Node::Intermediate(internal_node) => internal_node.add_child(child, fanout),
             _x => panic!("Wrong node type to add a child to"),
         }
     }
 
+    /// Timestamp of the oldest observation in this node.
+    fn first_timestamp(&self) -> Option<&TimeStamp> {
+        match self {
+            Node::Intermediate(internal) => internal.children.first()?.first_timestamp(),
+            Node::Leaf(leaf) => leaf.observations.first().map(|o| &o.timestamp),
+            Node::Compressed(leaf) => Some(&leaf.metrics.timespan.start),
+            Node::Pruned(aggregation) => Some(&aggregation.timespan.start),
+        }
+    }
+
+    /// Index of the last child which starts at or before the given timestamp.
+    fn child_index_at(internal: &InternalNode<V, M>, timestamp: &TimeStamp) -> Option<usize> {
+        internal
+            .children
+            .iter()
+            .rposition(|c| c.first_timestamp().map_or(false, |t| t <= timestamp))
+    }
+
+    fn last_until(&self, timestamp: &TimeStamp) -> Option<Observation<V>> {
+        match self {
+            Node::Intermediate(internal) => {
+                let index = Self::child_index_at(internal, timestamp)?;
+                internal.children[..=index]
+                    .iter()
+                    .rev()
+                    .find_map(|c| c.last_until(timestamp))
+            }
+            Node::Leaf(leaf) => leaf
+                .observations
+                .iter()
+                .rev()
+                .find(|o| o.timestamp <= *timestamp)
+                .cloned(),
+            Node::Compressed(leaf) => leaf
+                .observations()
+                .into_iter()
+                .rev()
+                .find(|o| o.timestamp <= *timestamp),
+            Node::Pruned(..) => None,
+        }
+    }
+
+    fn first_from(&self, timestamp: &TimeStamp) -> Option<Observation<V>> {
+        match self {
+            Node::Intermediate(internal) => {
+                let index = Self::child_index_at(internal, timestamp).unwrap_or(0);
+                internal.children[index..]
+                    .iter()
+                    .find_map(|c| c.first_from(timestamp))
+            }
+            Node::Leaf(leaf) => leaf
+                .observations
+                .iter()
+                .find(|o| o.timestamp >= *timestamp)
+                .cloned(),
+            Node::Compressed(leaf) => leaf
+                .observations()
+                .into_iter()
+                .find(|o| o.timestamp >= *timestamp),
+            Node::Pruned(..) => None,
+        }
+    }
+
+    /// Timestamp of the oldest observation in this node which was not pruned.
+    fn first_retained_timestamp(&self) -> Option<&TimeStamp> {
+        match self {
+            Node::Intermediate(internal) => internal
+                .children
+                .iter()
+                .find(|c| !c.is_pruned())?
+                .first_retained_timestamp(),
+            Node::Leaf(leaf) => leaf.observations.first().map(|o| &o.timestamp),
+            Node::Compressed(leaf) => Some(&leaf.metrics.timespan.start),
+            Node::Pruned(..) => None,
+        }
+    }
+
+    /// Insert an observation somewhere in this node.
+    ///
+    /// Returns a new sibling when this node was split, and whether
+    /// the observation was stored or only included in pruned metrics.
+    fn insert_observation(
+        &mut self,
+        observation: Observation<V>,
+        fanout: Fanout,
+    ) -> (Option<Node<V, M>>, bool) {
+        match self {
+            Node::Intermediate(internal_node) => {
+                let (split, stored) = internal_node.insert_observation(observation, fanout);
+                (split.map(Node::Intermediate), stored)
+            }
+            Node::Leaf(leaf_node) => (
+                leaf_node
+                    .insert_observation(observation, fanout)
+                    .map(Node::Leaf),
+                true,
+            ),
+            Node::Compressed(leaf) => {
+                if leaf.len() < fanout.leaf {
+                    leaf.metrics.include_observation(&observation);
+                    leaf.inserted.push(observation);
+                    (None, true)
+                } else {
+                    // Split in two halves, which both have room for
+                    // inserted observations again:
+                    let mut leaf_node = LeafNode::from_observations(leaf.observations());
+                    let new_leaf = leaf_node.insert_observation(observation, fanout);
+                    *self = Node::Leaf(leaf_node);
+                    self.compress();
+                    let split = new_leaf.map(|new_leaf| {
+                        let mut new_node = Node::Leaf(new_leaf);
+                        new_node.compress();
+                        new_node
+                    });
+                    (split, true)
+                }
+            }
+            Node::Pruned(aggregation) => {
+                aggregation.include_observation(&observation);
+                (None, false)
+            }
+        }
+    }
+
     /// The append observation to operation!
-    fn append_observation(&mut self, observation: Observation<V>) -> Option<Node<V, M>> {
+    fn append_observation(
+        &mut self,
+        observation: Observation<V>,
+        fanout: Fanout,
+    ) -> Option<Node<V, M>> {
+        match self {
+            Node::Intermediate(internal_node) => internal_node
+                .append_observation(observation, fanout)
+                .map(Node::Intermediate),
+            Node::Leaf(leaf_node) => {
+                let new_leaf = leaf_node.append_observation(observation, fanout)?;
+                self.compress();
+                Some(Node::Leaf(new_leaf))
+            }
+            Node::Compressed(..) => panic!("Cannot append to a compressed node"),
+            Node::Pruned(..) => panic!("Cannot append to a pruned node"),
+        }
+    }
+
+    /// Append a leaf after the last leaf in this node.
+    fn append_leaf(&mut self, leaf: LeafNode<V, M>, fanout: Fanout) -> Option<Node<V, M>> {
         match self {
             Node::Intermediate(internal_node) => internal_node
-                .append_observation(observation)
+                .append_leaf(leaf, fanout)
                 .map(Node::Intermediate),
-            Node::Leaf(leaf_node) => leaf_node.append_observation(observation).map(Node::Leaf),
+            Node::Leaf(..) => {
+                self.compress();
+                Some(Node::Leaf(leaf))
+            }
+            Node::Compressed(..) => panic!("Cannot append to a compressed node"),
+            Node::Pruned(..) => panic!("Cannot append to a pruned node"),
+        }
+    }
+
+    /// The number of observations in the last leaf of this node.
+    fn last_leaf_len(&self) -> usize {
+        match self {
+            Node::Intermediate(internal) => internal
+                .children
+                .last()
+                .map_or(0, |child| child.last_leaf_len()),
+            Node::Leaf(leaf) => leaf.observations.len(),
+            Node::Compressed(..) | Node::Pruned(..) => {
+                panic!("The last leaf is never compressed or pruned")
+            }
         }
     }
 
@@ -239,6 +707,10 @@ where
         match self {
             Node::Intermediate(internal) => RangeSelectionResult::Nodes(internal.select_all()),
             Node::Leaf(leaf) => RangeSelectionResult::Observations(leaf.select_all()),
+            Node::Compressed(leaf) => RangeSelectionResult::Observations(
+                leaf.observations().into_iter().map(Cow::Owned).collect(),
+            ),
+            Node::Pruned(..) => RangeSelectionResult::Nodes(vec![self]),
         }
     }
 
@@ -249,6 +721,74 @@ where
                 RangeSelectionResult::Nodes(internal.select_range(timespan))
             }
             Node::Leaf(leaf) => RangeSelectionResult::Observations(leaf.select_range(timespan)),
+            Node::Compressed(leaf) => RangeSelectionResult::Observations(
+                leaf.observations()
+                    .into_iter()
+                    .filter(|o| timespan.contains(&o.timestamp))
+                    .map(Cow::Owned)
+                    .collect(),
+            ),
+            Node::Pruned(..) => RangeSelectionResult::Nodes(vec![self]),
+        }
+    }
+
+    fn visit_range<R>(&self, timespan: &TimeSpan, visitor: &mut R)
+    where
+        R: RangeVisitor<V, M>,
+    {
+        match self {
+            Node::Intermediate(internal) => {
+                if !Self::visit_whole(internal.metrics.as_ref(), timespan, visitor) {
+                    for child in &internal.children {
+                        child.visit_range(timespan, visitor);
+                    }
+                }
+            }
+            Node::Leaf(leaf) => {
+                if !Self::visit_whole(leaf.metrics.as_ref(), timespan, visitor) {
+                    for observation in &leaf.observations {
+                        if timespan.contains(&observation.timestamp) {
+                            visitor.observation(observation);
+                        }
+                    }
+                }
+            }
+            Node::Compressed(leaf) => {
+                if !Self::visit_whole(Some(&leaf.metrics), timespan, visitor) {
+                    for observation in leaf.observations() {
+                        if timespan.contains(&observation.timestamp) {
+                            visitor.observation(&observation);
+                        }
+                    }
+                }
+            }
+            Node::Pruned(aggregation) => {
+                if aggregation.timespan.overlap(timespan) {
+                    visitor.pruned(aggregation);
+                }
+            }
+        }
+    }
+
+    /// Offer the metrics of a node as a whole to the visitor.
+    ///
+    /// Returns true when the node needs no further visiting, because the
+    /// visitor took it or it lies outside the time span. Nodes without
+    /// pre-calculated metrics are always visited part by part.
+    fn visit_whole<R>(
+        metrics: Option<&Aggregation<V, M>>,
+        timespan: &TimeSpan,
+        visitor: &mut R,
+    ) -> bool
+    where
+        R: RangeVisitor<V, M>,
+    {
+        match metrics {
+            Some(aggregation) if !aggregation.timespan.overlap(timespan) => true,
+            Some(aggregation) if timespan.covers(&aggregation.timespan) => {
+                visitor.aggregation(aggregation)
+            }
+            _ => false,
         }
     }
 
@@ -258,6 +798,8 @@ where
         match self {
             Node::Intermediate(internal) => internal.to_vec(),
             Node::Leaf(leaf) => leaf.to_vec(),
+            Node::Compressed(leaf) => leaf.observations(),
+            Node::Pruned(..) => vec![],
         }
     }
 
@@ -265,24 +807,155 @@ where
     fn metrics(&self) -> Option<Aggregation<V, M>> {
         match self {
             Node::Leaf(leaf) => leaf.metrics(),
+            Node::Compressed(leaf) => Some(leaf.metrics.clone()),
             Node::Intermediate(internal) => internal.metrics(),
+            Node::Pruned(aggregation) => Some(aggregation.clone()),
+        }
+    }
+
+    /// Get the metrics of the first leaf with observations.
+    ///
+    /// The `is_last` flag indicates that this node is the last node
+    /// on its level in the tree.
+    fn first_leaf_metrics(&self, is_last: bool) -> Option<Aggregation<V, M>> {
+        match self {
+            Node::Intermediate(internal) => {
+                let index = internal.children.iter().position(|c| !c.is_pruned())?;
+                let is_last = is_last && index + 1 == internal.children.len();
+                internal.children[index].first_leaf_metrics(is_last)
+            }
+            Node::Leaf(leaf) => {
+                if is_last {
+                    None
+                } else {
+                    leaf.metrics()
+                }
+            }
+            Node::Compressed(leaf) => Some(leaf.metrics.clone()),
+            Node::Pruned(..) => None,
+        }
+    }
+
+    /// Prune the first leaf with observations.
+    fn prune_first_leaf(&mut self, is_last: bool) -> usize {
+        match self {
+            Node::Intermediate(internal) => {
+                if let Some(index) = internal.children.iter().position(|c| !c.is_pruned()) {
+                    let child_is_last = is_last && index + 1 == internal.children.len();
+                    let dropped =
+                        internal.update_child(index, |child| child.prune_first_leaf(child_is_last));
+
+                    if !is_last && internal.children.iter().all(|c| c.is_pruned()) {
+                        // Replace this node by a single pruned node.
+                        let metrics = internal.metrics().expect("Pruned nodes have metrics");
+                        *self = Node::Pruned(metrics);
+                    }
+
+                    dropped
+                } else {
+                    0
+                }
+            }
+            Node::Leaf(leaf) => {
+                if is_last {
+                    0
+                } else if let Some(metrics) = leaf.metrics() {
+                    let dropped = leaf.observations.len();
+                    *self = Node::Pruned(metrics);
+                    dropped
+                } else {
+                    0
+                }
+            }
+            Node::Compressed(leaf) => {
+                let dropped = leaf.len();
+                let metrics = leaf.metrics.clone();
+                *self = Node::Pruned(metrics);
+                dropped
+            }
+            Node::Pruned(..) => 0,
+        }
+    }
+
+    fn image(&self) -> NodeImage<V, M> {
+        match self {
+            Node::Intermediate(internal) => NodeImage::Intermediate {
+                children: internal.children.iter().map(|c| c.image()).collect(),
+                metrics: internal.metrics.clone(),
+            },
+            Node::Leaf(leaf) => NodeImage::Leaf {
+                size: leaf.observations.len(),
+                metrics: leaf.metrics.clone(),
+            },
+            Node::Compressed(leaf) => NodeImage::Leaf {
+                size: leaf.len(),
+                metrics: Some(leaf.metrics.clone()),
+            },
+            Node::Pruned(aggregation) => NodeImage::Pruned {
+                size: aggregation.count,
+                metrics: aggregation.clone(),
+            },
+        }
+    }
+
+    fn from_image<I>(image: NodeImage<V, M>, observations: &mut I, fanout: Fanout) -> Option<Self>
+    where
+        I: Iterator<Item = Observation<V>>,
+    {
+        match image {
+            NodeImage::Intermediate { children, metrics } => {
+                let mut internal = InternalNode::new(fanout);
+                for child in children {
+                    if internal.is_full(fanout) {
+                        return None;
+                    }
+                    internal.add_child(Node::from_image(child, observations, fanout)?, fanout);
+                }
+                internal.metrics = metrics;
+                Some(Node::Intermediate(internal))
+            }
+            NodeImage::Leaf { size, metrics } => {
+                if size > fanout.leaf {
+                    return None;
+                }
+                let mut leaf = LeafNode::new(fanout);
+                leaf.observations.extend(observations.take(size));
+                if leaf.observations.len() == size {
+                    leaf.metrics = metrics;
+                    Some(Node::Leaf(leaf))
+                } else {
+                    None
+                }
+            }
+            NodeImage::Pruned { size, metrics } => {
+                // Skip the observations which were pruned.
+                if observations.take(size).count() == size {
+                    Some(Node::Pruned(metrics))
+                } else {
+                    None
+                }
+            }
         }
     }
 }
 
 /// The result of selecting a time range on a node.
+///
+/// Observations are borrowed from the tree, except for those which
+/// were decompressed.
 enum RangeSelectionResult<'t, V, M>
 where
     M: Metrics<V> + From<V>,
+    V: Clone,
 {
     Nodes(Vec<&'t Node<V, M>>),
-    Observations(Vec<&'t Observation<V>>),
+    Observations(Vec<Cow<'t, Observation<V>>>),
 }
 
 impl<'t, V, M> RangeSelectionResult<'t, V, M>
 where
-    M: Metrics<V> + From<V> + Clone,
-    V: Clone,
+    M: Metrics<V> + From<V> + Clone + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
     // fn len(&self) -> usize {
     //     match self {
@@ -299,28 +972,37 @@ where
     // }
 
     /// Test if we can enhance this selection result any further.
+    ///
+    /// Pruned nodes cannot be enhanced, so we stop when only pruned
+    /// nodes are left, or when the other nodes would turn into
+    /// observations.
     fn can_enhance(&self) -> bool {
         match self {
-            RangeSelectionResult::Nodes(nodes) => !nodes.is_empty(),
+            RangeSelectionResult::Nodes(nodes) => {
+                let has_pruned = nodes.iter().any(|n| n.is_pruned());
+                let has_leaves = nodes.iter().any(|n| n.is_leaf());
+                let all_pruned = nodes.iter().all(|n| n.is_pruned());
+                if nodes.is_empty() || all_pruned {
+                    false
+                } else {
+                    !(has_pruned && has_leaves)
+                }
+            }
             RangeSelectionResult::Observations(_) => false,
         }
     }
 
     /// Give an estimate how many results we could get when we enhance.
-    fn enhanced_size(&self) -> usize {
+    fn enhanced_size(&self, fanout: Fanout) -> usize {
         match self {
-            RangeSelectionResult::Nodes(nodes) => {
-                if nodes.is_empty() {
-                    0
-                } else {
-                    let first_node: &Node<V, M> = nodes.first().expect("A single item");
-                    let worst_case_child_count = match first_node {
-                        Node::Intermediate(..) => INTERMEDIATE_CHUNK_SIZE,
-                        Node::Leaf(..) => LEAF_CHUNK_SIZE,
-                    };
-                    nodes.len() * worst_case_child_count
-                }
-            }
+            RangeSelectionResult::Nodes(nodes) => nodes
+                .iter()
+                .map(|node| match node {
+                    Node::Intermediate(..) => fanout.intermediate,
+                    Node::Leaf(..) | Node::Compressed(..) => fanout.leaf,
+                    Node::Pruned(..) => 1,
+                })
+                .sum(),
             RangeSelectionResult::Observations(observations) => observations.len(),
         }
     }
@@ -382,27 +1064,48 @@ where
             RangeSelectionResult::Nodes(nodes) => RangeQueryResult::Aggregations(
                 nodes.into_iter().map(|n| n.metrics().unwrap()).collect(),
             ),
-            RangeSelectionResult::Observations(observations) => {
-                RangeQueryResult::Observations(observations.into_iter().cloned().collect())
-            }
+            RangeSelectionResult::Observations(observations) => RangeQueryResult::Observations(
+                observations.into_iter().map(Cow::into_owned).collect(),
+            ),
         }
     }
 }
 
 impl<V, M> InternalNode<V, M>
 where
-    M: Metrics<V> + Clone + From<V>,
-    V: Clone,
+    M: Metrics<V> + Clone + From<V> + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
-    fn new() -> Self {
+    fn new(fanout: Fanout) -> Self {
         InternalNode {
-            children: Vec::with_capacity(INTERMEDIATE_CHUNK_SIZE),
+            children: Vec::with_capacity(fanout.intermediate),
             metrics: Default::default(),
+            children_memory: 0,
         }
     }
 
-    fn is_full(&self) -> bool {
-        self.children.len() >= INTERMEDIATE_CHUNK_SIZE
+    /// The memory used by this node and its children.
+    fn memory(&self) -> usize {
+        self.children.capacity() * size_of::<Arc<Node<V, M>>>()
+            + self.children_memory
+            + self.metrics.as_ref().map_or(0, HeapSize::heap_size)
+    }
+
+    /// The memory used by a child node, in its shared allocation
+    /// next to the reference counts.
+    fn child_memory(child: &Node<V, M>) -> usize {
+        2 * size_of::<usize>() + size_of::<Node<V, M>>() + child.memory()
+    }
+
+    fn calculate_children_memory(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| Self::child_memory(child))
+            .sum()
+    }
+
+    fn is_full(&self, fanout: Fanout) -> bool {
+        self.children.len() >= fanout.intermediate
     }
 
     fn metrics(&self) -> Option<Aggregation<V, M>> {
@@ -428,39 +1131,115 @@ where
         metrics
     }
 
-    fn append_observation(&mut self, observation: Observation<V>) -> Option<InternalNode<V, M>> {
+    fn append_observation(
+        &mut self,
+        observation: Observation<V>,
+        fanout: Fanout,
+    ) -> Option<InternalNode<V, M>> {
         // For now alway insert into last chunk:
-        let optional_new_chunk = self
-            .children
-            .last_mut()
-            .unwrap()
-            .append_observation(observation);
+        let index = self.children.len() - 1;
+        let optional_new_chunk =
+            self.update_child(index, |child| child.append_observation(observation, fanout));
 
         // Optionally we have a new chunk which must be added.
-        if let Some(new_child) = optional_new_chunk {
-            if self.is_full() {
-                self.metrics = self.calculate_metrics_from_child_nodes();
-                // Split required!
-                // for now, just split by creating a new node.
-                //  debug!("Split of sub chunk node");
-                let mut new_sibling = InternalNode::new();
-                new_sibling.add_child(new_child);
-                Some(new_sibling)
-            } else {
-                self.add_child(new_child);
-                None
-            }
+        let new_child = optional_new_chunk?;
+        self.append_child(new_child, fanout)
+    }
+
+    fn append_leaf(&mut self, leaf: LeafNode<V, M>, fanout: Fanout) -> Option<InternalNode<V, M>> {
+        let index = self.children.len() - 1;
+        let new_child = self.update_child(index, |child| child.append_leaf(leaf, fanout))?;
+        self.append_child(new_child, fanout)
+    }
+
+    /// Add a new last child, or return a new sibling with the child
+    /// when this node is full.
+    fn append_child(
+        &mut self,
+        new_child: Node<V, M>,
+        fanout: Fanout,
+    ) -> Option<InternalNode<V, M>> {
+        if self.is_full(fanout) {
+            self.metrics = self.calculate_metrics_from_child_nodes();
+            // Split required!
+            // for now, just split by creating a new node.
+            //  debug!("Split of sub chunk node");
+            let mut new_sibling = InternalNode::new(fanout);
+            new_sibling.add_child(new_child, fanout);
+            Some(new_sibling)
         } else {
+            self.add_child(new_child, fanout);
             None
         }
     }
 
+    fn insert_observation(
+        &mut self,
+        observation: Observation<V>,
+        fanout: Fanout,
+    ) -> (Option<InternalNode<V, M>>, bool) {
+        // Pre-calculated metrics must include the new observation:
+        if let Some(metrics) = &mut self.metrics {
+            metrics.include_observation(&observation);
+        }
+
+        // Select the last child starting before the observation:
+        let index = self
+            .children
+            .iter()
+            .rposition(|c| {
+                c.first_timestamp()
+                    .map_or(false, |t| *t <= observation.timestamp)
+            })
+            .unwrap_or(0);
+
+        let (optional_new_chunk, stored) =
+            self.update_child(index, |child| child.insert_observation(observation, fanout));
+
+        if let Some(new_child) = optional_new_chunk {
+            self.children_memory += Self::child_memory(&new_child);
+            self.children.insert(index + 1, Arc::new(new_child));
+
+            if self.children.len() > fanout.intermediate {
+                // Split in two halves:
+                let mut new_sibling = InternalNode::new(fanout);
+                new_sibling
+                    .children
+                    .extend(self.children.drain(self.children.len() / 2..));
+                new_sibling.children_memory = new_sibling.calculate_children_memory();
+                self.children_memory = self.calculate_children_memory();
+                if self.metrics.is_some() {
+                    self.metrics = self.calculate_metrics_from_child_nodes();
+                }
+                return (Some(new_sibling), stored);
+            }
+        }
+
+        (None, stored)
+    }
+
     /// Append a chunk into this chunk.
     /// Note: chunk must be of variant subchunk, otherwise this
     /// will fail.
-    fn add_child(&mut self, child: Node<V, M>) {
-        assert!(!self.is_full());
-        self.children.push(child);
+    fn add_child(&mut self, child: Node<V, M>, fanout: Fanout) {
+        assert!(!self.is_full(fanout));
+        self.children_memory += Self::child_memory(&child);
+        self.children.push(Arc::new(child));
+    }
+
+    /// Change a child node, copying it when it is shared, and keep
+    /// track of the memory it uses.
+    fn update_child<R, F>(&mut self, index: usize, update: F) -> R
+    where
+        F: FnOnce(&mut Node<V, M>) -> R,
+    {
+        // A copy of a shared child can use less memory than the original.
+        let before = self.children[index].memory();
+        let child = Arc::make_mut(&mut self.children[index]);
+        let result = update(child);
+        let after = child.memory();
+        self.children_memory = self.children_memory + after - before;
+        result
     }
 
     /// Select child nodes in range.
@@ -470,7 +1249,7 @@ where
         for child in &self.children {
             if let Some(child_metrics) = child.metrics() {
                 if child_metrics.timespan.overlap(timespan) {
-                    in_range_nodes.push(child);
+                    in_range_nodes.push(child.as_ref());
                 }
             }
         }
@@ -480,7 +1259,7 @@ where
 
     /// Select all child nodes.
     fn select_all(&self) -> Vec<&Node<V, M>> {
-        self.children.iter().collect()
+        self.children.iter().map(|child| child.as_ref()).collect()
     }
 
     fn to_vec(&self) -> Vec<Observation<V>> {
@@ -492,45 +1271,123 @@ where
     }
 }
 
+impl<V, M> CompressedLeafNode<V, M>
+where
+    M: Metrics<V> + Clone + From<V> + HeapSize,
+    V: Clone + Compress + HeapSize,
+{
+    /// The number of observations in this leaf.
+    fn len(&self) -> usize {
+        self.size + self.inserted.len()
+    }
+
+    fn memory(&self) -> usize {
+        self.data.capacity() + self.inserted.heap_size() + self.metrics.heap_size()
+    }
+
+    /// Decompress the observations, and merge the inserted observations.
+    fn observations(&self) -> Vec<Observation<V>> {
+        let mut observations = V::decompress(&self.data, self.size);
+        if !self.inserted.is_empty() {
+            // A stable sort keeps observations with equal timestamps
+            // in order of arrival.
+            observations.extend(self.inserted.iter().cloned());
+            observations.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        }
+        observations
+    }
+}
+
 impl<V, M> LeafNode<V, M>
 where
-    M: Metrics<V> + Clone + From<V>,
-    V: Clone,
+    M: Metrics<V> + Clone + From<V> + HeapSize,
+    V: Clone + Compress + HeapSize,
 {
     /// Create a new leaf chunk!
-    fn new() -> Self {
+    fn new(fanout: Fanout) -> Self {
         LeafNode {
-            observations: Vec::with_capacity(LEAF_CHUNK_SIZE),
+            observations: Vec::with_capacity(fanout.leaf),
             metrics: Default::default(),
         }
     }
 
+    /// Create a leaf with the given observations, sorted by time.
+    fn from_observations(observations: Vec<Observation<V>>) -> Self {
+        let metrics = Aggregation::from_observations(&observations);
+        LeafNode {
+            observations,
+            metrics,
+        }
+    }
+
     /// Test if this leaf is full or not.
-    fn is_full(&self) -> bool {
-        self.observations.len() >= LEAF_CHUNK_SIZE
+    fn is_full(&self, fanout: Fanout) -> bool {
+        self.observations.len() >= fanout.leaf
     }
 
     fn metrics(&self) -> Option<Aggregation<V, M>> {
         self.metrics.clone()
     }
 
+    fn memory(&self) -> usize {
+        self.observations.heap_size() + self.metrics.as_ref().map_or(0, HeapSize::heap_size)
+    }
+
     /// Append a single observation to this tree.
     /// If the node is full, return a new leaf node.
-    fn append_observation(&mut self, observation: Observation<V>) -> Option<LeafNode<V, M>> {
-        if self.is_full() {
+    fn append_observation(
+        &mut self,
+        observation: Observation<V>,
+        fanout: Fanout,
+    ) -> Option<LeafNode<V, M>> {
+        if self.is_full(fanout) {
             // We must split!
             // debug!("Split of leaf node!");
-            let mut new_leaf = LeafNode::new();
-            new_leaf.add_sample(observation);
+            let mut new_leaf = LeafNode::new(fanout);
+            new_leaf.add_sample(observation, fanout);
+            Some(new_leaf)
+        } else {
+            self.add_sample(observation, fanout);
+            None
+        }
+    }
+
+    /// Insert an observation at the right position in this leaf.
+    /// If the node is full, it is split and the new leaf is returned.
+    fn insert_observation(
+        &mut self,
+        observation: Observation<V>,
+        fanout: Fanout,
+    ) -> Option<LeafNode<V, M>> {
+        let index = self
+            .observations
+            .iter()
+            .rposition(|o| o.timestamp <= observation.timestamp)
+            .map_or(0, |i| i + 1);
+
+        if self.is_full(fanout) {
+            // Split in two halves:
+            self.observations.insert(index, observation);
+            let mut new_leaf = LeafNode::new(fanout);
+            new_leaf
+                .observations
+                .extend(self.observations.drain(self.observations.len() / 2..));
+            new_leaf.metrics = Aggregation::from_observations(&new_leaf.observations);
+            self.metrics = Aggregation::from_observations(&self.observations);
             Some(new_leaf)
         } else {
-            self.add_sample(observation);
+            if let Some(metrics) = &mut self.metrics {
+                metrics.include_observation(&observation);
+            } else {
+                self.metrics = Some(Aggregation::from(observation.clone()));
+            }
+            self.observations.insert(index, observation);
             None
         }
     }
 
-    fn add_sample(&mut self, observation: Observation<V>) {
-        assert!(!self.is_full());
+    fn add_sample(&mut self, observation: Observation<V>, fanout: Fanout) {
+        assert!(!self.is_full(fanout));
 
         // Update metrics:
         if self.metrics.is_none() {
@@ -547,20 +1404,16 @@ where
 
     /// Select the observations from this leaf which fall into the given
     /// timespan.
-    fn select_range(&self, timespan: &TimeSpan) -> Vec<&Observation<V>> {
-        let mut in_range_observations = vec![];
-
-        for observation in &self.observations {
-            if timespan.contains(&observation.timestamp) {
-                in_range_observations.push(observation);
-            }
-        }
-
-        in_range_observations
+    fn select_range(&self, timespan: &TimeSpan) -> Vec<Cow<'_, Observation<V>>> {
+        self.observations
+            .iter()
+            .filter(|observation| timespan.contains(&observation.timestamp))
+            .map(Cow::Borrowed)
+            .collect()
     }
 
-    fn select_all(&self) -> Vec<&Observation<V>> {
-        self.observations.iter().collect()
+    fn select_all(&self) -> Vec<Cow<'_, Observation<V>>> {
+        self.observations.iter().map(Cow::Borrowed).collect()
     }
 
     fn to_vec(&self) -> Vec<Observation<V>> {
@@ -571,8 +1424,11 @@ where
 #[cfg(test)]
 mod tests {
     use super::super::sample::{Sample, SampleMetrics};
-    use super::{Btree, Observation};
+    use super::super::{CountMetrics, Text};
+    use super::{Btree, Fanout, Node, Observation, RangeQueryResult};
     use crate::time::{TimeSpan, TimeStamp};
+    use std::mem::size_of;
+    use std::sync::Arc;
 
     #[test]
     fn btree_single_insertion() {
@@ -592,7 +1448,7 @@ mod tests {
 
     #[test]
     fn btree_mutliple_insertions() {
-        let mut tree = Btree::<Sample, SampleMetrics>::default();
+        let mut tree = Btree::<Sample, SampleMetrics>::new(Fanout::new(8, 4));
 
         // Insert some samples:
         for i in 0..1000 {
@@ -613,4 +1469,172 @@ mod tests {
         let result = tree.query_range(&time_span, 9);
         assert_eq!(result.len(), 2);
     }
+
+    #[test]
+    fn btree_bulk_append() {
+        let fanout = Fanout::new(16, 5);
+        let mut single = Btree::<Sample, SampleMetrics>::new(fanout);
+        let mut bulk = Btree::<Sample, SampleMetrics>::new(fanout);
+
+        let observations: Vec<Observation<Sample>> = (0..1000)
+            .map(|i| Observation::new(TimeStamp::from_seconds(i), Sample::new(i as f64)))
+            .collect();
+        for observation in observations.iter().cloned() {
+            single.append_sample(observation);
+        }
+        bulk.append_samples(observations[..3].to_vec());
+        bulk.append_samples(observations[3..700].to_vec());
+        bulk.append_samples(observations[700..].to_vec());
+
+        // Both ways result in the same tree:
+        assert_eq!(
+            format!("{:?}", single.image()),
+            format!("{:?}", bulk.image())
+        );
+        assert_eq!(bulk.to_vec().len(), 1000);
+        assert_eq!(bulk.summary().unwrap().count, 1000);
+
+        let time_span = TimeSpan::from_seconds(3, 13);
+        let result = bulk.query_range(&time_span, 20);
+        assert_eq!(result.len(), 11);
+    }
+
+    #[test]
+    fn btree_pruning() {
+        let mut tree = Btree::<Sample, SampleMetrics>::default();
+
+        for i in 0..1000 {
+            let t1 = TimeStamp::from_seconds(i);
+            let sample = Sample::new(i as f64);
+            let observation = Observation::new(t1, sample);
+            tree.append_sample(observation);
+        }
+
+        // Drop the oldest half of the data:
+        let mut dropped = 0;
+        while dropped < 500 {
+            dropped += tree.prune_first_leaf();
+        }
+        assert_eq!(tree.to_vec().len(), 1000 - dropped);
+
+        // Metrics of the dropped data are still there:
+        let summary = tree.summary().unwrap();
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.metrics().min, 0.0);
+
+        // Old data can be queried as aggregations:
+        let time_span = TimeSpan::from_seconds(3, 13);
+        let result = tree.query_range(&time_span, 100);
+        assert!(matches!(result, RangeQueryResult::Aggregations(..)));
+        let summary = tree.range_summary(&time_span).unwrap();
+        assert!(summary.timespan.covers(&time_span));
+
+        // Recent data can still be queried as observations:
+        let time_span = TimeSpan::from_seconds(900, 905);
+        let result = tree.query_range(&time_span, 100);
+        assert_eq!(result.len(), 6);
+
+        // Pruning everything keeps the most recent leaf:
+        while tree.prune_first_leaf() > 0 {}
+        assert!(tree.first_leaf_metrics().is_none());
+        assert!(!tree.to_vec().is_empty());
+
+        // Appending still works:
+        tree.append_sample(Observation::new(
+            TimeStamp::from_seconds(1000),
+            Sample::new(1.0),
+        ));
+        assert_eq!(tree.summary().unwrap().count, 1001);
+    }
+
+    #[test]
+    fn btree_out_of_order_insertions() {
+        let mut tree = Btree::<Sample, SampleMetrics>::default();
+
+        // Insert in order, and fill the gaps later on:
+        for i in 0..500 {
+            let t1 = TimeStamp::from_seconds(i * 2);
+            tree.append_sample(Observation::new(t1, Sample::new(i as f64)));
+        }
+        for i in (0..500).rev() {
+            let t1 = TimeStamp::from_seconds(i * 2 + 1);
+            assert!(tree.insert_sample(Observation::new(t1, Sample::new(-1.0))));
+        }
+
+        let observations = tree.to_vec();
+        assert_eq!(observations.len(), 1000);
+        for (i, observation) in observations.iter().enumerate() {
+            assert_eq!(observation.timestamp, TimeStamp::from_seconds(i as isize));
+        }
+
+        let summary = tree.summary().unwrap();
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.metrics().min, -1.0);
+
+        let time_span = TimeSpan::from_seconds(3, 13);
+        let result = tree.query_range(&time_span, 100);
+        assert_eq!(result.len(), 11);
+
+        // Appending still works:
+        tree.append_sample(Observation::new(
+            TimeStamp::from_seconds(1000),
+            Sample::new(1.0),
+        ));
+        assert_eq!(tree.summary().unwrap().count, 1001);
+
+        // Late samples in a pruned range only end up in the metrics:
+        tree.prune_first_leaf();
+        assert!(!tree.insert_sample(Observation::new(
+            TimeStamp::from_seconds(0),
+            Sample::new(-5.0),
+        )));
+        assert_eq!(tree.summary().unwrap().count, 1002);
+        assert_eq!(tree.summary().unwrap().metrics().min, -5.0);
+    }
+
+    /// Recount the memory of a node, without the memory kept per node.
+    fn recount_memory(node: &Node<Text, CountMetrics>) -> usize {
+        match node {
+            Node::Intermediate(internal) => {
+                let shared = 2 * size_of::<usize>() + size_of::<Node<Text, CountMetrics>>();
+                let children: usize = internal
+                    .children
+                    .iter()
+                    .map(|child| {
+                        size_of::<Arc<Node<Text, CountMetrics>>>() + shared + recount_memory(child)
+                    })
+                    .sum();
+                let spare = internal.children.capacity() - internal.children.len();
+                children + spare * size_of::<Arc<Node<Text, CountMetrics>>>()
+            }
+            _ => node.memory(),
+        }
+    }
+
+    #[test]
+    fn btree_memory() {
+        let mut tree = Btree::<Text, CountMetrics>::new(Fanout::new(4, 3));
+        let text = |i: isize| Text::new("x".repeat(i as usize));
+        let empty = tree.memory();
+
+        for i in 0..100 {
+            let t1 = TimeStamp::from_seconds(i * 2);
+            tree.append_sample(Observation::new(t1, text(i)));
+        }
+        let appended = tree.memory();
+        assert!(appended > empty + (0..100).sum::<usize>());
+
+        let snapshot = tree.clone();
+        for i in (0..100).rev() {
+            let t1 = TimeStamp::from_seconds(i * 2 + 1);
+            tree.insert_sample(Observation::new(t1, text(i)));
+        }
+        tree.prune_first_leaf();
+        tree.prune_first_leaf();
+
+        // The memory kept per node matches the nodes:
+        let root = size_of::<Node<Text, CountMetrics>>();
+        assert_eq!(recount_memory(&tree.root), tree.memory() - root);
+        assert_eq!(appended, snapshot.memory());
+    }
 }
@@ -0,0 +1,356 @@
+//! Compact encoding of the observations in a leaf of the tree.
+//!
+//! Timestamps are encoded as the difference between successive time
+//! differences (delta-of-delta), and values as the XOR with the previous
+//! value, as in the Gorilla time series database. Regularly sampled,
+//! slowly changing signals take only a few bits per observation.
+
+use super::{Event, LogRecord, Observation, ProfileEvent, Sample, Text, Vector};
+use crate::time::TimeStamp;
+
+/// Encoding of the observations in full leaves of the tree.
+pub trait Compress: Sized {
+    /// Encode observations, sorted by time.
+    ///
+    /// Returns None when this type of value is not compressed.
+    fn compress(observations: &[Observation<Self>]) -> Option<Vec<u8>>;
+
+    /// Decode the given number of observations.
+    ///
+    /// Only called with data returned by `compress`.
+    fn decompress(data: &[u8], count: usize) -> Vec<Observation<Self>>;
+}
+
+impl Compress for Sample {
+    fn compress(observations: &[Observation<Self>]) -> Option<Vec<u8>> {
+        let mut writer = BitWriter::with_capacity(observations.len() * 4);
+        let mut timestamps = TimestampEncoder::default();
+        let mut values = ValueEncoder::default();
+        for observation in observations {
+            timestamps.encode(&mut writer, &observation.timestamp);
+            values.encode(&mut writer, observation.value.value);
+        }
+        let mut data = writer.bytes;
+        data.shrink_to_fit();
+        Some(data)
+    }
+
+    fn decompress(data: &[u8], count: usize) -> Vec<Observation<Self>> {
+        let mut reader = BitReader::new(data);
+        let mut timestamps = TimestampEncoder::default();
+        let mut values = ValueEncoder::default();
+        (0..count)
+            .map(|_| {
+                let timestamp = timestamps.decode(&mut reader);
+                let value = values.decode(&mut reader);
+                Observation::new(timestamp, Sample::new(value))
+            })
+            .collect()
+    }
+}
+
+/// Implement `Compress` for types of which the observations are
+/// kept as they are.
+macro_rules! uncompressed {
+    ($($value:ty),*) => {
+        $(
+            impl Compress for $value {
+                fn compress(_observations: &[Observation<Self>]) -> Option<Vec<u8>> {
+                    None
+                }
+
+                fn decompress(_data: &[u8], _count: usize) -> Vec<Observation<Self>> {
+                    unreachable!("{} values are never compressed", stringify!($value))
+                }
+            }
+        )*
+    };
+}
+
+uncompressed!(Vector, Text, ProfileEvent, Event, LogRecord);
+
+/// Ranges of time difference changes, as prefix code and
+/// number of bits of the change.
+///
+/// Larger changes are stored in full, after a prefix of five ones.
+const DELTA_RANGES: [(u64, u32, u32); 4] = [
+    (0b10, 2, 7),
+    (0b110, 3, 12),
+    (0b1110, 4, 20),
+    (0b1_1110, 5, 32),
+];
+
+/// Delta-of-delta coding of timestamps.
+///
+/// The differences use wrapping arithmetic, so that timestamps far apart
+/// still roundtrip exactly.
+#[derive(Default)]
+struct TimestampEncoder {
+    previous: Option<(i64, i64)>,
+}
+
+impl TimestampEncoder {
+    fn encode(&mut self, writer: &mut BitWriter, timestamp: &TimeStamp) {
+        let nanos = timestamp.nanos();
+        if let Some((previous, previous_delta)) = self.previous {
+            let delta = nanos.wrapping_sub(previous);
+            let change = delta.wrapping_sub(previous_delta);
+            if change == 0 {
+                writer.write(0, 1);
+            } else if let Some((prefix, prefix_bits, bits)) = DELTA_RANGES
+                .iter()
+                .find(|(_, _, bits)| fits_in_bits(change, *bits))
+            {
+                writer.write(*prefix, *prefix_bits);
+                writer.write(change as u64, *bits);
+            } else {
+                writer.write(0b1_1111, 5);
+                writer.write(change as u64, 64);
+            }
+            self.previous = Some((nanos, delta));
+        } else {
+            writer.write(nanos as u64, 64);
+            self.previous = Some((nanos, 0));
+        }
+    }
+
+    fn decode(&mut self, reader: &mut BitReader) -> TimeStamp {
+        let nanos = if let Some((previous, previous_delta)) = self.previous {
+            let mut prefix_bits = 0;
+            while prefix_bits < 5 && reader.read_bit() {
+                prefix_bits += 1;
+            }
+            let change = match prefix_bits {
+                0 => 0,
+                5 => reader.read(64) as i64,
+                n => {
+                    let (_, _, bits) = DELTA_RANGES[n - 1];
+                    sign_extend(reader.read(bits), bits)
+                }
+            };
+            let delta = previous_delta.wrapping_add(change);
+            let nanos = previous.wrapping_add(delta);
+            self.previous = Some((nanos, delta));
+            nanos
+        } else {
+            let nanos = reader.read(64) as i64;
+            self.previous = Some((nanos, 0));
+            nanos
+        };
+        TimeStamp::from_nanos(nanos)
+    }
+}
+
+/// XOR coding of floating point values.
+#[derive(Default)]
+struct ValueEncoder {
+    previous: Option<u64>,
+
+    /// Leading and trailing zero bits of the previous XOR value.
+    window: Option<(u32, u32)>,
+}
+
+impl ValueEncoder {
+    fn encode(&mut self, writer: &mut BitWriter, value: f64) {
+        let bits = value.to_bits();
+        if let Some(previous) = self.previous {
+            let xor = bits ^ previous;
+            if xor == 0 {
+                writer.write(0, 1);
+            } else {
+                writer.write(1, 1);
+                let leading = xor.leading_zeros().min(31);
+                let trailing = xor.trailing_zeros();
+                match self.window {
+                    Some((window_leading, window_trailing))
+                        if leading >= window_leading && trailing >= window_trailing =>
+                    {
+                        // The meaningful bits fit in the previous window:
+                        writer.write(0, 1);
+                        writer.write(
+                            xor >> window_trailing,
+                            64 - window_leading - window_trailing,
+                        );
+                    }
+                    _ => {
+                        let meaningful = 64 - leading - trailing;
+                        writer.write(1, 1);
+                        writer.write(u64::from(leading), 5);
+                        writer.write(u64::from(meaningful - 1), 6);
+                        writer.write(xor >> trailing, meaningful);
+                        self.window = Some((leading, trailing));
+                    }
+                }
+            }
+        } else {
+            writer.write(bits, 64);
+        }
+        self.previous = Some(bits);
+    }
+
+    fn decode(&mut self, reader: &mut BitReader) -> f64 {
+        let bits = if let Some(previous) = self.previous {
+            if reader.read_bit() {
+                if reader.read_bit() {
+                    let leading = reader.read(5) as u32;
+                    let meaningful = reader.read(6) as u32 + 1;
+                    self.window = Some((leading, 64 - leading - meaningful));
+                }
+                let (leading, trailing) = self.window.unwrap_or_default();
+                let xor = reader.read(64 - leading - trailing) << trailing;
+                previous ^ xor
+            } else {
+                previous
+            }
+        } else {
+            reader.read(64)
+        };
+        self.previous = Some(bits);
+        f64::from_bits(bits)
+    }
+}
+
+fn fits_in_bits(value: i64, bits: u32) -> bool {
+    let limit = 1i64 << (bits - 1);
+    -limit <= value && value < limit
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// Writes values of a number of bits, most significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn with_capacity(bytes: usize) -> Self {
+        BitWriter {
+            bytes: Vec::with_capacity(bytes),
+            bits: 0,
+        }
+    }
+
+    /// Write the lowest bits of the value.
+    fn write(&mut self, value: u64, count: u32) {
+        let mut remaining = count;
+        while remaining > 0 {
+            let used = (self.bits % 8) as u32;
+            if used == 0 {
+                self.bytes.push(0);
+            }
+            let free = 8 - used;
+            let take = free.min(remaining);
+            let chunk = ((value >> (remaining - take)) & ((1 << take) - 1)) as u8;
+            *self.bytes.last_mut().unwrap() |= chunk << (free - take);
+            remaining -= take;
+            self.bits += take as usize;
+        }
+    }
+}
+
+/// Reads values written by the `BitWriter`.
+struct BitReader<'d> {
+    bytes: &'d [u8],
+    bit: usize,
+}
+
+impl<'d> BitReader<'d> {
+    fn new(bytes: &'d [u8]) -> Self {
+        BitReader { bytes, bit: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> u64 {
+        let mut value = 0;
+        let mut remaining = count;
+        while remaining > 0 {
+            let byte = self.bytes.get(self.bit / 8).cloned().unwrap_or(0);
+            let free = 8 - (self.bit % 8) as u32;
+            let take = free.min(remaining);
+            let chunk = u64::from(byte >> (free - take)) & ((1 << take) - 1);
+            value = (value << take) | chunk;
+            remaining -= take;
+            self.bit += take as usize;
+        }
+        value
+    }
+
+    fn read_bit(&mut self) -> bool {
+        self.read(1) == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compress;
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Observation, Sample};
+
+    fn round_trip(observations: &[Observation<Sample>]) -> usize {
+        let data = Sample::compress(observations).unwrap();
+        let decoded = Sample::decompress(&data, observations.len());
+        assert_eq!(observations.len(), decoded.len());
+        for (observation, decoded) in observations.iter().zip(decoded) {
+            assert_eq!(observation.timestamp, decoded.timestamp);
+            assert_eq!(
+                observation.value.value.to_bits(),
+                decoded.value.value.to_bits()
+            );
+        }
+        data.len()
+    }
+
+    #[test]
+    fn regular_signal() {
+        let observations: Vec<Observation<Sample>> = (0..1000)
+            .map(|i| {
+                let timestamp = TimeStamp::new(1.5e9 + i as f64 * 0.001);
+                Observation::new(timestamp, Sample::new((i / 10) as f64))
+            })
+            .collect();
+        let size = round_trip(&observations);
+
+        // Much less than the 16 bytes of an observation:
+        assert!(size < 2 * observations.len(), "{} bytes", size);
+    }
+
+    #[test]
+    fn irregular_signal() {
+        let values = vec![
+            0.0,
+            -0.0,
+            1.0e300,
+            -3.25,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MIN_POSITIVE,
+            0.1,
+            0.1,
+            7.0,
+        ];
+        let times = vec![
+            -5.0e9, -1.0, 0.0, 0.0, 1.0e-9, 2.0, 2.5, 1.0e9, 3.0e9, 3.0e9, 9.0e9,
+        ];
+        let observations: Vec<Observation<Sample>> = times
+            .into_iter()
+            .zip(values)
+            .map(|(t, v)| Observation::new(TimeStamp::new(t), Sample::new(v)))
+            .collect();
+        round_trip(&observations);
+        round_trip(&observations[..1]);
+        round_trip(&[]);
+    }
+
+    #[test]
+    fn extreme_timestamps() {
+        let observations: Vec<Observation<Sample>> = vec![i64::MAX, i64::MIN, 0, i64::MAX]
+            .into_iter()
+            .map(|t| Observation::new(TimeStamp::from_nanos(t), Sample::new(1.0)))
+            .collect();
+        round_trip(&observations);
+    }
+}
@@ -7,12 +7,15 @@ impl Connection for TsDb {
     }
 
     /// Close database
-    fn close(&self) {
+    fn close(&mut self) {
         trace!("closing {}", self);
+        if let Err(err) = self.flush() {
+            error!("Error flushing {}: {}", self, err);
+        }
     }
 }
 
 pub trait Connection {
     fn open(&self);
-    fn close(&self);
+    fn close(&mut self);
 }
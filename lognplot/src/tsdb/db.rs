@@ -1,80 +1,417 @@
 //! Time series database which uses B+ trees to store tha data.
 
+use super::derived::{DerivedSignal, Signals};
+use super::expression::{Expression, ExpressionError};
 use super::handle::{make_handle, TsDbHandle};
+use super::namespace::{is_in_namespace, SignalTree};
 use super::query::Query;
+use super::retention::{PendingRetention, RetentionPolicy};
+use super::storage::Storage;
 use super::ChangeSubscriber;
 use super::Summary;
-use super::{Observation, ProfileEvent, QueryResult, QuickSummary, Sample, Text};
-use super::{Track, TrackType};
-use crate::time::{TimeSpan, TimeStamp};
-use std::collections::HashMap;
+use super::{ArmedTrigger, Trigger, TriggerEvent, TriggerInput};
+use super::{
+    Bins, CallSpan, CountMetrics, Histogram, LookupMode, QuantileSketch, RangeQueryResult,
+};
+use super::{
+    Event, LogRecord, Observation, ProfileEvent, QueryResult, QuickSummary, Sample, Text, Vector,
+};
+use super::{Fanout, LastValue, Metadata, Snapshot};
+use super::{SharedTrack, Track, TrackType};
+use crate::time::{Duration, TimeSpan, TimeStamp};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Global retention limits are checked after this amount of new samples.
+const RETENTION_CHECK_INTERVAL: usize = 1024;
+
+/// Number of recent trigger events to remember.
+const TRIGGER_HISTORY: usize = 1000;
 
 /// A time series database which can be used as a library.
 /// Note that this struct is not usable in multiple threads.
 /// To make it accessible from multiple threads, use the TsDbHandle wrapper.
+///
+/// Each trace is locked on its own, so that the TsDbHandle wrapper
+/// can query a trace without blocking additions to other traces.
 #[derive(Debug)]
 pub struct TsDb {
-    path: String,
-    data: HashMap<String, Track>,
+    /// Optional on disk storage. When absent, all data lives in memory only.
+    storage: Option<Storage>,
+    data: HashMap<String, SharedTrack>,
+
+    /// Signals computed from other signals.
+    derived: HashMap<String, Arc<DerivedSignal>>,
+
+    /// Information such as units, also for signals without data yet.
+    metadata: HashMap<String, Metadata>,
     change_subscribers: Vec<ChangeSubscriber>,
+
+    /// Retention policy for the database as a whole.
+    retention: RetentionPolicy,
+
+    /// Retention policies for specific traces.
+    trace_retention: HashMap<String, RetentionPolicy>,
+
+    /// Fanout of the trees of specific traces.
+    trace_fanout: HashMap<String, Fanout>,
+
+    /// Samples added since the global retention policy was enforced.
+    samples_since_retention: usize,
+
+    /// When set, samples arriving later than this amount of seconds
+    /// move the existing data of a trace into a backup trace.
+    backup_on_rewind: Option<f64>,
+
+    /// Triggers evaluated on new data, by name.
+    triggers: HashMap<String, ArmedTrigger>,
+
+    /// Trigger events not yet sent to the subscribers.
+    fired_triggers: Vec<TriggerEvent>,
+
+    /// Most recent trigger events, oldest first.
+    trigger_events: VecDeque<TriggerEvent>,
 }
 
 impl std::fmt::Display for TsDb {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "TsDb {} with {} traces", self.path, self.data.len())
+        if let Some(storage) = &self.storage {
+            write!(
+                f,
+                "TsDb {} with {} traces",
+                storage.path().display(),
+                self.data.len()
+            )
+        } else {
+            write!(f, "TsDb in memory with {} traces", self.data.len())
+        }
     }
 }
 
 impl Default for TsDb {
     fn default() -> Self {
-        let path = "x".to_string();
         let data = HashMap::new();
         let change_subscribers = vec![];
         Self {
-            path,
+            storage: None,
             data,
+            derived: HashMap::new(),
+            metadata: HashMap::new(),
             change_subscribers,
+            retention: Default::default(),
+            trace_retention: HashMap::new(),
+            trace_fanout: HashMap::new(),
+            samples_since_retention: 0,
+            backup_on_rewind: None,
+            triggers: HashMap::new(),
+            fired_triggers: vec![],
+            trigger_events: VecDeque::new(),
+        }
+    }
+}
+
+impl Drop for TsDb {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            error!("Error flushing {} to disk: {}", self, err);
         }
     }
 }
 
 /// Database for time series.
 impl TsDb {
+    /// Open a database stored in the given directory.
+    ///
+    /// All traces stored previously are loaded, and new data
+    /// is appended to them. The directory is created if it
+    /// does not exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let (storage, tracks) = Storage::open(path.as_ref())?;
+        let mut db = Self::default();
+        db.metadata = storage.load_metadata();
+        db.storage = Some(storage);
+        for (name, track) in &tracks {
+            let fanout = track.fanout();
+            if fanout != Fanout::default() {
+                db.trace_fanout.insert(name.clone(), fanout);
+            }
+        }
+        db.data = tracks
+            .into_iter()
+            .map(|(name, track)| (name, Arc::new(RwLock::new(track))))
+            .collect();
+        Ok(db)
+    }
+
+    /// Write all pending data to disk, including a checkpoint
+    /// of the aggregates of each trace.
+    ///
+    /// This is a no-op for an in memory database.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(storage) = &mut self.storage {
+            for (name, track) in &self.data {
+                storage.checkpoint(name, &track.read().unwrap())?;
+            }
+            storage.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Set the retention policy for the database as a whole.
+    ///
+    /// The age limit is applied to each trace, the sample and memory limits
+    /// are shared by all traces. When over budget, the oldest data of
+    /// all traces is dropped first.
+    pub fn set_retention(&mut self, policy: RetentionPolicy) {
+        self.retention = policy;
+        let pending = self.pending_global_retention(PendingRetention::default());
+        self.apply_retention(pending);
+    }
+
+    /// Set the retention policy for a single trace.
+    pub fn set_trace_retention(&mut self, name: &str, policy: RetentionPolicy) {
+        self.trace_retention.insert(name.to_owned(), policy);
+        let pending = self.pending_trace_retention(name, PendingRetention::default());
+        self.apply_retention(pending);
+    }
+
+    /// Set the fanout of the tree of a single trace.
+    ///
+    /// Use a larger fanout for traces with many samples, to reduce the
+    /// memory used per sample. This applies when the trace is created,
+    /// an existing trace keeps its tree.
+    pub fn set_trace_fanout(&mut self, name: &str, fanout: Fanout) {
+        self.trace_fanout.insert(name.to_owned(), fanout);
+    }
+
+    /// Configure what happens with samples which are older than the last
+    /// sample of a trace.
+    ///
+    /// By default, such samples are inserted into the trace at the proper
+    /// place. When a window in seconds is given, samples which are more
+    /// than this window older than the last sample start a new trace, and
+    /// the existing data is moved into a backup trace.
+    pub fn set_backup_on_rewind(&mut self, window: Option<f64>) {
+        self.backup_on_rewind = window;
+    }
+
+    /// Add a trigger, replacing an existing trigger with the same name.
+    ///
+    /// The trigger is evaluated on data added from now on.
+    pub fn add_trigger(&mut self, name: &str, trigger: Trigger) {
+        self.triggers
+            .insert(name.to_owned(), ArmedTrigger::new(trigger));
+    }
+
+    /// Remove a trigger.
+    ///
+    /// A capture which is still waiting for its post-trigger data is
+    /// dropped. Events which were already reported are kept.
+    pub fn remove_trigger(&mut self, name: &str) {
+        self.triggers.remove(name);
+    }
+
+    /// Get the definition of the trigger with the given name.
+    pub fn get_trigger(&self, name: &str) -> Option<Trigger> {
+        self.triggers.get(name).map(|armed| armed.trigger().clone())
+    }
+
+    /// Get the most recent trigger events, oldest first.
+    pub fn trigger_events(&self) -> Vec<TriggerEvent> {
+        self.trigger_events.iter().cloned().collect()
+    }
+
     pub fn into_handle(self) -> TsDbHandle {
         make_handle(self)
     }
 
     pub fn get_signal_names(&self) -> Vec<String> {
-        self.data.keys().cloned().collect()
+        self.data
+            .keys()
+            .chain(self.derived.keys())
+            .cloned()
+            .collect()
+    }
+
+    /// Get the signal names, arranged as a tree of namespaces.
+    pub fn get_signal_tree(&self) -> SignalTree {
+        SignalTree::from_names(self.get_signal_names())
+    }
+
+    /// Get the names of the signals in a namespace, such as `robot/arm`.
+    pub fn get_signal_names_in(&self, namespace: &str) -> Vec<String> {
+        self.get_signal_names()
+            .into_iter()
+            .filter(|name| is_in_namespace(name, namespace))
+            .collect()
+    }
+
+    /// Get the trace with the given name.
+    ///
+    /// The trace stays usable after the lock on the database is released,
+    /// even when it is deleted from the database in the meantime.
+    ///
+    /// A derived signal is evaluated when its inputs changed.
+    pub fn track(&self, name: &str) -> Option<SharedTrack> {
+        self.signals(&[name]).track(name)
+    }
+
+    /// Get the given signals, and the signals they are computed from.
+    ///
+    /// Derived signals are not evaluated yet, this is done when
+    /// looking them up, which does not need the database.
+    pub fn signals(&self, names: &[&str]) -> Signals {
+        let mut signals = Signals::default();
+        for name in names {
+            self.collect_signal(name, &mut signals);
+        }
+        signals
+    }
+
+    /// Get all signals, see `signals`.
+    pub fn all_signals(&self) -> Signals {
+        Signals {
+            traces: self.data.clone(),
+            derived: self.derived.clone(),
+        }
+    }
+
+    fn collect_signal(&self, name: &str, signals: &mut Signals) {
+        if let Some(track) = self.data.get(name) {
+            signals.traces.insert(name.to_owned(), track.clone());
+        } else if let Some(derived) = self.derived.get(name) {
+            if signals.derived.contains_key(name) {
+                return;
+            }
+            signals.derived.insert(name.to_owned(), derived.clone());
+            for input in derived.inputs() {
+                self.collect_signal(input, signals);
+            }
+        }
+    }
+
+    /// Take a read-only view of all signals, see `Snapshot`.
+    ///
+    /// Derived signals are evaluated and included in the snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::from_signals(&self.all_signals(), self.metadata.clone())
+    }
+
+    /// Update the metadata of a signal.
+    ///
+    /// Only the fields which are set in the given metadata are changed.
+    /// Metadata can be set before the signal has any data.
+    pub fn set_metadata(&mut self, name: &str, metadata: Metadata) {
+        self.metadata
+            .entry(name.to_owned())
+            .or_default()
+            .update(metadata);
+        self.persist_metadata();
+        for subscriber in &mut self.change_subscribers {
+            subscriber.notify_signal_changed(name);
+        }
+    }
+
+    /// Write the metadata of all signals to disk, if we have storage.
+    fn persist_metadata(&mut self) {
+        if let Some(storage) = &self.storage {
+            if let Err(err) = storage.save_metadata(&self.metadata) {
+                error!("Error writing metadata to disk: {}", err);
+            }
+        }
+    }
+
+    /// Get the metadata of a signal, if any was set.
+    pub fn metadata(&self, name: &str) -> Option<Metadata> {
+        self.metadata.get(name).cloned()
+    }
+
+    /// Get the metadata of all signals.
+    pub fn all_metadata(&self) -> HashMap<String, Metadata> {
+        self.metadata.clone()
+    }
+
+    /// Add a signal which is computed from other signals.
+    ///
+    /// The expression combines signals by name, for example
+    /// `avg(speed, 0.5) * 3.6`. See the expression module for the
+    /// syntax. An existing derived signal with this name is replaced.
+    pub fn add_derived(&mut self, name: &str, expression: &str) -> Result<(), ExpressionError> {
+        let expression = Expression::parse(expression)?;
+        if self.data.contains_key(name) {
+            return Err(ExpressionError::new(0, "A trace with this name exists"));
+        }
+        if expression
+            .signals()
+            .iter()
+            .any(|input| self.uses_signal(input, name))
+        {
+            return Err(ExpressionError::new(0, "Expression uses the signal itself"));
+        }
+
+        let is_new = self
+            .derived
+            .insert(name.to_owned(), Arc::new(DerivedSignal::new(expression)))
+            .is_none();
+        if is_new {
+            self.notify_signal_added(name);
+        }
+        self.notify_signal_changed(name);
+        Ok(())
+    }
+
+    /// Test if the given signal is, or is computed from, another signal.
+    fn uses_signal(&self, signal: &str, other: &str) -> bool {
+        signal == other
+            || self.derived.get(signal).map_or(false, |derived| {
+                derived
+                    .inputs()
+                    .iter()
+                    .any(|input| self.uses_signal(input, other))
+            })
     }
 
     fn get_or_create_trace(
         &mut self,
         name: &str,
         typ: TrackType,
+        components: &[String],
         first_timestamp: &TimeStamp,
-    ) -> &mut Track {
-        if self.data.contains_key(name) {
-            let trace = self.data.get(name).expect("name to be present");
-            if trace.get_type() == typ {
-                if let Some(summary) = trace.quick_summary() {
+    ) -> SharedTrack {
+        let existing = self.data.get(name).map(|trace| {
+            let trace = trace.read().unwrap();
+            let compatible = trace.get_type() == typ && trace.components() == components;
+            (compatible, trace.quick_summary())
+        });
+
+        match existing {
+            Some((true, summary)) => {
+                if let (Some(summary), Some(window)) = (summary, self.backup_on_rewind) {
                     let last_saved_observation_time = summary.last_timestamp();
 
-                    if first_timestamp < &last_saved_observation_time {
+                    let lateness = last_saved_observation_time - first_timestamp;
+                    if lateness > Duration::from_secs_f64(window) {
                         self.backup_track(name);
-                        self.new_trace(name, typ);
+                        self.new_trace(name, typ, components);
                     }
                 }
-            } else {
+            }
+            Some((false, _)) => {
                 self.backup_track(name);
-                self.new_trace(name, typ);
+                self.new_trace(name, typ, components);
+            }
+            None => {
+                if self.derived.remove(name).is_some() {
+                    warn!("Replacing derived signal {} by a trace", name);
+                }
+                self.new_trace(name, typ, components);
+                self.notify_signal_added(name);
             }
-        } else {
-            self.new_trace(name, typ);
-            self.notify_signal_added(name);
         }
 
-        self.data.get_mut(name).unwrap()
+        self.data.get(name).unwrap().clone()
     }
 
     fn backup_track(&mut self, name: &str) {
@@ -91,83 +428,374 @@ impl TsDb {
         // Copy of the old data:
         let no_data = self.data.insert(backup_new_name.clone(), trace);
         assert!(no_data.is_none()); // "Name must not be present already."
+        if let Some(storage) = &mut self.storage {
+            if let Err(err) = storage.rename(name, &backup_new_name) {
+                error!("Error renaming {} on disk: {}", name, err);
+            }
+        }
         self.notify_signal_added(&backup_new_name);
         self.notify_signal_changed(&backup_new_name);
     }
 
-    fn new_trace(&mut self, name: &str, typ: TrackType) {
-        let trace = Track::new_with_type(typ);
-        self.data.insert(name.to_owned(), trace);
+    fn new_trace(&mut self, name: &str, typ: TrackType, components: &[String]) {
+        let fanout = self.trace_fanout.get(name).cloned().unwrap_or_default();
+        let trace = Track::with_fanout(typ, components.to_vec(), fanout);
+        if let Some(storage) = &mut self.storage {
+            if let Err(err) = storage.create_segment(name, &trace) {
+                error!("Error creating {} on disk: {}", name, err);
+            }
+        }
+        self.data
+            .insert(name.to_owned(), Arc::new(RwLock::new(trace)));
+    }
+
+    /// Write observations to disk, if we have storage.
+    fn persist<V: Serialize>(&mut self, name: &str, observations: &[Observation<V>]) {
+        if let Some(storage) = &mut self.storage {
+            if let Err(err) = storage.append(name, observations) {
+                error!("Error writing {} to disk: {}", name, err);
+            }
+        }
+    }
+
+    /// Create the trace when required and write the observations to disk.
+    ///
+    /// Returns the trace to which the observations must be added. Call
+    /// `added_samples` after adding them.
+    pub fn begin_insert<V: Serialize + TriggerInput>(
+        &mut self,
+        name: &str,
+        typ: TrackType,
+        components: &[String],
+        observations: &[Observation<V>],
+    ) -> SharedTrack {
+        let first_observation = observations
+            .first()
+            .expect("Must have an observation here.");
+        let track = self.get_or_create_trace(name, typ, components, &first_observation.timestamp);
+        self.persist(name, observations);
+        self.evaluate_triggers(name, observations);
+        track
+    }
+
+    /// Evaluate the triggers on the given signal.
+    ///
+    /// The observations also complete the captures of triggers on other
+    /// signals, when they are past the end of the capture.
+    /// Completed captures are reported after the observations were added.
+    fn evaluate_triggers<V: TriggerInput>(&mut self, name: &str, observations: &[Observation<V>]) {
+        let latest = observations.iter().map(|o| &o.timestamp).max();
+        for (trigger_name, armed) in &mut self.triggers {
+            if armed.trigger().signal == name {
+                let events = armed.process(trigger_name, observations);
+                self.fired_triggers.extend(events);
+            } else if let Some(event) = latest.and_then(|latest| armed.advance(latest)) {
+                self.fired_triggers.push(event);
+            }
+        }
+    }
+
+    /// Add observations to a trace, using the given function.
+    fn insert<V, F>(
+        &mut self,
+        name: &str,
+        typ: TrackType,
+        components: &[String],
+        observations: Vec<Observation<V>>,
+        add: F,
+    ) where
+        V: Serialize + TriggerInput,
+        F: FnOnce(&mut Track, Vec<Observation<V>>),
+    {
+        if !observations.is_empty() {
+            let amount = observations.len();
+            let timespan = observations_timespan(&observations);
+            let track = self.begin_insert(name, typ, components, &observations);
+            add(&mut track.write().unwrap(), observations);
+            let pending = self.added_samples(name, amount, &timespan);
+            self.apply_retention(pending);
+        }
+    }
+
+    /// Collect the trace with its own retention policy, if any.
+    fn pending_trace_retention(&self, name: &str, pending: PendingRetention) -> PendingRetention {
+        match (self.trace_retention.get(name), self.data.get(name)) {
+            (Some(policy), Some(track)) => pending.trace(name, policy.clone(), track.clone()),
+            _ => pending,
+        }
+    }
+
+    /// Collect all traces to enforce the global retention policy.
+    fn pending_global_retention(&mut self, pending: PendingRetention) -> PendingRetention {
+        self.samples_since_retention = 0;
+        if self.retention.is_unlimited() {
+            return pending;
+        }
+
+        let tracks = self
+            .data
+            .iter()
+            .map(|(name, track)| (name.clone(), track.clone()))
+            .collect();
+        pending.global(self.retention.clone(), tracks)
+    }
+
+    /// Bookkeeping after adding observations to a trace.
+    ///
+    /// Returns the retention to apply. Apply it after unlocking the
+    /// database, and report the changed traces with `retention_applied`.
+    pub fn added_samples(
+        &mut self,
+        name: &str,
+        amount: usize,
+        timespan: &TimeSpan,
+    ) -> PendingRetention {
+        let mut pending = self.pending_trace_retention(name, PendingRetention::default());
+        self.samples_since_retention += amount;
+        if self.samples_since_retention >= RETENTION_CHECK_INTERVAL {
+            pending = self.pending_global_retention(pending);
+        }
+        self.notify_samples_added(name, amount, timespan);
+        self.notify_triggers();
+        pending
+    }
+
+    /// Notify listeners of the traces from which retention dropped data.
+    pub fn retention_applied(&mut self, changed: Vec<String>) {
+        for name in changed {
+            // The trace may be deleted in the meantime.
+            if self.data.contains_key(&name) {
+                self.notify_signal_changed(&name);
+            }
+        }
+    }
+
+    /// Apply retention right away, while the database is in use anyway.
+    fn apply_retention(&mut self, pending: PendingRetention) {
+        let changed = pending.apply();
+        self.retention_applied(changed);
     }
 
     /// Add a batch of values
     pub fn add_values(&mut self, name: &str, samples: Vec<Observation<Sample>>) {
-        if !samples.is_empty() {
-            let first_observation = samples.first().expect("Must have an observation here.");
-            let trace =
-                self.get_or_create_trace(name, TrackType::Value, &first_observation.timestamp);
-            trace.add_value_observations(samples);
-            self.notify_signal_changed(name);
-        }
+        self.insert(
+            name,
+            TrackType::Value,
+            &[],
+            samples,
+            Track::add_value_observations,
+        );
     }
 
     /// Add a single observation to the database.
     pub fn add_value(&mut self, name: &str, observation: Observation<Sample>) {
-        let trace = self.get_or_create_trace(name, TrackType::Value, &observation.timestamp);
-        trace.add_value_observation(observation);
-        self.notify_signal_changed(name);
+        self.add_values(name, vec![observation]);
     }
 
     /// Add a text record.
     pub fn add_text(&mut self, name: &str, observation: Observation<Text>) {
-        let track = self.get_or_create_trace(name, TrackType::Text, &observation.timestamp);
-        track.add_text_observation(observation);
-        self.notify_signal_changed(name);
+        self.insert(
+            name,
+            TrackType::Text,
+            &[],
+            vec![observation],
+            Track::add_text_observations,
+        );
+    }
+
+    /// Add an event with attributes.
+    pub fn add_event(&mut self, name: &str, observation: Observation<Event>) {
+        self.insert(
+            name,
+            TrackType::Event,
+            &[],
+            vec![observation],
+            Track::add_event_observations,
+        );
+    }
+
+    /// Add a log record.
+    pub fn add_log(&mut self, name: &str, observation: Observation<LogRecord>) {
+        self.insert(
+            name,
+            TrackType::Log,
+            &[],
+            vec![observation],
+            Track::add_log_observations,
+        );
     }
 
     pub fn add_profile_event(&mut self, name: &str, observation: Observation<ProfileEvent>) {
-        let track = self.get_or_create_trace(name, TrackType::Profile, &observation.timestamp);
-        track.add_profile_observation(observation);
-        self.notify_signal_changed(name);
-        // TODO
-        // let track = self.get_or_create_trace(name, TrackType::Text, &observation.timestamp);
-        // self.db.lock().unwrap().add_profile_event(name, event);
+        self.insert(
+            name,
+            TrackType::Profile,
+            &[],
+            vec![observation],
+            Track::add_profile_observations,
+        );
+    }
+
+    /// Add a batch of vector values.
+    ///
+    /// The component names are part of the identity of the trace: when
+    /// they change, the existing data is moved into a backup trace.
+    pub fn add_vectors(
+        &mut self,
+        name: &str,
+        components: &[String],
+        observations: Vec<Observation<Vector>>,
+    ) {
+        self.insert(
+            name,
+            TrackType::Vector,
+            components,
+            observations,
+            Track::add_vector_observations,
+        );
     }
 
     /// Delete all data from the database.
     pub fn delete_all(&mut self) {
         self.data.clear();
         self.data.shrink_to_fit();
+        self.derived.clear();
+        self.metadata.clear();
+        self.persist_metadata();
+        if let Some(storage) = &mut self.storage {
+            if let Err(err) = storage.remove_all() {
+                error!("Error removing data from disk: {}", err);
+            }
+        }
         self.notify_delete_all();
     }
 
-    /// Delete a single trace from the database.
+    /// Delete a single trace, or derived signal, from the database.
     pub fn delete(&mut self, name: &str) {
-        self.data.remove(name);
-        self.delete_event(name);
+        if self.metadata.remove(name).is_some() {
+            self.persist_metadata();
+        }
+        if self.data.remove(name).is_some() {
+            if let Some(storage) = &mut self.storage {
+                if let Err(err) = storage.remove(name) {
+                    error!("Error removing {} from disk: {}", name, err);
+                }
+            }
+            self.notify_signal_deleted(name);
+        } else if self.derived.remove(name).is_some() {
+            self.notify_signal_deleted(name);
+        }
+    }
+
+    /// Delete all signals in a namespace from the database.
+    pub fn delete_namespace(&mut self, namespace: &str) {
+        for name in self.get_signal_names_in(namespace) {
+            self.delete(&name);
+        }
     }
 
     /// Query the given trace for data.
     pub fn query(&self, name: &str, query: Query) -> Option<QueryResult> {
-        if let Some(trace) = self.data.get(name) {
-            Some(trace.query(query))
-        } else {
-            None
-        }
+        self.track(name)
+            .map(|trace| trace.read().unwrap().query(query))
+    }
+
+    /// Query the given text trace for data.
+    ///
+    /// Returns None when there is no text trace with this name.
+    pub fn query_text(
+        &self,
+        name: &str,
+        query: Query,
+    ) -> Option<RangeQueryResult<Text, CountMetrics>> {
+        self.track(name)?.read().unwrap().query_text(query)
+    }
+
+    /// Query the given profile trace for data.
+    ///
+    /// Returns None when there is no profile trace with this name.
+    pub fn query_profile(
+        &self,
+        name: &str,
+        query: Query,
+    ) -> Option<RangeQueryResult<ProfileEvent, CountMetrics>> {
+        self.track(name)?.read().unwrap().query_profile(query)
+    }
+
+    /// Get the calls of a profile trace which overlap the given timespan.
+    ///
+    /// Calls which are too short to be seen when showing the
+    /// timespan with the given amount of points are merged.
+    pub fn query_calls(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+        amount: usize,
+    ) -> Option<Vec<CallSpan>> {
+        self.track(name)?
+            .read()
+            .unwrap()
+            .query_calls(timespan, amount)
+    }
+
+    /// Get the observation of any kind of trace at a certain time.
+    pub fn observation_at(
+        &self,
+        name: &str,
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Option<LastValue> {
+        self.track(name)?
+            .read()
+            .unwrap()
+            .observation_at(timestamp, mode)
+    }
+
+    /// Get the value of the given trace at a certain time.
+    pub fn value_at(
+        &self,
+        name: &str,
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Option<Observation<Sample>> {
+        self.track(name)?.read().unwrap().value_at(timestamp, mode)
+    }
+
+    /// Get the values of several traces at a certain time.
+    pub fn values_at(
+        &self,
+        names: &[&str],
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Vec<Option<Observation<Sample>>> {
+        names
+            .iter()
+            .map(|name| self.value_at(name, timestamp, mode))
+            .collect()
+    }
+
+    /// Get the distribution of the values of a trace in a time span.
+    pub fn histogram(&self, name: &str, timespan: &TimeSpan, bins: &Bins) -> Option<Histogram> {
+        self.track(name)?.read().unwrap().histogram(timespan, bins)
+    }
+
+    /// Sketch the distribution of the values of a trace in a time span,
+    /// to estimate quantiles.
+    pub fn quantile_sketch(&self, name: &str, timespan: &TimeSpan) -> Option<QuantileSketch> {
+        self.track(name)?.read().unwrap().quantile_sketch(timespan)
     }
 
     // Download raw samples.
     pub fn get_raw_samples(&self, name: &str) -> Option<Vec<Observation<Sample>>> {
-        self.data.get(name).map(|t| t.to_vec())
+        self.track(name).map(|t| t.read().unwrap().to_vec())
     }
 
     pub fn quick_summary(&self, name: &str) -> Option<QuickSummary> {
-        self.data.get(name)?.quick_summary()
+        self.track(name)?.read().unwrap().quick_summary()
     }
 
     /// Get a summary for a certain timerange (or all time) the given trace.
     pub fn summary(&self, name: &str, timespan: Option<&TimeSpan>) -> Option<Summary> {
-        self.data.get(name)?.summary(timespan)
+        self.track(name)?.read().unwrap().summary(timespan)
     }
 
     // Events
@@ -175,7 +803,7 @@ impl TsDb {
     /// Register a subscriber which will be notified of any change.
     pub fn register_notifier(&mut self, mut subscriber: ChangeSubscriber) {
         // Add a new signal event for all currently present signals:
-        for signal_name in self.data.keys() {
+        for signal_name in self.data.keys().chain(self.derived.keys()) {
             subscriber.notify_signal_added(signal_name);
             subscriber.notify_signal_changed(signal_name);
         }
@@ -200,6 +828,42 @@ impl TsDb {
         for subscriber in &mut self.change_subscribers {
             subscriber.notify_signal_changed(name);
         }
+        self.notify_derived_signals(name);
+    }
+
+    /// Notify listeners of new samples in a trace.
+    fn notify_samples_added(&mut self, name: &str, amount: usize, timespan: &TimeSpan) {
+        for subscriber in &mut self.change_subscribers {
+            subscriber.notify_samples_added(name, amount, timespan);
+        }
+        self.notify_derived_signals(name);
+    }
+
+    /// Mark all derived signals using the given signal as changed.
+    fn notify_derived_signals(&mut self, name: &str) {
+        let dependents: Vec<String> = self
+            .derived
+            .iter()
+            .filter(|(_, derived)| derived.depends_on(name))
+            .map(|(dependent, _)| dependent.clone())
+            .collect();
+        for dependent in dependents {
+            self.derived[&dependent].invalidate();
+            self.notify_signal_changed(&dependent);
+        }
+    }
+
+    /// Report the completed trigger captures.
+    fn notify_triggers(&mut self) {
+        for event in std::mem::take(&mut self.fired_triggers) {
+            for subscriber in &mut self.change_subscribers {
+                subscriber.notify_trigger(&event);
+            }
+            if self.trigger_events.len() >= TRIGGER_HISTORY {
+                self.trigger_events.pop_front();
+            }
+            self.trigger_events.push_back(event);
+        }
     }
 
     fn notify_signal_added(&mut self, name: &str) {
@@ -208,8 +872,11 @@ impl TsDb {
         }
     }
 
-    fn delete_event(&mut self, _name: &str) {
-        unimplemented!("TODO");
+    fn notify_signal_deleted(&mut self, name: &str) {
+        for subscriber in &mut self.change_subscribers {
+            subscriber.notify_signal_deleted(name);
+        }
+        self.notify_derived_signals(name);
     }
 
     fn notify_delete_all(&mut self) {
@@ -218,3 +885,14 @@ impl TsDb {
         }
     }
 }
+
+/// The time span covered by observations, which need not be sorted.
+/// The timespan of a non-empty series of observations.
+pub fn observations_timespan<V>(observations: &[Observation<V>]) -> TimeSpan {
+    let first = &observations[0].timestamp;
+    let mut timespan = TimeSpan::new(first.clone(), first.clone());
+    for observation in &observations[1..] {
+        timespan.extend_to_include(&observation.timestamp);
+    }
+    timespan
+}
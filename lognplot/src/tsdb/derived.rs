@@ -0,0 +1,342 @@
+//! Derived signals, computed from other signals using an expression.
+//!
+//! A derived signal is evaluated lazily: only when it is used after
+//! one of its inputs changed. When two signals are combined, both are
+//! resampled onto the timestamps of the two signals, using linear
+//! interpolation. The result only covers the time where both signals
+//! have data.
+//!
+//! Derived signals are evaluated without locking the database, see `Signals`.
+
+use super::expression::{Expression, Operator};
+use super::resample::{resample_at, Interpolation};
+use super::{Observation, Sample, SharedTrack, Track, TrackType};
+use crate::time::{Duration, TimeStamp};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+type Point = Observation<Sample>;
+
+/// A signal computed from other signals.
+#[derive(Debug)]
+pub struct DerivedSignal {
+    expression: Expression,
+    inputs: Vec<String>,
+
+    /// The most recently evaluated values.
+    track: SharedTrack,
+
+    /// Set when the inputs changed since the last evaluation.
+    dirty: AtomicBool,
+
+    /// Held while evaluating, so that the signal is evaluated only once
+    /// when several threads use it at the same time.
+    evaluating: Mutex<()>,
+}
+
+impl DerivedSignal {
+    pub fn new(expression: Expression) -> Self {
+        let inputs = expression.signals();
+        DerivedSignal {
+            expression,
+            inputs,
+            track: Arc::new(RwLock::new(Track::new_with_type(TrackType::Value))),
+            dirty: AtomicBool::new(true),
+            evaluating: Mutex::new(()),
+        }
+    }
+
+    /// The signals used by this signal.
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn depends_on(&self, name: &str) -> bool {
+        self.inputs.iter().any(|input| input == name)
+    }
+
+    /// Mark the signal for evaluation when it is used next.
+    pub fn invalidate(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Get the values of this signal, evaluating it when required.
+    ///
+    /// The given function is used to find the input signals.
+    pub fn track<F>(&self, lookup: F) -> SharedTrack
+    where
+        F: Fn(&str) -> Option<SharedTrack>,
+    {
+        let _evaluating = self.evaluating.lock().unwrap();
+        if self.dirty.swap(false, Ordering::SeqCst) {
+            let lookup = |name: &str| -> Vec<Point> {
+                lookup(name)
+                    .map(|track| track.read().unwrap().to_vec())
+                    .unwrap_or_default()
+            };
+
+            let observations = evaluate(&self.expression, &lookup)
+                .into_iter()
+                .filter(|observation| observation.value.value.is_finite())
+                .collect();
+            let mut track = Track::new_with_type(TrackType::Value);
+            track.add_value_observations(observations);
+            *self.track.write().unwrap() = track;
+        }
+        self.track.clone()
+    }
+}
+
+/// Traces and derived signals, taken from the database.
+///
+/// These are gathered while the database is locked. Derived signals
+/// are evaluated when they are looked up, so that the database does not
+/// have to be locked while evaluating them.
+#[derive(Debug, Default)]
+pub struct Signals {
+    pub traces: HashMap<String, SharedTrack>,
+    pub derived: HashMap<String, Arc<DerivedSignal>>,
+}
+
+impl Signals {
+    /// Get the trace of a signal, evaluating derived signals when required.
+    pub fn track(&self, name: &str) -> Option<SharedTrack> {
+        if let Some(track) = self.traces.get(name) {
+            Some(track.clone())
+        } else {
+            let derived = self.derived.get(name)?;
+            Some(derived.track(|input| self.track(input)))
+        }
+    }
+
+    /// The names of all signals.
+    pub fn names(&self) -> Vec<String> {
+        self.traces
+            .keys()
+            .chain(self.derived.keys())
+            .cloned()
+            .collect()
+    }
+}
+
+/// The value of an expression.
+enum Series {
+    Constant(f64),
+    Points(Vec<Point>),
+}
+
+impl Series {
+    /// Get the points of the series.
+    ///
+    /// Parsed expressions only apply functions to signals,
+    /// so a constant has no points.
+    fn into_points(self) -> Vec<Point> {
+        match self {
+            Series::Constant(_) => vec![],
+            Series::Points(points) => points,
+        }
+    }
+
+    fn map<F>(self, f: F) -> Self
+    where
+        F: Fn(f64) -> f64,
+    {
+        match self {
+            Series::Constant(value) => Series::Constant(f(value)),
+            Series::Points(points) => Series::Points(
+                points
+                    .into_iter()
+                    .map(|p| Observation::new(p.timestamp, Sample::new(f(p.value.value))))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn evaluate<F>(expression: &Expression, lookup: &F) -> Vec<Point>
+where
+    F: Fn(&str) -> Vec<Point>,
+{
+    evaluate_series(expression, lookup).into_points()
+}
+
+fn evaluate_series<F>(expression: &Expression, lookup: &F) -> Series
+where
+    F: Fn(&str) -> Vec<Point>,
+{
+    match expression {
+        Expression::Constant(value) => Series::Constant(*value),
+        Expression::Signal(name) => Series::Points(lookup(name)),
+        Expression::Negate(a) => evaluate_series(a, lookup).map(|v| -v),
+        Expression::Abs(a) => evaluate_series(a, lookup).map(f64::abs),
+        Expression::Binary(a, op, b) => {
+            let a = evaluate_series(a, lookup);
+            let b = evaluate_series(b, lookup);
+            combine(a, *op, b)
+        }
+        Expression::Derivative(a) => Series::Points(derivative(&evaluate(a, lookup))),
+        Expression::Integral(a) => Series::Points(integral(&evaluate(a, lookup))),
+        Expression::Average(a, window) => {
+            Series::Points(moving_average(&evaluate(a, lookup), *window))
+        }
+    }
+}
+
+fn combine(a: Series, op: Operator, b: Series) -> Series {
+    match (a, b) {
+        (Series::Constant(a), Series::Constant(b)) => Series::Constant(op.apply(a, b)),
+        (Series::Points(a), Series::Constant(b)) => Series::Points(a).map(|a| op.apply(a, b)),
+        (Series::Constant(a), Series::Points(b)) => Series::Points(b).map(|b| op.apply(a, b)),
+        (Series::Points(a), Series::Points(b)) => Series::Points(resample(&a, op, &b)),
+    }
+}
+
+/// Combine two signals, at the timestamps of both signals.
+///
+/// Both signals are interpolated linearly at these timestamps.
+fn resample(a: &[Point], op: Operator, b: &[Point]) -> Vec<Point> {
+    if let (Some(a_first), Some(a_last), Some(b_first), Some(b_last)) =
+        (a.first(), a.last(), b.first(), b.last())
+    {
+        let start = std::cmp::max(&a_first.timestamp, &b_first.timestamp).clone();
+        let end = std::cmp::min(&a_last.timestamp, &b_last.timestamp).clone();
+
+        let mut timestamps: Vec<TimeStamp> = a
+            .iter()
+            .chain(b.iter())
+            .map(|p| &p.timestamp)
+            .filter(|t| **t >= start && **t <= end)
+            .cloned()
+            .collect();
+        timestamps.sort();
+        timestamps.dedup();
+
+        let a = resample_at(a, timestamps.iter().cloned(), Interpolation::Linear);
+        let b = resample_at(b, timestamps.into_iter(), Interpolation::Linear);
+        a.into_iter()
+            .zip(b)
+            .map(|(a, b)| {
+                let value = op.apply(a.value.value, b.value.value);
+                Observation::new(a.timestamp, Sample::new(value))
+            })
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+fn derivative(points: &[Point]) -> Vec<Point> {
+    points
+        .windows(2)
+        .filter_map(|pair| {
+            let dt = (&pair[1].timestamp - &pair[0].timestamp).as_secs_f64();
+            if dt > 0.0 {
+                let value = (pair[1].value.value - pair[0].value.value) / dt;
+                Some(Observation::new(
+                    pair[1].timestamp.clone(),
+                    Sample::new(value),
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Integrate using the trapezoidal rule, starting at zero.
+fn integral(points: &[Point]) -> Vec<Point> {
+    let mut sum = 0.0;
+    let mut integral = Vec::with_capacity(points.len());
+    for (index, point) in points.iter().enumerate() {
+        if index > 0 {
+            let previous = &points[index - 1];
+            let dt = (&point.timestamp - &previous.timestamp).as_secs_f64();
+            sum += dt * (previous.value.value + point.value.value) / 2.0;
+        }
+        integral.push(Observation::new(point.timestamp.clone(), Sample::new(sum)));
+    }
+    integral
+}
+
+/// Average of all points in the time window before each point.
+fn moving_average(points: &[Point], window: Duration) -> Vec<Point> {
+    let mut sum = 0.0;
+    let mut first = 0;
+    points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| {
+            sum += point.value.value;
+            while &point.timestamp - &points[first].timestamp >= window {
+                sum -= points[first].value.value;
+                first += 1;
+            }
+            let average = sum / (index + 1 - first) as f64;
+            Observation::new(point.timestamp.clone(), Sample::new(average))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, Point};
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Expression, Observation, Sample};
+
+    fn points(values: &[(f64, f64)]) -> Vec<Point> {
+        values
+            .iter()
+            .map(|(t, v)| Observation::new(TimeStamp::new(*t), Sample::new(*v)))
+            .collect()
+    }
+
+    fn values(points: Vec<Point>) -> Vec<(f64, f64)> {
+        points
+            .into_iter()
+            .map(|p| (p.timestamp.to_seconds(), p.value.value))
+            .collect()
+    }
+
+    fn lookup(name: &str) -> Vec<Point> {
+        match name {
+            "a" => points(&[(0.0, 0.0), (1.0, 2.0), (2.0, 4.0), (3.0, 6.0)]),
+            "b" => points(&[(0.5, 1.0), (2.5, 1.0)]),
+            _ => vec![],
+        }
+    }
+
+    fn eval(expression: &str) -> Vec<(f64, f64)> {
+        values(evaluate(&Expression::parse(expression).unwrap(), &lookup))
+    }
+
+    #[test]
+    fn resample_signals() {
+        assert_eq!(
+            vec![(0.5, 0.0), (1.0, 1.0), (2.0, 3.0), (2.5, 4.0)],
+            eval("a - b")
+        );
+        assert_eq!(vec![(0.5, 3.0), (2.5, 3.0)], eval("b * 2 + 1"));
+        assert!(eval("a + c").is_empty());
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            vec![(1.0, 2.0), (2.0, 2.0), (3.0, 2.0)],
+            eval("derivative(a)")
+        );
+        assert_eq!(
+            vec![(0.0, 0.0), (1.0, 1.0), (2.0, 4.0), (3.0, 9.0)],
+            eval("integral(a)")
+        );
+        assert_eq!(
+            vec![(0.0, 0.0), (1.0, 1.0), (2.0, 3.0), (3.0, 5.0)],
+            eval("avg(a, 1.5)")
+        );
+        assert_eq!(
+            vec![(0.0, 0.0), (1.0, 2.0), (2.0, 4.0), (3.0, 6.0)],
+            eval("abs(-a)")
+        );
+    }
+}
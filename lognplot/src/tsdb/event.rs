@@ -0,0 +1,169 @@
+//! Log events with key/value attributes.
+//!
+//! Events can be used to log state machine transitions and
+//! similar things which happen at a certain point in time.
+
+use super::heap_size::HeapSize;
+use super::Metrics;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A single event, described by a set of attributes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub attributes: HashMap<String, String>,
+}
+
+impl Event {
+    pub fn new(attributes: HashMap<String, String>) -> Self {
+        Event { attributes }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Sort the attributes, to have a stable order:
+        let mut attributes: Vec<(&String, &String)> = self.attributes.iter().collect();
+        attributes.sort();
+        let attributes: Vec<String> = attributes
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        write!(f, "{}", attributes.join(", "))
+    }
+}
+
+/// The reference counts allocated next to the text of an `Arc<str>`.
+const ARC_STR_OVERHEAD: usize = 2 * std::mem::size_of::<usize>();
+
+/// Metrics about events: count the events, and count how often
+/// each attribute had a certain value.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventMetrics {
+    pub count: usize,
+
+    /// The number of times each attribute value occurred, sorted by
+    /// attribute and value.
+    ///
+    /// The names are allocated when counting events, and shared with
+    /// the metrics which include these metrics, such as parent nodes.
+    attributes: Vec<(Arc<str>, Arc<str>, usize)>,
+}
+
+impl EventMetrics {
+    /// How often the given attribute had the given value.
+    pub fn value_count(&self, key: &str, value: &str) -> usize {
+        match self.find(key, value) {
+            Ok(index) => self.attributes[index].2,
+            Err(_) => 0,
+        }
+    }
+
+    /// The counts of all values of the given attribute.
+    pub fn value_counts(&self, key: &str) -> Vec<(&str, usize)> {
+        self.attributes
+            .iter()
+            .filter(|(k, _, _)| k.as_ref() == key)
+            .map(|(_, value, count)| (value.as_ref(), *count))
+            .collect()
+    }
+
+    fn find(&self, key: &str, value: &str) -> Result<usize, usize> {
+        self.attributes
+            .binary_search_by(|(k, v, _)| (k.as_ref(), v.as_ref()).cmp(&(key, value)))
+    }
+}
+
+impl From<Event> for EventMetrics {
+    fn from(event: Event) -> Self {
+        let mut metrics = EventMetrics {
+            count: 0,
+            attributes: vec![],
+        };
+        metrics.update(&event);
+        metrics
+    }
+}
+
+impl HeapSize for EventMetrics {
+    fn heap_size(&self) -> usize {
+        // Names shared with included metrics are counted by both, which
+        // overestimates the memory by about the part of the inner nodes.
+        let names: usize = self
+            .attributes
+            .iter()
+            .map(|(key, value, _)| 2 * ARC_STR_OVERHEAD + key.len() + value.len())
+            .sum();
+        self.attributes.capacity() * std::mem::size_of::<(Arc<str>, Arc<str>, usize)>() + names
+    }
+}
+
+impl Metrics<Event> for EventMetrics {
+    fn update(&mut self, event: &Event) {
+        self.count += 1;
+        for (key, value) in &event.attributes {
+            match self.find(key, value) {
+                Ok(index) => self.attributes[index].2 += 1,
+                Err(index) => self.attributes.insert(
+                    index,
+                    (Arc::from(key.as_str()), Arc::from(value.as_str()), 1),
+                ),
+            }
+        }
+    }
+
+    fn include(&mut self, metrics: &EventMetrics) {
+        self.count += metrics.count;
+        for (key, value, count) in &metrics.attributes {
+            match self.find(key, value) {
+                Ok(index) => self.attributes[index].2 += count,
+                Err(index) => self
+                    .attributes
+                    .insert(index, (key.clone(), value.clone(), *count)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventMetrics};
+    use crate::tsdb::heap_size::HeapSize;
+    use crate::tsdb::Metrics;
+
+    fn state_event(state: &str) -> Event {
+        let attributes = vec![("state".to_owned(), state.to_owned())];
+        Event::new(attributes.into_iter().collect())
+    }
+
+    #[test]
+    fn count_attribute_values() {
+        let mut metrics = EventMetrics::from(state_event("idle"));
+        metrics.update(&state_event("running"));
+        metrics.update(&state_event("idle"));
+
+        assert_eq!(3, metrics.count);
+        assert_eq!(2, metrics.value_count("state", "idle"));
+        assert_eq!(1, metrics.value_count("state", "running"));
+        assert_eq!(0, metrics.value_count("state", "error"));
+        assert_eq!(
+            vec![("idle", 2), ("running", 1)],
+            metrics.value_counts("state")
+        );
+
+        metrics.include(&metrics.clone());
+        assert_eq!(6, metrics.count);
+        assert_eq!(4, metrics.value_count("state", "idle"));
+        assert_eq!("state=idle", state_event("idle").to_string());
+    }
+
+    #[test]
+    fn count_name_memory() {
+        let mut metrics = EventMetrics::from(state_event("idle"));
+        let small = metrics.heap_size();
+        metrics.update(&state_event(&"x".repeat(1000)));
+        assert!(metrics.heap_size() > small + 1000);
+    }
+}
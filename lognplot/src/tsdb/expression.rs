@@ -0,0 +1,364 @@
+//! Expressions over signals, used to define derived signals.
+//!
+//! The expression language supports:
+//! - numbers, such as `2` or `1.5e3`
+//! - signal names, such as `motor.speed`, or quoted as `"motor speed"`
+//! - the operators `+`, `-`, `*` and `/`, and parentheses
+//! - the functions `abs(x)`, `derivative(x)`, `integral(x)`,
+//!   and `avg(x, seconds)` for a moving average over a time window.
+
+use crate::time::Duration;
+use std::fmt;
+
+/// A parsed expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Constant(f64),
+    Signal(String),
+    Negate(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+    Abs(Box<Expression>),
+    Derivative(Box<Expression>),
+    Integral(Box<Expression>),
+
+    /// Moving average over the given time window.
+    Average(Box<Expression>, Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    pub fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Operator::Add => a + b,
+            Operator::Subtract => a - b,
+            Operator::Multiply => a * b,
+            Operator::Divide => a / b,
+        }
+    }
+}
+
+/// Error in the text of an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpressionError {
+    /// Character offset in the expression text.
+    pub position: usize,
+    pub message: String,
+}
+
+impl ExpressionError {
+    pub fn new(position: usize, message: &str) -> Self {
+        ExpressionError {
+            position,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+impl Expression {
+    /// Parse an expression.
+    ///
+    /// The expression must refer to at least one signal, since the
+    /// signals determine the timestamps of the result.
+    pub fn parse(text: &str) -> Result<Self, ExpressionError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            index: 0,
+            end: text.chars().count(),
+        };
+        let expression = parser.parse_expression()?;
+        if let Some((position, _)) = parser.peek() {
+            return Err(ExpressionError::new(*position, "Unexpected input"));
+        }
+        if expression.signals().is_empty() {
+            return Err(ExpressionError::new(0, "Expression uses no signals"));
+        }
+        Ok(expression)
+    }
+
+    /// The names of all signals used in this expression.
+    pub fn signals(&self) -> Vec<String> {
+        let mut signals = vec![];
+        self.collect_signals(&mut signals);
+        signals.sort();
+        signals.dedup();
+        signals
+    }
+
+    fn collect_signals(&self, signals: &mut Vec<String>) {
+        match self {
+            Expression::Constant(_) => {}
+            Expression::Signal(name) => signals.push(name.clone()),
+            Expression::Binary(a, _, b) => {
+                a.collect_signals(signals);
+                b.collect_signals(signals);
+            }
+            Expression::Negate(a)
+            | Expression::Abs(a)
+            | Expression::Derivative(a)
+            | Expression::Integral(a)
+            | Expression::Average(a, _) => a.collect_signals(signals),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+/// Split the expression text into tokens, with their position.
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().enumerate().peekable();
+
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            ' ' | '\t' | '\n' => continue,
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            ',' => Token::Comma,
+            '"' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => name.push(c),
+                        None => {
+                            return Err(ExpressionError::new(position, "Unterminated name"));
+                        }
+                    }
+                }
+                Token::Name(name)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some((_, c)) = chars.peek() {
+                    let exponent_sign = (*c == '-' || *c == '+') && number.ends_with(['e', 'E']);
+                    if c.is_ascii_digit() || *c == '.' || *c == 'e' || *c == 'E' || exponent_sign {
+                        number.push(*c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = number
+                    .parse()
+                    .map_err(|_| ExpressionError::new(position, "Invalid number"))?;
+                Token::Number(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some((_, c)) = chars.peek() {
+                    if c.is_alphanumeric() || *c == '_' || *c == '.' {
+                        name.push(*c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                Token::Name(name)
+            }
+            _ => {
+                return Err(ExpressionError::new(position, "Unexpected character"));
+            }
+        };
+        tokens.push((position, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+
+    /// Position of the end of the text, for errors at the end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ExpressionError> {
+        let token = self
+            .tokens
+            .get(self.index)
+            .cloned()
+            .ok_or_else(|| ExpressionError::new(self.end, "Unexpected end of expression"))?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token, message: &str) -> Result<(), ExpressionError> {
+        let (position, token) = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(ExpressionError::new(position, message))
+        }
+    }
+
+    /// Parse a sum or difference of terms.
+    fn parse_expression(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expression = self.parse_term()?;
+        while let Some((_, Token::Operator(op @ Operator::Add)))
+        | Some((_, Token::Operator(op @ Operator::Subtract))) = self.peek().cloned()
+        {
+            self.index += 1;
+            let rhs = self.parse_term()?;
+            expression = Expression::Binary(Box::new(expression), op, Box::new(rhs));
+        }
+        Ok(expression)
+    }
+
+    /// Parse a product or quotient of factors.
+    fn parse_term(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expression = self.parse_factor()?;
+        while let Some((_, Token::Operator(op @ Operator::Multiply)))
+        | Some((_, Token::Operator(op @ Operator::Divide))) = self.peek().cloned()
+        {
+            self.index += 1;
+            let rhs = self.parse_factor()?;
+            expression = Expression::Binary(Box::new(expression), op, Box::new(rhs));
+        }
+        Ok(expression)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expression, ExpressionError> {
+        let (position, token) = self.next()?;
+        match token {
+            Token::Number(value) => Ok(Expression::Constant(value)),
+            Token::Operator(Operator::Subtract) => {
+                Ok(Expression::Negate(Box::new(self.parse_factor()?)))
+            }
+            Token::OpenParen => {
+                let expression = self.parse_expression()?;
+                self.expect(Token::CloseParen, "Expected ')'")?;
+                Ok(expression)
+            }
+            Token::Name(name) => {
+                if let Some((_, Token::OpenParen)) = self.peek() {
+                    self.index += 1;
+                    self.parse_function(position, &name)
+                } else {
+                    Ok(Expression::Signal(name))
+                }
+            }
+            _ => Err(ExpressionError::new(position, "Unexpected token")),
+        }
+    }
+
+    /// Parse the arguments of a function, after the opening parenthesis.
+    fn parse_function(
+        &mut self,
+        position: usize,
+        name: &str,
+    ) -> Result<Expression, ExpressionError> {
+        let argument = Box::new(self.parse_expression()?);
+        if argument.signals().is_empty() {
+            return Err(ExpressionError::new(
+                position,
+                "Function argument uses no signals",
+            ));
+        }
+
+        let expression = match name {
+            "abs" => Expression::Abs(argument),
+            "derivative" => Expression::Derivative(argument),
+            "integral" => Expression::Integral(argument),
+            "avg" => {
+                self.expect(Token::Comma, "Expected ',' and a time window")?;
+                let (window_position, window) = self.next()?;
+                match window {
+                    Token::Number(seconds) if seconds > 0.0 => {
+                        Expression::Average(argument, Duration::from_secs_f64(seconds))
+                    }
+                    _ => {
+                        return Err(ExpressionError::new(
+                            window_position,
+                            "Expected a positive time window in seconds",
+                        ));
+                    }
+                }
+            }
+            _ => {
+                return Err(ExpressionError::new(position, "Unknown function"));
+            }
+        };
+
+        self.expect(Token::CloseParen, "Expected ')'")?;
+        Ok(expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expression, Operator};
+    use crate::time::Duration;
+
+    fn signal(name: &str) -> Box<Expression> {
+        Box::new(Expression::Signal(name.to_owned()))
+    }
+
+    #[test]
+    fn parse_expressions() {
+        assert_eq!(
+            Expression::Binary(
+                signal("a"),
+                Operator::Subtract,
+                Box::new(Expression::Binary(
+                    signal("b.c"),
+                    Operator::Multiply,
+                    Box::new(Expression::Constant(2.5))
+                ))
+            ),
+            Expression::parse("a - b.c * 2.5").unwrap()
+        );
+
+        assert_eq!(
+            Expression::Average(signal("motor speed"), Duration::from_millis(500)),
+            Expression::parse("avg(\"motor speed\", 0.5)").unwrap()
+        );
+
+        let expression = Expression::parse("-(integral(a) + derivative(b)) / abs(a)").unwrap();
+        assert_eq!(vec!["a".to_owned(), "b".to_owned()], expression.signals());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(4, Expression::parse("a + * b").unwrap_err().position);
+        assert_eq!(6, Expression::parse("(a + b").unwrap_err().position);
+        assert!(Expression::parse("1 + 2").is_err());
+        assert!(Expression::parse("foo(a)").is_err());
+        assert!(Expression::parse("avg(a)").is_err());
+        assert!(Expression::parse("a b").is_err());
+    }
+}
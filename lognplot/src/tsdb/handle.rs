@@ -1,11 +1,23 @@
 //! Thread usable handle. Wrapper around a database.
 
-use super::{ChangeSubscriber, DataChangeEvent};
+use super::async_query::QueryPool;
+use super::db::observations_timespan;
 use super::{
-    Observation, ProfileEvent, Query, QueryResult, QuickSummary, Sample, Summary, Text, TsDb,
+    AsyncQuery, Bins, ExpressionError, Fanout, Histogram, LookupMode, Metadata, QuantileSketch,
+    SignalTree, Snapshot,
 };
-use crate::time::TimeSpan;
+use super::{
+    CallSpan, ChangeSubscriber, CountMetrics, DataChangeEvent, LastValue, RangeQueryResult,
+};
+use super::{
+    Event, LogRecord, Observation, ProfileEvent, Query, QueryResult, QuickSummary, RetentionPolicy,
+    Sample, SharedTrack, Summary, Text, Track, TrackType, Trigger, TriggerEvent, TriggerInput,
+    TsDb, Vector,
+};
+use crate::time::{TimeSpan, TimeStamp};
 use futures::channel::mpsc;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 pub type TsDbHandle = Arc<LockedTsDb>;
@@ -14,45 +26,358 @@ pub fn make_handle(db: TsDb) -> TsDbHandle {
     Arc::new(LockedTsDb::new(db))
 }
 
+/// A database which can be used from multiple threads.
+///
+/// The database lock is only held to look up a trace. Queries and
+/// additions lock the trace itself, so that a query on one trace
+/// does not block additions to other traces.
+///
+/// To keep showing the data as it was at some moment, while new data
+/// is still added to the database, take a `snapshot`.
 #[derive(Debug)]
 pub struct LockedTsDb {
     db: Mutex<TsDb>,
+
+    /// Worker threads for asynchronous queries.
+    query_pool: QueryPool,
+
+    /// A lock per trace name, taken while adding observations to the trace.
+    ///
+    /// Only names which are being added to are kept here.
+    inserting: Mutex<HashMap<String, Arc<Mutex<()>>>>,
 }
 
 impl LockedTsDb {
     pub fn new(db: TsDb) -> Self {
-        LockedTsDb { db: Mutex::new(db) }
+        LockedTsDb {
+            db: Mutex::new(db),
+            query_pool: QueryPool::default(),
+            inserting: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a read-only view of the current data, see `Snapshot`.
+    pub fn snapshot(&self) -> Snapshot {
+        // Derived signals are evaluated after unlocking the database:
+        let (signals, metadata) = {
+            let db = self.db.lock().unwrap();
+            (db.all_signals(), db.all_metadata())
+        };
+        Snapshot::from_signals(&signals, metadata)
     }
 
     pub fn get_signal_names(&self) -> Vec<String> {
         self.db.lock().unwrap().get_signal_names()
     }
 
+    /// Get a trace, without keeping the database locked.
+    ///
+    /// Derived signals are evaluated after unlocking the database.
+    pub fn track(&self, name: &str) -> Option<SharedTrack> {
+        let signals = self.db.lock().unwrap().signals(&[name]);
+        signals.track(name)
+    }
+
     /// Add a single observation.
     pub fn add_value(&self, name: &str, sample: Observation<Sample>) {
-        self.db.lock().unwrap().add_value(name, sample);
+        self.add_values(name, vec![sample]);
     }
 
     /// Add a series of observations
     pub fn add_values(&self, name: &str, samples: Vec<Observation<Sample>>) {
-        self.db.lock().unwrap().add_values(name, samples);
+        self.insert(
+            name,
+            TrackType::Value,
+            &[],
+            samples,
+            Track::add_value_observations,
+        );
     }
 
     pub fn add_text(&self, name: &str, text: Observation<Text>) {
-        self.db.lock().unwrap().add_text(name, text);
+        self.insert(
+            name,
+            TrackType::Text,
+            &[],
+            vec![text],
+            Track::add_text_observations,
+        );
+    }
+
+    /// Add a series of vector observations.
+    pub fn add_vectors(
+        &self,
+        name: &str,
+        components: &[String],
+        observations: Vec<Observation<Vector>>,
+    ) {
+        self.insert(
+            name,
+            TrackType::Vector,
+            components,
+            observations,
+            Track::add_vector_observations,
+        );
+    }
+
+    pub fn add_event(&self, name: &str, event: Observation<Event>) {
+        self.insert(
+            name,
+            TrackType::Event,
+            &[],
+            vec![event],
+            Track::add_event_observations,
+        );
+    }
+
+    pub fn add_log(&self, name: &str, record: Observation<LogRecord>) {
+        self.insert(
+            name,
+            TrackType::Log,
+            &[],
+            vec![record],
+            Track::add_log_observations,
+        );
     }
 
     pub fn add_profile_event(&self, name: &str, event: Observation<ProfileEvent>) {
-        self.db.lock().unwrap().add_profile_event(name, event);
+        self.insert(
+            name,
+            TrackType::Profile,
+            &[],
+            vec![event],
+            Track::add_profile_observations,
+        );
+    }
+
+    /// Add observations to a trace, using the given function.
+    ///
+    /// The database is locked to find the trace, and to do the bookkeeping
+    /// afterwards. The observations are added while only the trace is locked,
+    /// so other threads can use other traces in the meantime.
+    ///
+    /// Old data is dropped after unlocking the database as well, since
+    /// this has to wait for queries of the traces involved.
+    ///
+    /// Additions to the same trace are done one after the other. This way
+    /// the observations are added to the trace they were checked against,
+    /// also when another thread replaces the trace by a backup trace,
+    /// and the order on disk is the order in memory.
+    fn insert<V, F>(
+        &self,
+        name: &str,
+        typ: TrackType,
+        components: &[String],
+        observations: Vec<Observation<V>>,
+        add: F,
+    ) where
+        V: Serialize + TriggerInput,
+        F: FnOnce(&mut Track, Vec<Observation<V>>),
+    {
+        if observations.is_empty() {
+            return;
+        }
+
+        let trace_lock = self
+            .inserting
+            .lock()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_default()
+            .clone();
+        let inserting = trace_lock.lock().unwrap();
+
+        let amount = observations.len();
+        let timespan = observations_timespan(&observations);
+        let track = self
+            .db
+            .lock()
+            .unwrap()
+            .begin_insert(name, typ, components, &observations);
+        add(&mut track.write().unwrap(), observations);
+        let pending = self
+            .db
+            .lock()
+            .unwrap()
+            .added_samples(name, amount, &timespan);
+        drop(inserting);
+
+        self.done_inserting(name, trace_lock);
+
+        let changed = pending.apply();
+        if !changed.is_empty() {
+            self.db.lock().unwrap().retention_applied(changed);
+        }
+    }
+
+    /// Forget the lock of a trace name, when no other thread uses it.
+    fn done_inserting(&self, name: &str, trace_lock: Arc<Mutex<()>>) {
+        let mut inserting = self.inserting.lock().unwrap();
+        // Other threads only get the lock from the map, while it is locked:
+        if Arc::strong_count(&trace_lock) == 2 {
+            inserting.remove(name);
+        }
+    }
+
+    /// Get the signal names, arranged as a tree of namespaces.
+    pub fn get_signal_tree(&self) -> SignalTree {
+        self.db.lock().unwrap().get_signal_tree()
+    }
+
+    pub fn get_signal_names_in(&self, namespace: &str) -> Vec<String> {
+        self.db.lock().unwrap().get_signal_names_in(namespace)
+    }
+
+    /// Add a signal which is computed from other signals.
+    pub fn add_derived(&self, name: &str, expression: &str) -> Result<(), ExpressionError> {
+        self.db.lock().unwrap().add_derived(name, expression)
+    }
+
+    /// Update the metadata of a signal, such as its unit.
+    pub fn set_metadata(&self, name: &str, metadata: Metadata) {
+        self.db.lock().unwrap().set_metadata(name, metadata);
+    }
+
+    pub fn metadata(&self, name: &str) -> Option<Metadata> {
+        self.db.lock().unwrap().metadata(name)
+    }
+
+    /// Get the units of several signals, for those which have one.
+    ///
+    /// The database is locked only once.
+    pub fn units(&self, names: &[&str]) -> Vec<String> {
+        let db = self.db.lock().unwrap();
+        names
+            .iter()
+            .filter_map(|name| db.metadata(name).and_then(|m| m.unit))
+            .collect()
+    }
+
+    /// The names of the components of a vector signal.
+    ///
+    /// This is empty for other signals.
+    pub fn components(&self, name: &str) -> Option<Vec<String>> {
+        Some(self.track(name)?.read().unwrap().components().to_vec())
     }
 
     /// Query the database.
     pub fn query(&self, name: &str, query: Query) -> Option<QueryResult> {
-        self.db.lock().unwrap().query(name, query)
+        Some(self.track(name)?.read().unwrap().query(query))
+    }
+
+    /// Query a trace on a worker thread, without blocking the caller.
+    ///
+    /// Large queries can take a while, so the caller should cancel
+    /// queries it no longer needs, for example with a `QuerySlot`.
+    pub fn query_async(&self, name: &str, query: Query) -> AsyncQuery {
+        match self.track(name) {
+            Some(track) => self.query_pool.query(track, query),
+            None => AsyncQuery::ready(None),
+        }
+    }
+
+    /// Run other queries on the worker threads, for example several
+    /// queries of a chart, or queries of a snapshot.
+    pub fn run_async<T, F>(&self, query: F) -> AsyncQuery<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Option<T> + Send + 'static,
+    {
+        self.query_pool.run(query)
+    }
+
+    /// Query a text trace.
+    pub fn query_text(
+        &self,
+        name: &str,
+        query: Query,
+    ) -> Option<RangeQueryResult<Text, CountMetrics>> {
+        self.track(name)?.read().unwrap().query_text(query)
+    }
+
+    /// Query a profile trace.
+    pub fn query_profile(
+        &self,
+        name: &str,
+        query: Query,
+    ) -> Option<RangeQueryResult<ProfileEvent, CountMetrics>> {
+        self.track(name)?.read().unwrap().query_profile(query)
+    }
+
+    /// Query the calls of a profile trace.
+    pub fn query_calls(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+        amount: usize,
+    ) -> Option<Vec<CallSpan>> {
+        self.track(name)?
+            .read()
+            .unwrap()
+            .query_calls(timespan, amount)
+    }
+
+    /// Get the observation of any kind of trace at a certain time.
+    pub fn observation_at(
+        &self,
+        name: &str,
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Option<LastValue> {
+        self.track(name)?
+            .read()
+            .unwrap()
+            .observation_at(timestamp, mode)
+    }
+
+    /// Get the value of a trace at a certain time.
+    pub fn value_at(
+        &self,
+        name: &str,
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Option<Observation<Sample>> {
+        self.track(name)?.read().unwrap().value_at(timestamp, mode)
+    }
+
+    /// Get the values of several traces at a certain time.
+    ///
+    /// The database is locked only once, to find all traces.
+    pub fn values_at(
+        &self,
+        names: &[&str],
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Vec<Option<Observation<Sample>>> {
+        let signals = self.db.lock().unwrap().signals(names);
+        names
+            .iter()
+            .map(|name| {
+                signals
+                    .track(name)?
+                    .read()
+                    .unwrap()
+                    .value_at(timestamp, mode)
+            })
+            .collect()
+    }
+
+    /// Get the distribution of the values of a trace in a time span.
+    ///
+    /// The bins are either a number of bins of equal width, spanning
+    /// all values, or given by their edges.
+    pub fn histogram(&self, name: &str, timespan: &TimeSpan, bins: &Bins) -> Option<Histogram> {
+        self.track(name)?.read().unwrap().histogram(timespan, bins)
+    }
+
+    /// Sketch the distribution of the values of a trace in a time span,
+    /// to estimate quantiles.
+    pub fn quantile_sketch(&self, name: &str, timespan: &TimeSpan) -> Option<QuantileSketch> {
+        self.track(name)?.read().unwrap().quantile_sketch(timespan)
     }
 
     pub fn get_raw_samples(&self, name: &str) -> Option<Vec<Observation<Sample>>> {
-        self.db.lock().unwrap().get_raw_samples(name)
+        Some(self.track(name)?.read().unwrap().to_vec())
     }
 
     /// Grab a quick data summary.
@@ -61,7 +386,7 @@ impl LockedTsDb {
     /// - sample count
     /// - last observation
     pub fn quick_summary(&self, name: &str) -> Option<QuickSummary> {
-        self.db.lock().unwrap().quick_summary(name)
+        self.track(name)?.read().unwrap().quick_summary()
     }
 
     /// Retrieve a detailed summary of the data.
@@ -71,7 +396,52 @@ impl LockedTsDb {
     /// - sample count
     /// - first, last observations
     pub fn summary(&self, name: &str, timespan: Option<&TimeSpan>) -> Option<Summary> {
-        self.db.lock().unwrap().summary(name, timespan)
+        self.track(name)?.read().unwrap().summary(timespan)
+    }
+
+    /// Write pending data to disk, when the database is stored on disk.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.db.lock().unwrap().flush()
+    }
+
+    /// Move data into a backup trace when samples arrive too late.
+    pub fn set_backup_on_rewind(&self, window: Option<f64>) {
+        self.db.lock().unwrap().set_backup_on_rewind(window);
+    }
+
+    /// Limit the amount of data kept in memory.
+    pub fn set_retention(&self, policy: RetentionPolicy) {
+        self.db.lock().unwrap().set_retention(policy);
+    }
+
+    /// Limit the amount of data kept in memory for a single trace.
+    pub fn set_trace_retention(&self, name: &str, policy: RetentionPolicy) {
+        self.db.lock().unwrap().set_trace_retention(name, policy);
+    }
+
+    /// Set the fanout of the tree of a single trace.
+    pub fn set_trace_fanout(&self, name: &str, fanout: Fanout) {
+        self.db.lock().unwrap().set_trace_fanout(name, fanout);
+    }
+
+    /// Add a trigger, evaluated on newly added data.
+    pub fn add_trigger(&self, name: &str, trigger: Trigger) {
+        self.db.lock().unwrap().add_trigger(name, trigger);
+    }
+
+    /// Remove a trigger, see `TsDb::remove_trigger`.
+    pub fn remove_trigger(&self, name: &str) {
+        self.db.lock().unwrap().remove_trigger(name);
+    }
+
+    /// Get the definition of the trigger with the given name.
+    pub fn get_trigger(&self, name: &str) -> Option<Trigger> {
+        self.db.lock().unwrap().get_trigger(name)
+    }
+
+    /// Get the most recent trigger events, oldest first.
+    pub fn trigger_events(&self) -> Vec<TriggerEvent> {
+        self.db.lock().unwrap().trigger_events()
     }
 
     /// Delete all data from the database.
@@ -79,6 +449,16 @@ impl LockedTsDb {
         self.db.lock().unwrap().delete_all();
     }
 
+    /// Delete a single signal from the database.
+    pub fn delete(&self, name: &str) {
+        self.db.lock().unwrap().delete(name);
+    }
+
+    /// Delete all signals in a namespace.
+    pub fn delete_namespace(&self, namespace: &str) {
+        self.db.lock().unwrap().delete_namespace(namespace);
+    }
+
     /// Register database change handler.
     pub fn new_notify_queue(&self) -> mpsc::Receiver<DataChangeEvent> {
         let (sender, receiver) = mpsc::channel::<DataChangeEvent>(0);
@@ -101,3 +481,23 @@ impl std::fmt::Display for LockedTsDb {
         write!(f, "{}", self.db.lock().unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Observation, Sample, TsDb};
+    use crate::time::TimeStamp;
+
+    #[test]
+    fn forget_insert_locks() {
+        let db = TsDb::default().into_handle();
+        for i in 0..100 {
+            let name = format!("signal{}", i);
+            db.add_value(
+                &name,
+                Observation::new(TimeStamp::new(0.0), Sample::new(1.0)),
+            );
+            db.delete(&name);
+        }
+        assert!(db.inserting.lock().unwrap().is_empty());
+    }
+}
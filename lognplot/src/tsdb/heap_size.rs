@@ -0,0 +1,138 @@
+//! Estimates of the memory used by observations and metrics on the heap.
+//!
+//! Retention policies limit the memory of traces, which for most types
+//! of observations is dominated by data outside the observation itself,
+//! such as the text of a log message.
+
+use super::{
+    Aggregation, CountMetrics, Event, LogMetrics, LogRecord, Metrics, Observation, ProfileEvent,
+    Sample, SampleMetrics, Text, Vector,
+};
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Memory used by a value on the heap.
+pub trait HeapSize {
+    /// The amount of bytes allocated by this value, next to its own size.
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl HeapSize for HashMap<String, String> {
+    fn heap_size(&self) -> usize {
+        // A hash table stores a control byte next to each entry.
+        let table = self.capacity() * (size_of::<(String, String)>() + 1);
+        let entries: usize = self
+            .iter()
+            .map(|(key, value)| key.heap_size() + value.heap_size())
+            .sum();
+        table + entries
+    }
+}
+
+impl HeapSize for f64 {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl<V: HeapSize> HeapSize for Observation<V> {
+    fn heap_size(&self) -> usize {
+        self.value.heap_size()
+    }
+}
+
+impl<V, M> HeapSize for Aggregation<V, M>
+where
+    M: Metrics<V> + From<V> + Clone + HeapSize,
+    V: Clone,
+{
+    fn heap_size(&self) -> usize {
+        self.metrics().heap_size()
+    }
+}
+
+impl HeapSize for Sample {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for SampleMetrics {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for CountMetrics {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for Text {
+    fn heap_size(&self) -> usize {
+        self.text.heap_size()
+    }
+}
+
+impl HeapSize for ProfileEvent {
+    fn heap_size(&self) -> usize {
+        match self {
+            ProfileEvent::FunctionEnter { name } => name.heap_size(),
+            ProfileEvent::FunctionExit => 0,
+        }
+    }
+}
+
+impl HeapSize for Vector {
+    fn heap_size(&self) -> usize {
+        self.values.heap_size()
+    }
+}
+
+impl HeapSize for Event {
+    fn heap_size(&self) -> usize {
+        self.attributes.heap_size()
+    }
+}
+
+impl HeapSize for LogRecord {
+    fn heap_size(&self) -> usize {
+        self.target.heap_size() + self.message.heap_size() + self.fields.heap_size()
+    }
+}
+
+impl HeapSize for LogMetrics {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::LogLevel;
+    use super::{HeapSize, LogRecord, Sample, Text, Vector};
+
+    #[test]
+    fn heap_sizes() {
+        assert_eq!(0, Sample::new(1.0).heap_size());
+        assert_eq!(100, Text::new("x".repeat(100)).heap_size());
+        assert_eq!(24, Vector::new(vec![1.0, 2.0, 3.0]).heap_size());
+
+        let record = LogRecord::new(LogLevel::Info, "main".to_owned(), "hello".to_owned())
+            .with_field("user", "me".to_owned());
+        assert!(record.heap_size() > 4 + 5 + 4 + 2);
+    }
+}
@@ -0,0 +1,199 @@
+//! Distribution of the values of a trace over a range of value bins.
+//!
+//! The histogram is filled from the b-tree: sub trees of which the
+//! minimum and maximum fall into a single bin, or outside of all bins,
+//! are counted at once, without visiting their observations.
+
+use super::btree::RangeVisitor;
+use super::{Aggregation, Observation, Sample, SampleMetrics};
+
+/// How to divide the value range into bins.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Bins {
+    /// Bins of equal width between the minimum and maximum value.
+    Count(usize),
+
+    /// Bins between the given increasing edges.
+    Edges(Vec<f64>),
+}
+
+/// The number of values per bin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// The edges of the bins, one more than the number of bins.
+    ///
+    /// A bin includes its lower edge, the last bin also its upper edge.
+    pub edges: Vec<f64>,
+
+    /// The number of values in each bin.
+    pub counts: Vec<usize>,
+
+    /// The number of values outside of the bins, including NaN and
+    /// infinite values.
+    pub outside: usize,
+}
+
+impl Histogram {
+    /// Create an empty histogram with the given bin edges.
+    ///
+    /// Returns None when there are less than two edges, or when the
+    /// edges are not increasing.
+    pub fn new(edges: Vec<f64>) -> Option<Self> {
+        let increasing = edges.windows(2).all(|pair| pair[0] <= pair[1]);
+        if edges.len() < 2 || !increasing || edges.iter().any(|e| !e.is_finite()) {
+            None
+        } else {
+            let counts = vec![0; edges.len() - 1];
+            Some(Histogram {
+                edges,
+                counts,
+                outside: 0,
+            })
+        }
+    }
+
+    /// Create an empty histogram with bins of equal width.
+    pub fn with_equal_bins(min: f64, max: f64, count: usize) -> Option<Self> {
+        let width = (max - min) / count as f64;
+        let mut edges: Vec<f64> = (0..=count).map(|i| min + width * i as f64).collect();
+        // Make sure the maximum is in the last bin, despite rounding:
+        if let Some(last) = edges.last_mut() {
+            *last = max;
+        }
+        Histogram::new(edges)
+    }
+
+    /// The total number of values in this histogram.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum::<usize>() + self.outside
+    }
+
+    /// Find the bin of a value.
+    fn bin(&self, value: f64) -> Option<usize> {
+        let last = self.counts.len() - 1;
+        if value < self.edges[0] || value > self.edges[last + 1] || value.is_nan() {
+            None
+        } else {
+            let index = self.edges.partition_point(|e| *e <= value) - 1;
+            Some(index.min(last))
+        }
+    }
+
+    /// Count some finite values within a single bin, or outside of the bins.
+    fn add(&mut self, bin: Option<usize>, count: usize) {
+        if let Some(bin) = bin {
+            self.counts[bin] += count;
+        } else {
+            self.outside += count;
+        }
+    }
+}
+
+impl RangeVisitor<Sample, SampleMetrics> for Histogram {
+    /// Count all values at once, if they fall into a single bin.
+    fn aggregation(&mut self, aggregation: &Aggregation<Sample, SampleMetrics>) -> bool {
+        let metrics = aggregation.metrics();
+        let non_finite = metrics.nan_count + metrics.inf_count;
+        let finite = metrics.finite_count();
+
+        let (min_bin, max_bin) = (self.bin(metrics.min), self.bin(metrics.max));
+        let below = metrics.max < self.edges[0];
+        let above = metrics.min > self.edges[self.edges.len() - 1];
+
+        if finite == 0 || below || above {
+            self.outside += metrics.count;
+            true
+        } else if min_bin.is_some() && min_bin == max_bin {
+            self.add(min_bin, finite);
+            self.outside += non_finite;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The distribution of dropped values is unknown, so count them
+    /// in the bin of their mean value when they span several bins.
+    fn pruned(&mut self, aggregation: &Aggregation<Sample, SampleMetrics>) {
+        if !self.aggregation(aggregation) {
+            let metrics = aggregation.metrics();
+            let bin = self.bin(metrics.mean());
+            self.add(bin, metrics.finite_count());
+            self.outside += metrics.nan_count + metrics.inf_count;
+        }
+    }
+
+    fn observation(&mut self, observation: &Observation<Sample>) {
+        let bin = self.bin(observation.value.value);
+        self.add(bin, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bins, Histogram};
+    use crate::time::{TimeSpan, TimeStamp};
+    use crate::tsdb::{Observation, Sample, SampleMetrics, Trace};
+
+    fn sine_trace() -> Trace<Sample, SampleMetrics> {
+        let mut trace = Trace::default();
+        for i in 0..10_000 {
+            let value = (i as f64 * 0.001).sin() * 10.0;
+            trace.add_observation(Observation::new(
+                TimeStamp::new(i as f64),
+                Sample::new(value),
+            ));
+        }
+        trace
+    }
+
+    /// Calculate the histogram from all observations in the time span.
+    fn brute_force(
+        trace: &Trace<Sample, SampleMetrics>,
+        timespan: &TimeSpan,
+        edges: Vec<f64>,
+    ) -> Histogram {
+        let mut histogram = Histogram::new(edges).unwrap();
+        for observation in trace.to_vec() {
+            if timespan.contains(&observation.timestamp) {
+                let bin = histogram.bin(observation.value.value);
+                histogram.add(bin, 1);
+            }
+        }
+        histogram
+    }
+
+    #[test]
+    fn histogram_matches_observations() {
+        let trace = sine_trace();
+        let timespan = TimeSpan::new(TimeStamp::new(123.0), TimeStamp::new(8765.0));
+
+        let edges = vec![-5.0, -1.0, 0.0, 1.0, 5.0];
+        let histogram = trace
+            .histogram(&timespan, &Bins::Edges(edges.clone()))
+            .unwrap();
+        assert_eq!(brute_force(&trace, &timespan, edges), histogram);
+        assert_eq!(8643, histogram.total());
+
+        let histogram = trace.histogram(&timespan, &Bins::Count(7)).unwrap();
+        assert_eq!(7, histogram.counts.len());
+        assert_eq!(0, histogram.outside);
+        assert_eq!(
+            brute_force(&trace, &timespan, histogram.edges.clone()),
+            histogram
+        );
+    }
+
+    #[test]
+    fn invalid_bins() {
+        let trace = sine_trace();
+        let timespan = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(10.0));
+        assert!(trace.histogram(&timespan, &Bins::Count(0)).is_none());
+        assert!(trace
+            .histogram(&timespan, &Bins::Edges(vec![1.0, 0.0]))
+            .is_none());
+
+        let empty = TimeSpan::new(TimeStamp::new(-10.0), TimeStamp::new(-1.0));
+        assert!(trace.histogram(&empty, &Bins::Count(10)).is_none());
+    }
+}
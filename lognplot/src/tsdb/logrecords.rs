@@ -1,5 +1,176 @@
-struct LogMetrics {
-    // TODO: amount of messages for each level
+//! Log records, such as the output of the `log` crate.
+//!
+//! Each record has a severity level, a target, usually the module
+//! which emitted the record, a message and optional extra fields.
+
+use super::Metrics;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Severity of a log record, ordered from most to least severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// All levels, from most to least severe.
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl From<log::Level> for LogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => LogLevel::Error,
+            log::Level::Warn => LogLevel::Warn,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Debug => LogLevel::Debug,
+            log::Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single log record.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub level: LogLevel,
+
+    /// The origin of the record, for example a module path.
+    pub target: String,
+
+    pub message: String,
+
+    /// Additional key/value information about the record.
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+}
+
+impl LogRecord {
+    pub fn new(level: LogLevel, target: String, message: String) -> Self {
+        LogRecord {
+            level,
+            target,
+            message,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Attach an extra field to this record.
+    pub fn with_field(mut self, key: &str, value: String) -> Self {
+        self.fields.insert(key.to_owned(), value);
+        self
+    }
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.level, self.target, self.message)
+    }
+}
+
+/// Metrics of log records: the amount of records for each level.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogMetrics {
     /// Total amount of log messages.
     pub count: usize,
+
+    /// Amount of log messages per level.
+    levels: [usize; 5],
+}
+
+impl LogMetrics {
+    /// The amount of records with the given level.
+    pub fn level_count(&self, level: LogLevel) -> usize {
+        self.levels[level.index()]
+    }
+
+    /// The most severe level of all records.
+    pub fn max_level(&self) -> Option<LogLevel> {
+        LogLevel::ALL
+            .iter()
+            .find(|level| self.level_count(**level) > 0)
+            .cloned()
+    }
+}
+
+impl From<LogRecord> for LogMetrics {
+    fn from(record: LogRecord) -> Self {
+        let mut metrics = LogMetrics {
+            count: 0,
+            levels: [0; 5],
+        };
+        metrics.update(&record);
+        metrics
+    }
+}
+
+impl Metrics<LogRecord> for LogMetrics {
+    fn update(&mut self, record: &LogRecord) {
+        self.count += 1;
+        self.levels[record.level.index()] += 1;
+    }
+
+    fn include(&mut self, metrics: &LogMetrics) {
+        self.count += metrics.count;
+        for (count, other) in self.levels.iter_mut().zip(metrics.levels.iter()) {
+            *count += other;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogLevel, LogMetrics, LogRecord};
+    use crate::tsdb::Metrics;
+
+    fn record(level: LogLevel) -> LogRecord {
+        LogRecord::new(level, "app::motor".to_owned(), "bla".to_owned())
+    }
+
+    #[test]
+    fn count_per_level() {
+        let mut metrics = LogMetrics::from(record(LogLevel::Info));
+        metrics.update(&record(LogLevel::Warn));
+        metrics.update(&record(LogLevel::Info));
+
+        assert_eq!(3, metrics.count);
+        assert_eq!(2, metrics.level_count(LogLevel::Info));
+        assert_eq!(1, metrics.level_count(LogLevel::Warn));
+        assert_eq!(0, metrics.level_count(LogLevel::Error));
+        assert_eq!(Some(LogLevel::Warn), metrics.max_level());
+
+        metrics.include(&LogMetrics::from(record(LogLevel::Error)));
+        assert_eq!(4, metrics.count);
+        assert_eq!(Some(LogLevel::Error), metrics.max_level());
+        assert_eq!("INFO app::motor: bla", record(LogLevel::Info).to_string());
+    }
 }
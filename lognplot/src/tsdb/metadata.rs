@@ -0,0 +1,67 @@
+//! Descriptive information about signals.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Information about a signal, such as its unit.
+///
+/// Other information, such as display hints, can be stored
+/// as key/value attributes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The unit of the values, for example `m/s`.
+    pub unit: Option<String>,
+
+    pub description: Option<String>,
+
+    /// Where the signal comes from, for example a device or a program.
+    pub source: Option<String>,
+
+    pub attributes: HashMap<String, String>,
+}
+
+impl Metadata {
+    /// Create metadata with only a unit.
+    pub fn with_unit(unit: &str) -> Self {
+        Metadata {
+            unit: Some(unit.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// Update this metadata with the fields which are set in the other metadata.
+    pub fn update(&mut self, other: Metadata) {
+        if other.unit.is_some() {
+            self.unit = other.unit;
+        }
+        if other.description.is_some() {
+            self.description = other.description;
+        }
+        if other.source.is_some() {
+            self.source = other.source;
+        }
+        self.attributes.extend(other.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metadata;
+
+    #[test]
+    fn update_metadata() {
+        let mut metadata = Metadata::with_unit("V");
+        metadata.description = Some("Supply voltage".to_owned());
+
+        let mut other = Metadata::with_unit("mV");
+        other
+            .attributes
+            .insert("color".to_owned(), "red".to_owned());
+        metadata.update(other);
+
+        assert_eq!(Some("mV".to_owned()), metadata.unit);
+        assert_eq!(Some("Supply voltage".to_owned()), metadata.description);
+        assert_eq!(None, metadata.source);
+        assert_eq!(Some(&"red".to_owned()), metadata.attributes.get("color"));
+    }
+}
@@ -1,14 +1,27 @@
+use crate::time::TimeStamp;
+use serde::{Deserialize, Serialize};
+
 /// Implement this for specific observations.
 pub trait Metrics<V> {
     // TODO: we might merge update and include into a single function?
     fn update(&mut self, sample: &V);
 
+    /// Integrate a single sample, observed at the given time.
+    ///
+    /// By default, the time is ignored.
+    fn update_at(&mut self, sample: &V, _timestamp: &TimeStamp) {
+        self.update(sample);
+    }
+
+    /// Called with the time of the sample these metrics were created from.
+    fn created_at(&mut self, _timestamp: &TimeStamp) {}
+
     /// Include other metrics into this metrics.
     fn include(&mut self, metrics: &Self);
 }
 
 /// The most simple metric which works always: just count the observations.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CountMetrics {
     pub count: usize,
 }
@@ -1,50 +1,96 @@
 //! Time series database, usable as a library.
 
 mod aggregation;
+mod async_query;
 mod btree;
+mod compress;
 mod connection;
 mod db;
+mod derived;
+mod event;
+mod expression;
 mod handle;
-// mod logrecords;
+mod heap_size;
+mod histogram;
+mod logrecords;
+mod metadata;
 mod metrics;
+mod namespace;
 mod notify;
 mod observation;
 mod profile;
 mod query;
 mod query_result;
+mod resample;
+mod retention;
 mod sample;
+mod sketch;
+mod snapshot;
+mod storage;
 mod summary;
 mod text;
 mod trace;
 mod track;
 mod track_type;
+mod trigger;
+mod vector;
 
 pub use aggregation::Aggregation;
+pub use async_query::{AsyncQuery, QuerySlot};
 use btree::Btree;
+pub use btree::Fanout;
+pub use compress::Compress;
 pub use db::TsDb;
+pub use event::{Event, EventMetrics};
+pub use expression::{Expression, ExpressionError, Operator};
 pub use handle::TsDbHandle;
+pub use heap_size::HeapSize;
+pub use histogram::{Bins, Histogram};
+pub use logrecords::{LogLevel, LogMetrics, LogRecord};
+pub use metadata::Metadata;
 pub use metrics::{CountMetrics, Metrics};
-pub use notify::{ChangeSubscriber, DataChangeEvent};
+pub use namespace::{base_name, namespaces, SignalTree, SEPARATOR};
+pub use notify::{ChangeSubscriber, DataChangeEvent, NewSamples};
 pub use observation::Observation;
-pub use profile::ProfileEvent;
+pub use profile::{CallSpan, CallTree, ProfileEvent};
 pub use query::Query;
 pub use query_result::{QueryResult, RangeQueryResult};
-pub use sample::{Sample, SampleMetrics};
-pub use summary::{QuickSummary, Summary};
+pub use resample::{Grid, Interpolate, Interpolation, LookupMode};
+pub use retention::RetentionPolicy;
+pub use sample::{Sample, SampleMetrics, Statistic, STATISTICS};
+pub use sketch::QuantileSketch;
+pub use snapshot::Snapshot;
+pub use summary::{LastValue, QuickSummary, Summary};
 pub use text::Text;
 pub use trace::Trace;
-pub use track::Track;
+pub use track::{SharedTrack, Track};
 pub use track_type::TrackType;
+use trigger::ArmedTrigger;
+pub use trigger::{Trigger, TriggerCondition, TriggerEvent, TriggerInput};
+pub use vector::{Vector, VectorMetrics};
 
 #[cfg(test)]
 mod tests {
     use super::connection::Connection;
     use super::query::Query;
     use super::Observation;
+    use super::ProfileEvent;
     use super::Sample;
     use super::TsDb;
+    use super::{ChangeSubscriber, DataChangeEvent};
+    use super::{Event, LookupMode, QueryResult, RangeQueryResult, Summary, Text, Vector};
+    use super::{QuerySlot, Trigger, TriggerCondition};
     use crate::time::TimeModifiers;
+    use crate::time::TimeSpan;
     use crate::time::TimeStamp;
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::{FutureExt, StreamExt};
+
+    /// Take the change event waiting in the queue, if any.
+    fn pending_event(receiver: &mut mpsc::Receiver<DataChangeEvent>) -> Option<DataChangeEvent> {
+        receiver.next().now_or_never().flatten()
+    }
 
     #[test]
     fn basic_usage() {
@@ -81,4 +127,486 @@ mod tests {
 
         db.close();
     }
+
+    #[test]
+    fn delete_signal() {
+        let mut db = TsDb::default();
+        let ts = TimeStamp::from_seconds(0);
+        db.add_value("foo", Observation::new(ts.clone(), Sample::new(1.0)));
+        db.add_value("bar", Observation::new(ts, Sample::new(2.0)));
+
+        let (sender, mut receiver) = mpsc::channel(0);
+        db.register_notifier(ChangeSubscriber::new(sender));
+        let event = pending_event(&mut receiver).unwrap();
+        assert_eq!(2, event.new_signals.len());
+
+        db.delete("foo");
+        db.delete("non-existing");
+        assert!(db.quick_summary("foo").is_none());
+        assert!(db.quick_summary("bar").is_some());
+        assert_eq!(vec!["bar".to_owned()], db.get_signal_names());
+
+        let event = pending_event(&mut receiver).unwrap();
+        assert!(event.deleted_signals.contains("foo"));
+        assert_eq!(1, event.deleted_signals.len());
+    }
+
+    #[test]
+    fn late_samples() {
+        let mut db = TsDb::default();
+        for i in &[0, 2, 1, 4, 3] {
+            let ts = TimeStamp::from_seconds(*i);
+            db.add_value("foo", Observation::new(ts, Sample::new(*i as f64)));
+        }
+        let timestamps: Vec<f64> = db
+            .get_raw_samples("foo")
+            .unwrap()
+            .iter()
+            .map(|o| o.timestamp.to_seconds())
+            .collect();
+        assert_eq!(vec![0.0, 1.0, 2.0, 3.0, 4.0], timestamps);
+        assert_eq!(
+            4.0,
+            db.quick_summary("foo")
+                .unwrap()
+                .last_timestamp()
+                .to_seconds()
+        );
+        assert_eq!(1, db.get_signal_names().len());
+
+        // Opt-in to move the data into a backup trace:
+        db.set_backup_on_rewind(Some(2.0));
+        let ts = TimeStamp::from_seconds(3);
+        db.add_value("foo", Observation::new(ts, Sample::new(3.0)));
+        assert_eq!(1, db.get_signal_names().len());
+        let ts = TimeStamp::from_seconds(1);
+        db.add_value("foo", Observation::new(ts, Sample::new(1.0)));
+        assert_eq!(2, db.get_signal_names().len());
+        assert_eq!(1, db.quick_summary("foo").unwrap().count);
+    }
+
+    #[test]
+    fn late_profile_events() {
+        let mut db = TsDb::default();
+        let enter = |name: &str| ProfileEvent::FunctionEnter {
+            name: name.to_owned(),
+        };
+        let events = vec![
+            (0, enter("main")),
+            (1, enter("foo")),
+            (4, enter("bar")),
+            (3, ProfileEvent::FunctionExit),
+            (5, ProfileEvent::FunctionExit),
+            (6, ProfileEvent::FunctionExit),
+        ];
+        for (t, event) in events {
+            let ts = TimeStamp::from_seconds(t);
+            db.add_profile_event("prof", Observation::new(ts, event));
+        }
+
+        // foo returned before bar was called:
+        let timespan = TimeSpan::from_seconds(0, 10);
+        let spans = db.query_calls("prof", &timespan, 1000).unwrap();
+        let calls: Vec<(&str, usize, f64)> = spans
+            .iter()
+            .map(|s| (&s.name[..], s.depth, s.timespan.end.to_seconds()))
+            .collect();
+        assert_eq!(
+            vec![("main", 0, 6.0), ("foo", 1, 3.0), ("bar", 1, 5.0)],
+            calls
+        );
+    }
+
+    #[test]
+    fn vector_signal() {
+        let mut db = TsDb::default();
+        let components = vec!["x".to_owned(), "y".to_owned()];
+        let observations = (0..5)
+            .map(|i| {
+                let ts = TimeStamp::from_seconds(i);
+                Observation::new(ts, Vector::new(vec![i as f64, -i as f64]))
+            })
+            .collect();
+        db.add_vectors("imu", &components, observations);
+
+        let query = Query::create()
+            .start(TimeStamp::from_seconds(0))
+            .end(TimeStamp::from_seconds(10))
+            .build();
+        if let Some(QueryResult::Vector(result)) = db.query("imu", query) {
+            assert_eq!(2, result.components());
+            assert_eq!(5, result.component(1).len());
+        } else {
+            panic!("Expected a vector result");
+        }
+
+        if let Some(Summary::Vector(summary)) = db.summary("imu", None) {
+            assert_eq!(4.0, summary.component(0).unwrap().metrics().max);
+            assert_eq!(-4.0, summary.component(1).unwrap().metrics().min);
+        } else {
+            panic!("Expected a vector summary");
+        }
+
+        // Other components start a new trace:
+        let ts = TimeStamp::from_seconds(5);
+        let observation = Observation::new(ts, Vector::new(vec![1.0]));
+        db.add_vectors("imu", &["z".to_owned()], vec![observation]);
+        assert_eq!(2, db.get_signal_names().len());
+        assert_eq!(1, db.quick_summary("imu").unwrap().count);
+    }
+
+    #[test]
+    fn event_signal() {
+        let mut db = TsDb::default();
+        for (i, state) in ["idle", "running", "idle"].iter().enumerate() {
+            let mut attributes = std::collections::HashMap::new();
+            attributes.insert("state".to_owned(), state.to_string());
+            let ts = TimeStamp::from_seconds(i as isize);
+            db.add_event("machine", Observation::new(ts, Event::new(attributes)));
+        }
+
+        let query = Query::create()
+            .start(TimeStamp::from_seconds(0))
+            .end(TimeStamp::from_seconds(10))
+            .build();
+        if let Some(QueryResult::Event(RangeQueryResult::Observations(events))) =
+            db.query("machine", query)
+        {
+            assert_eq!(3, events.len());
+            assert_eq!("state=running", events[1].value.to_string());
+        } else {
+            panic!("Expected event observations");
+        }
+
+        if let Some(Summary::Event(summary)) = db.summary("machine", None) {
+            assert_eq!(3, summary.count);
+            assert_eq!(2, summary.metrics().value_count("state", "idle"));
+            assert_eq!(1, summary.metrics().value_count("state", "running"));
+        } else {
+            panic!("Expected an event summary");
+        }
+    }
+
+    #[test]
+    fn typed_queries() {
+        let mut db = TsDb::default();
+        let ts = TimeStamp::from_seconds(1);
+        db.add_text(
+            "log",
+            Observation::new(ts.clone(), Text::new("hi".to_owned())),
+        );
+        db.add_value("foo", Observation::new(ts, Sample::new(1.0)));
+
+        let query = || Query::create().span(&TimeSpan::from_seconds(0, 2)).build();
+        match db.query_text("log", query()) {
+            Some(RangeQueryResult::Observations(observations)) => {
+                assert_eq!("hi", observations[0].value.text);
+            }
+            _ => panic!("Expected text observations"),
+        }
+        assert!(db.query_text("foo", query()).is_none());
+        assert!(db.query_profile("log", query()).is_none());
+    }
+
+    #[test]
+    fn derived_signal() {
+        let mut db = TsDb::default();
+        for i in 0..3 {
+            let ts = TimeStamp::from_seconds(i);
+            db.add_value("a", Observation::new(ts.clone(), Sample::new(i as f64)));
+            db.add_value("b", Observation::new(ts, Sample::new(1.0)));
+        }
+
+        assert!(db.add_derived("c", "a - ").is_err());
+        assert!(db.add_derived("a", "b * 2").is_err());
+        db.add_derived("c", "(a - b) * 2").unwrap();
+        assert!(db.add_derived("d", "c + d").is_err());
+        db.add_derived("d", "c + 1").unwrap();
+        assert!(db.add_derived("c", "d").is_err());
+        assert_eq!(4, db.get_signal_names().len());
+
+        let (sender, mut receiver) = mpsc::channel(0);
+        db.register_notifier(ChangeSubscriber::new(sender));
+        pending_event(&mut receiver).unwrap();
+
+        let summary = db.summary("c", None).unwrap();
+        assert_eq!(3, summary.count());
+
+        // New input data updates the derived signals:
+        let ts = TimeStamp::from_seconds(3);
+        db.add_value("a", Observation::new(ts.clone(), Sample::new(3.0)));
+        db.add_value("b", Observation::new(ts, Sample::new(1.0)));
+        let mut changed = pending_event(&mut receiver).unwrap().changed_signals;
+        db.poll_events();
+        changed.extend(pending_event(&mut receiver).unwrap().changed_signals);
+        assert!(changed.contains("d"));
+
+        let samples = db.get_raw_samples("d").unwrap();
+        let values: Vec<f64> = samples.iter().map(|o| o.value.value).collect();
+        assert_eq!(vec![-1.0, 1.0, 3.0, 5.0], values);
+
+        // Shared between threads, the signals are evaluated outside the lock:
+        let db = db.into_handle();
+        let ts = TimeStamp::from_seconds(4);
+        db.add_value("a", Observation::new(ts.clone(), Sample::new(4.0)));
+        db.add_value("b", Observation::new(ts, Sample::new(1.0)));
+        assert_eq!(5, db.summary("d", None).unwrap().count());
+        assert_eq!(5, db.snapshot().summary("c", None).unwrap().count());
+
+        db.delete("c");
+        assert!(db.summary("c", None).is_none());
+        assert!(db.get_raw_samples("d").unwrap().is_empty());
+    }
+
+    #[test]
+    fn value_lookup() {
+        let mut db = TsDb::default();
+        // Enough samples to fill several levels of the tree:
+        let samples = (0..10_000)
+            .map(|i| Observation::new(TimeStamp::new(i as f64 * 2.0), Sample::new(i as f64)))
+            .collect();
+        db.add_values("foo", samples);
+
+        let ts = TimeStamp::new(1001.5);
+        let value = |mode| db.value_at("foo", &ts, mode).unwrap();
+        assert_eq!(500.0, value(LookupMode::Previous).value.value);
+        assert_eq!(501.0, value(LookupMode::Next).value.value);
+        assert_eq!(501.0, value(LookupMode::Nearest).value.value);
+        assert_eq!(500.75, value(LookupMode::Linear).value.value);
+        assert_eq!(TimeStamp::new(1002.0), value(LookupMode::Next).timestamp);
+
+        let before = TimeStamp::new(-1.0);
+        assert!(db.value_at("foo", &before, LookupMode::Previous).is_none());
+        assert!(db.value_at("foo", &before, LookupMode::Linear).is_none());
+        let values = db.values_at(&["foo", "bar"], &before, LookupMode::Nearest);
+        assert_eq!(0.0, values[0].as_ref().unwrap().value.value);
+        assert!(values[1].is_none());
+    }
+
+    #[test]
+    fn namespaces() {
+        let mut db = TsDb::default();
+        let ts = TimeStamp::new(0.0);
+        for name in &[
+            "robot/arm/pos",
+            "robot/arm/speed",
+            "robot/leg/pos",
+            "robots",
+        ] {
+            db.add_value(name, Observation::new(ts.clone(), Sample::new(1.0)));
+        }
+
+        let mut names = db.get_signal_names_in("robot/arm");
+        names.sort();
+        assert_eq!(vec!["robot/arm/pos", "robot/arm/speed"], names);
+        assert_eq!(
+            3,
+            db.get_signal_tree()
+                .find("robot")
+                .unwrap()
+                .signal_names()
+                .len()
+        );
+
+        db.delete_namespace("robot");
+        assert_eq!(vec!["robots"], db.get_signal_names());
+    }
+
+    #[test]
+    fn snapshot() {
+        let db = TsDb::default().into_handle();
+        let samples = |range: std::ops::Range<usize>| {
+            range
+                .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(i as f64)))
+                .collect()
+        };
+        db.add_values("foo", samples(0..1000));
+
+        let snapshot = db.snapshot();
+        db.add_values("foo", samples(1000..2000));
+        db.add_value(
+            "foo",
+            Observation::new(TimeStamp::new(0.5), Sample::new(0.0)),
+        );
+        db.add_value(
+            "bar",
+            Observation::new(TimeStamp::new(0.0), Sample::new(1.0)),
+        );
+
+        // The snapshot does not change:
+        assert_eq!(vec!["foo"], snapshot.get_signal_names());
+        assert_eq!(1000, snapshot.quick_summary("foo").unwrap().count);
+        assert_eq!(1000, snapshot.get_raw_samples("foo").unwrap().len());
+        assert_eq!(2001, db.quick_summary("foo").unwrap().count);
+
+        // Deleting a signal does not change the snapshot either:
+        db.delete("foo");
+        assert!(db.quick_summary("foo").is_none());
+        assert_eq!(1000, snapshot.quick_summary("foo").unwrap().count);
+    }
+
+    #[test]
+    fn triggers() {
+        let mut db = TsDb::default();
+        let trigger = Trigger::new("foo", TriggerCondition::RisingEdge(0.5))
+            .pre_trigger(1.0)
+            .post_trigger(2.0);
+        db.add_trigger("edge", trigger);
+        let (sender, mut receiver) = mpsc::channel(0);
+        db.register_notifier(ChangeSubscriber::new(sender));
+
+        // Rising edges at 3 and 9 seconds:
+        let square_wave: Vec<Observation<Sample>> = (0..10)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new((i / 3 % 2) as f64)))
+            .collect();
+        db.add_values("foo", square_wave);
+
+        let mut events = vec![];
+        while let Some(event) = pending_event(&mut receiver) {
+            events.extend(event.triggers);
+            db.poll_events();
+        }
+
+        // The edge at 9 seconds is not captured yet:
+        assert_eq!(1, events.len());
+        assert_eq!("edge", events[0].trigger);
+        assert_eq!(
+            TimeSpan::new(TimeStamp::new(2.0), TimeStamp::new(5.0)),
+            events[0].timespan
+        );
+
+        db.add_value(
+            "foo",
+            Observation::new(TimeStamp::new(11.0), Sample::new(1.0)),
+        );
+        assert_eq!(2, db.trigger_events().len());
+
+        db.remove_trigger("edge");
+        assert!(db.get_trigger("edge").is_none());
+    }
+
+    #[test]
+    fn trigger_without_later_samples() {
+        let mut db = TsDb::default();
+        let trigger = Trigger::new("button", TriggerCondition::RisingEdge(0.5)).post_trigger(2.0);
+        db.add_trigger("press", trigger);
+
+        // A single press, after which the button signal stays silent:
+        db.add_values(
+            "button",
+            vec![
+                Observation::new(TimeStamp::new(1.0), Sample::new(0.0)),
+                Observation::new(TimeStamp::new(2.0), Sample::new(1.0)),
+            ],
+        );
+        assert!(db.trigger_events().is_empty());
+
+        // Other signals complete the capture, once they pass its end:
+        db.add_value(
+            "sensor",
+            Observation::new(TimeStamp::new(3.0), Sample::new(0.0)),
+        );
+        assert!(db.trigger_events().is_empty());
+        db.add_value(
+            "sensor",
+            Observation::new(TimeStamp::new(4.0), Sample::new(0.0)),
+        );
+        let events = db.trigger_events();
+        assert_eq!(1, events.len());
+        assert_eq!(TimeStamp::new(2.0), events[0].timestamp);
+    }
+
+    #[test]
+    fn sample_notifications() {
+        let mut db = TsDb::default();
+        let (sender, mut receiver) = mpsc::channel(0);
+        db.register_notifier(ChangeSubscriber::new(sender));
+        let samples = |times: &[f64]| {
+            times
+                .iter()
+                .map(|t| Observation::new(TimeStamp::new(*t), Sample::new(1.0)))
+                .collect()
+        };
+
+        // The new signal is reported right away, the samples when polled:
+        db.add_values("foo", samples(&[1.0, 2.0]));
+        let event = pending_event(&mut receiver).unwrap();
+        assert!(event.new_signals.contains("foo"));
+        db.poll_events();
+        let event = pending_event(&mut receiver).unwrap();
+        assert_eq!(2, event.new_samples["foo"].count);
+
+        // Later batches are merged until polled:
+        db.add_values("foo", samples(&[3.0, 5.0]));
+        db.add_values("foo", samples(&[4.0, 2.5]));
+        db.add_values("bar", samples(&[0.0]));
+        db.poll_events();
+        let event = pending_event(&mut receiver).unwrap();
+        assert_eq!(4, event.new_samples["foo"].count);
+        assert_eq!(
+            TimeSpan::new(TimeStamp::new(2.5), TimeStamp::new(5.0)),
+            event.new_samples["foo"].timespan
+        );
+        assert_eq!(1, event.new_samples["bar"].count);
+
+        let view = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(2.0));
+        assert!(!event.changed_in("foo", &view));
+        assert!(event.changed_in("bar", &view));
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        let db = TsDb::default().into_handle();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000 {
+                        let ts = TimeStamp::from_seconds(i);
+                        db.add_value("foo", Observation::new(ts, Sample::new(1.0)));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(4000, db.quick_summary("foo").unwrap().count);
+        assert_eq!(vec!["foo".to_owned()], db.get_signal_names());
+    }
+
+    #[test]
+    fn async_queries() {
+        let db = TsDb::default().into_handle();
+        let samples = (0..1000)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(i as f64)))
+            .collect();
+        db.add_values("foo", samples);
+        let timespan = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(1000.0));
+        let query = || Query::create().span(&timespan).amount(2000).build();
+
+        // The result is the same as that of a blocking query:
+        match block_on(db.query_async("foo", query())) {
+            Some(QueryResult::Value(RangeQueryResult::Observations(observations))) => {
+                assert_eq!(1000, observations.len());
+            }
+            _ => panic!("Expected observations"),
+        }
+        assert!(block_on(db.query_async("bar", query())).is_none());
+
+        // A newer query cancels the one it supersedes:
+        let mut slot = QuerySlot::default();
+        let first = db.query_async("foo", query());
+        slot.supersede(&first);
+        let second = db.query_async("foo", query());
+        slot.supersede(&second);
+        assert!(block_on(first).is_none());
+        assert!(block_on(second).is_some());
+
+        // Other queries run on the same workers:
+        let handle = db.clone();
+        let count = db.run_async(move || handle.summary("foo", None).map(|s| s.count()));
+        assert_eq!(Some(1000), block_on(count));
+    }
 }
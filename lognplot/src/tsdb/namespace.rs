@@ -0,0 +1,143 @@
+//! Hierarchical signal names.
+//!
+//! Signal names such as `robot/arm/joint3/pos` are split at the
+//! separator into namespaces. This signal is in the namespaces
+//! `robot`, `robot/arm` and `robot/arm/joint3`.
+
+use std::collections::BTreeMap;
+
+/// Separates the parts of a signal name.
+pub const SEPARATOR: char = '/';
+
+/// The namespaces a signal name is in, from the outermost namespace inwards.
+///
+/// Empty parts, such as the part before a leading separator, do not
+/// form a namespace.
+pub fn namespaces(name: &str) -> Vec<&str> {
+    name.char_indices()
+        .filter(|(index, c)| *c == SEPARATOR && *index > 0)
+        .map(|(index, _)| &name[..index])
+        .filter(|namespace| !namespace.ends_with(SEPARATOR))
+        .collect()
+}
+
+/// The last part of a signal name, without its namespaces.
+pub fn base_name(name: &str) -> &str {
+    name.rsplit(SEPARATOR).next().unwrap_or(name)
+}
+
+/// Test whether a signal is in the given namespace.
+pub fn is_in_namespace(name: &str, namespace: &str) -> bool {
+    if !name.starts_with(namespace) || name.len() == namespace.len() {
+        false
+    } else {
+        namespace.ends_with(SEPARATOR) || name[namespace.len()..].starts_with(SEPARATOR)
+    }
+}
+
+/// Signal names, arranged as a tree of namespaces.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SignalTree {
+    /// The full name of this node, such as `robot/arm`. Empty for the root.
+    pub name: String,
+
+    /// Whether there is a signal with exactly this name.
+    ///
+    /// A signal can also be a namespace of other signals.
+    pub is_signal: bool,
+
+    /// The nodes below this node, by the last part of their name.
+    pub children: BTreeMap<String, SignalTree>,
+}
+
+impl SignalTree {
+    pub fn from_names<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut tree = SignalTree::default();
+        for name in names {
+            tree.insert(name.as_ref());
+        }
+        tree
+    }
+
+    /// Add a signal, and the namespaces it is in.
+    pub fn insert(&mut self, name: &str) {
+        let mut node = self;
+        for path in namespaces(name).into_iter().chain(Some(name)) {
+            node = node
+                .children
+                .entry(base_name(path).to_owned())
+                .or_insert_with(|| SignalTree {
+                    name: path.to_owned(),
+                    ..Default::default()
+                });
+        }
+        node.is_signal = true;
+    }
+
+    /// Find the node of a signal or namespace.
+    pub fn find(&self, name: &str) -> Option<&SignalTree> {
+        let mut node = self;
+        for path in namespaces(name).into_iter().chain(Some(name)) {
+            node = node.children.get(base_name(path))?;
+        }
+        Some(node)
+    }
+
+    /// The names of all signals in this node and below it.
+    pub fn signal_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        self.collect_signal_names(&mut names);
+        names
+    }
+
+    fn collect_signal_names(&self, names: &mut Vec<String>) {
+        if self.is_signal {
+            names.push(self.name.clone());
+        }
+        for child in self.children.values() {
+            child.collect_signal_names(names);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base_name, is_in_namespace, namespaces, SignalTree};
+
+    #[test]
+    fn split_names() {
+        assert_eq!(vec!["robot", "robot/arm"], namespaces("robot/arm/pos"));
+        assert_eq!(vec!["/robot"], namespaces("/robot/pos"));
+        assert!(namespaces("pos").is_empty());
+        assert_eq!("pos", base_name("robot/arm/pos"));
+        assert_eq!("pos", base_name("pos"));
+
+        assert!(is_in_namespace("robot/arm/pos", "robot"));
+        assert!(is_in_namespace("robot/arm/pos", "robot/arm/"));
+        assert!(!is_in_namespace("robot/arm/pos", "rob"));
+        assert!(!is_in_namespace("robot", "robot"));
+    }
+
+    #[test]
+    fn signal_tree() {
+        let tree =
+            SignalTree::from_names(vec!["robot/arm/pos", "robot/arm", "robot/speed", "time"]);
+        assert_eq!(
+            vec!["robot", "time"],
+            tree.children.keys().collect::<Vec<_>>()
+        );
+
+        let arm = tree.find("robot/arm").unwrap();
+        assert!(arm.is_signal);
+        assert_eq!(vec!["robot/arm", "robot/arm/pos"], arm.signal_names());
+
+        let robot = tree.find("robot").unwrap();
+        assert!(!robot.is_signal);
+        assert_eq!(3, robot.signal_names().len());
+        assert!(tree.find("robot/leg").is_none());
+    }
+}
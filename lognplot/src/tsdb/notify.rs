@@ -3,8 +3,10 @@
 //! This module is used by the GUI to respond to changes
 //! which happen in the database.
 
+use super::TriggerEvent;
+use crate::time::TimeSpan;
 use futures::channel::mpsc;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct ChangeSubscriber {
@@ -28,18 +30,36 @@ impl ChangeSubscriber {
         self.emit_event();
     }
 
+    /// Notification of new samples in a data signal.
+    pub fn notify_samples_added(&mut self, name: &str, count: usize, timespan: &TimeSpan) {
+        self.event.add_new_samples(name, count, timespan);
+        self.emit_event();
+    }
+
     /// Notification of a change on a data signal
     pub fn notify_signal_changed(&mut self, name: &str) {
         self.event.add_changed_signal(name);
         self.emit_event();
     }
 
+    /// Notification that a signal was deleted.
+    pub fn notify_signal_deleted(&mut self, name: &str) {
+        self.event.add_deleted_signal(name);
+        self.emit_event();
+    }
+
     /// Notification that all data was deleted.
     pub fn notify_delete_all(&mut self) {
         self.event.add_delete_all();
         self.emit_event();
     }
 
+    /// Notification that a trigger fired, and its capture is complete.
+    pub fn notify_trigger(&mut self, event: &TriggerEvent) {
+        self.event.add_trigger(event);
+        self.emit_event();
+    }
+
     pub fn poll_events(&mut self) {
         self.ready = true;
         if !self.event.is_empty() {
@@ -80,7 +100,20 @@ impl ChangeSubscriber {
 pub struct DataChangeEvent {
     pub new_signals: HashSet<String>,
     pub changed_signals: HashSet<String>,
+
+    /// Samples added to the changed signals. Signals can also change
+    /// without new samples, for example when old data is dropped, or
+    /// when a derived signal must be evaluated again.
+    pub new_samples: HashMap<String, NewSamples>,
+
+    /// Signals which were deleted. Note that a deleted signal
+    /// can also be in the new signals, when it was created again
+    /// after the deletion, so process deletions first.
+    pub deleted_signals: HashSet<String>,
     pub delete_all: bool,
+
+    /// Trigger events, in the order in which they fired.
+    pub triggers: Vec<TriggerEvent>,
 }
 
 impl DataChangeEvent {
@@ -88,28 +121,85 @@ impl DataChangeEvent {
         DataChangeEvent {
             new_signals: HashSet::new(),
             changed_signals: HashSet::new(),
+            new_samples: HashMap::new(),
+            deleted_signals: HashSet::new(),
             delete_all: false,
+            triggers: vec![],
         }
     }
 
     fn is_empty(&self) -> bool {
-        self.new_signals.is_empty() && self.changed_signals.is_empty() && !self.delete_all
+        self.new_signals.is_empty()
+            && self.changed_signals.is_empty()
+            && self.deleted_signals.is_empty()
+            && !self.delete_all
+            && self.triggers.is_empty()
     }
 
     fn add_new_signal(&mut self, name: &str) {
         self.new_signals.insert(name.to_owned());
     }
 
+    /// Test if a signal changed within the given time span.
+    ///
+    /// A signal which changed without any new samples may have
+    /// changed anywhere.
+    pub fn changed_in(&self, name: &str, timespan: &TimeSpan) -> bool {
+        self.changed_signals.contains(name)
+            && self
+                .new_samples
+                .get(name)
+                .map_or(true, |new_samples| new_samples.timespan.overlap(timespan))
+    }
+
     fn add_changed_signal(&mut self, name: &str) {
         self.changed_signals.insert(name.to_owned());
     }
 
+    fn add_new_samples(&mut self, name: &str, count: usize, timespan: &TimeSpan) {
+        self.add_changed_signal(name);
+        if let Some(new_samples) = self.new_samples.get_mut(name) {
+            new_samples.count += count;
+            new_samples.timespan.extend_to_include_span(timespan);
+        } else {
+            let new_samples = NewSamples {
+                count,
+                timespan: timespan.clone(),
+            };
+            self.new_samples.insert(name.to_owned(), new_samples);
+        }
+    }
+
+    fn add_deleted_signal(&mut self, name: &str) {
+        // Forget earlier changes to this signal:
+        self.new_signals.remove(name);
+        self.changed_signals.remove(name);
+        self.new_samples.remove(name);
+
+        self.deleted_signals.insert(name.to_owned());
+    }
+
+    fn add_trigger(&mut self, event: &TriggerEvent) {
+        self.triggers.push(event.clone());
+    }
+
     /// Add a delete all signals event
     fn add_delete_all(&mut self) {
         // Drop all signals added so far:
         self.new_signals.clear();
         self.changed_signals.clear();
+        self.new_samples.clear();
+        self.deleted_signals.clear();
 
         self.delete_all = true;
     }
 }
+
+/// Samples added to a signal since the previous event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewSamples {
+    pub count: usize,
+
+    /// The time span covered by the new samples.
+    pub timespan: TimeSpan,
+}
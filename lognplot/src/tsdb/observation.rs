@@ -1,7 +1,8 @@
 use crate::time::TimeStamp;
+use serde::{Deserialize, Serialize};
 
 /// A single observation at some point in time.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Observation<V> {
     /// The timestamp when the observation was made.
     pub timestamp: TimeStamp,
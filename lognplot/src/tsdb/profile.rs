@@ -4,13 +4,15 @@
 //! callstack over time manner.
 
 use super::CountMetrics;
+use super::HeapSize;
 use super::Observation;
-use crate::time::TimeStamp;
+use crate::time::{Duration, TimeSpan, TimeStamp};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// A single profiling event, such as function enter or function
 /// return.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ProfileEvent {
     FunctionEnter { name: String },
     FunctionExit,
@@ -32,46 +34,323 @@ impl fmt::Display for ProfileEvent {
     }
 }
 
-/// A structure holding a running call stack.
-struct CallTrack {
-    root: Call,
+/// A tree of function calls, built from a series of profile events.
+///
+/// Events must be added in time order. A profile track keeps its tree
+/// up to date while events are added, and rewinds it to replay events
+/// which arrive late.
+#[derive(Clone, Debug, Default)]
+pub struct CallTree {
+    /// The outermost calls, sorted by time.
+    calls: Vec<Call>,
+
+    /// The amount of calls which did not return yet. The open
+    /// calls are always the last call on each level.
+    depth: usize,
+
+    /// The time of the last event.
+    last: Option<TimeStamp>,
 }
 
-impl CallTrack {
+impl CallTree {
+    pub fn from_observations(observations: Vec<Observation<ProfileEvent>>) -> Self {
+        let mut tree = Self::default();
+        for observation in observations {
+            tree.add_observation(observation);
+        }
+        tree
+    }
+
     pub fn add_observation(&mut self, observation: Observation<ProfileEvent>) {
         match observation.value {
             ProfileEvent::FunctionEnter { name } => {
-                let call = Call::new(observation.timestamp, name);
-                self.root.child_calls.push(call);
+                let call = Call::new(observation.timestamp.clone(), name);
+                self.innermost_calls().push(call);
+                self.depth += 1;
             }
             ProfileEvent::FunctionExit => {
-                self.root.end = Some(observation.timestamp);
+                if self.depth > 0 {
+                    self.depth -= 1;
+                    let call = self.innermost_calls().last_mut().unwrap();
+                    call.end = Some(observation.timestamp.clone());
+                } else {
+                    warn!("Ignoring function exit without function enter");
+                }
             }
         }
+        self.last = Some(observation.timestamp);
+    }
+
+    /// Undo all events at or after the given time.
+    ///
+    /// Calls which started at or after that time are removed, and calls
+    /// which returned at or after it are open again.
+    pub fn rewind(&mut self, timestamp: &TimeStamp) {
+        self.depth = rewind_calls(&mut self.calls, timestamp);
+    }
+
+    /// Forget the calls which returned before the given time, or all
+    /// calls which returned when None.
+    ///
+    /// This keeps the tree in line with the retained events of a trace,
+    /// while calls of which the enter event was dropped, but which
+    /// returned later, are kept.
+    pub fn trim(&mut self, oldest: Option<&TimeStamp>) {
+        trim_calls(&mut self.calls, oldest);
+    }
+
+    /// The time of the last event.
+    pub fn last(&self) -> Option<&TimeStamp> {
+        self.last.as_ref()
+    }
+
+    /// The calls of the innermost call which did not return yet.
+    fn innermost_calls(&mut self) -> &mut Vec<Call> {
+        let mut calls = &mut self.calls;
+        for _ in 0..self.depth {
+            calls = &mut calls.last_mut().unwrap().calls;
+        }
+        calls
+    }
+
+    /// Get the calls which overlap the given timespan.
+    ///
+    /// Calls shorter than the given resolution are merged with neighbouring
+    /// short calls, and the calls inside them are left out.
+    pub fn query(&self, timespan: &TimeSpan, resolution: Duration) -> Vec<CallSpan> {
+        let mut spans = vec![];
+        if let Some(last) = &self.last {
+            collect_calls(&self.calls, 0, timespan, resolution, last, &mut spans);
+        }
+        spans
     }
 }
 
-/// A single function call node.
+/// A single function call.
 ///
 /// Each function call has a name, a start and end time
 /// and other calls to other functions.
+#[derive(Clone, Debug)]
 struct Call {
     name: String,
 
-    start: Option<TimeStamp>,
+    start: TimeStamp,
 
+    /// The return time, or None when the call did not return yet.
     end: Option<TimeStamp>,
 
-    child_calls: Vec<Call>,
+    calls: Vec<Call>,
 }
 
 impl Call {
     fn new(timestamp: TimeStamp, name: String) -> Self {
         Call {
             name,
-            start: Some(timestamp),
+            start: timestamp,
             end: None,
-            child_calls: vec![],
+            calls: vec![],
+        }
+    }
+}
+
+impl HeapSize for CallTree {
+    fn heap_size(&self) -> usize {
+        self.calls.heap_size()
+    }
+}
+
+impl HeapSize for Call {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.calls.heap_size()
+    }
+}
+
+/// Remove the calls which returned before the given time.
+///
+/// Only the first remaining call on each level can contain such calls.
+fn trim_calls(calls: &mut Vec<Call>, oldest: Option<&TimeStamp>) {
+    let dropped = |call: &Call| match (&call.end, oldest) {
+        (Some(end), Some(oldest)) => end < oldest,
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+    let first = calls.partition_point(dropped);
+    calls.drain(..first);
+    if let Some(call) = calls.first_mut() {
+        trim_calls(&mut call.calls, oldest);
+    }
+}
+
+/// Remove the calls which started at or after the given time, and open
+/// the calls which returned at or after it again.
+///
+/// Returns the amount of nested calls which are open afterwards.
+fn rewind_calls(calls: &mut Vec<Call>, timestamp: &TimeStamp) -> usize {
+    let kept = calls.partition_point(|call| call.start < *timestamp);
+    calls.truncate(kept);
+    match calls.last_mut() {
+        Some(call) if call.end.as_ref().map_or(true, |end| end >= timestamp) => {
+            call.end = None;
+            1 + rewind_calls(&mut call.calls, timestamp)
         }
+        _ => 0,
+    }
+}
+
+/// A function call, or a series of short calls, as shown in a call stack.
+#[derive(Clone, Debug)]
+pub struct CallSpan {
+    /// The name of the called function. When several calls to
+    /// different functions are merged, this is empty.
+    pub name: String,
+
+    /// The nesting depth of the call, outermost calls are at depth 0.
+    pub depth: usize,
+
+    pub timespan: TimeSpan,
+
+    /// The amount of merged calls.
+    pub count: usize,
+}
+
+impl CallSpan {
+    fn include(&mut self, other: CallSpan) {
+        if self.name != other.name {
+            self.name.clear();
+        }
+        self.timespan.extend_to_include_span(&other.timespan);
+        self.count += other.count;
+    }
+}
+
+fn collect_calls(
+    calls: &[Call],
+    depth: usize,
+    timespan: &TimeSpan,
+    resolution: Duration,
+    last: &TimeStamp,
+    spans: &mut Vec<CallSpan>,
+) {
+    let end_of = |call: &Call| call.end.as_ref().unwrap_or(last).clone();
+    let first = calls.partition_point(|c| end_of(c) < timespan.start);
+
+    let mut merged: Option<CallSpan> = None;
+    for call in calls[first..]
+        .iter()
+        .take_while(|c| c.start <= timespan.end)
+    {
+        let span = CallSpan {
+            name: call.name.clone(),
+            depth,
+            timespan: TimeSpan::new(call.start.clone(), end_of(call)),
+            count: 1,
+        };
+
+        if span.timespan.end.distance(&span.timespan.start) < resolution {
+            // Merge short calls, when they are close together:
+            merged = match merged {
+                Some(mut previous)
+                    if span.timespan.start.distance(&previous.timespan.end) < resolution =>
+                {
+                    previous.include(span);
+                    Some(previous)
+                }
+                previous => {
+                    spans.extend(previous);
+                    Some(span)
+                }
+            };
+        } else {
+            spans.extend(merged.take());
+            spans.push(span);
+            collect_calls(&call.calls, depth + 1, timespan, resolution, last, spans);
+        }
+    }
+    spans.extend(merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallTree, ProfileEvent};
+    use crate::time::{Duration, TimeSpan, TimeStamp};
+    use crate::tsdb::Observation;
+
+    fn enter(t: isize, name: &str) -> Observation<ProfileEvent> {
+        let name = name.to_owned();
+        Observation::new(
+            TimeStamp::from_seconds(t),
+            ProfileEvent::FunctionEnter { name },
+        )
+    }
+
+    fn exit(t: isize) -> Observation<ProfileEvent> {
+        Observation::new(TimeStamp::from_seconds(t), ProfileEvent::FunctionExit)
+    }
+
+    #[test]
+    fn nested_calls() {
+        let tree = CallTree::from_observations(vec![
+            enter(0, "main"),
+            enter(1, "foo"),
+            exit(5),
+            enter(10, "bar"),
+            exit(11),
+            enter(12, "bar"),
+            exit(13),
+            exit(20),
+            enter(30, "main"),
+        ]);
+
+        let timespan = TimeSpan::from_seconds(0, 40);
+        let spans = tree.query(&timespan, Duration::from_nanos(0));
+        let names: Vec<(&str, usize)> = spans.iter().map(|s| (&s.name[..], s.depth)).collect();
+        assert_eq!(
+            vec![("main", 0), ("foo", 1), ("bar", 1), ("bar", 1), ("main", 0)],
+            names
+        );
+        assert_eq!(TimeStamp::from_seconds(30), spans[4].timespan.end);
+
+        // Zoomed out, the short calls are merged:
+        let spans = tree.query(&timespan, Duration::from_secs(2));
+        assert_eq!(4, spans.len());
+        assert_eq!("bar", spans[2].name);
+        assert_eq!(2, spans[2].count);
+
+        // Only calls overlapping the timespan:
+        let spans = tree.query(&TimeSpan::from_seconds(6, 9), Duration::from_nanos(0));
+        assert_eq!(1, spans.len());
+    }
+
+    #[test]
+    fn rewind_and_trim() {
+        let mut tree = CallTree::from_observations(vec![
+            enter(0, "main"),
+            enter(1, "foo"),
+            exit(5),
+            enter(10, "bar"),
+            exit(11),
+        ]);
+        let names = |tree: &CallTree| -> Vec<(String, usize)> {
+            let spans = tree.query(&TimeSpan::from_seconds(0, 40), Duration::from_nanos(0));
+            spans.into_iter().map(|s| (s.name, s.depth)).collect()
+        };
+
+        // Replay a late exit of foo, before bar was called:
+        tree.rewind(&TimeStamp::from_seconds(5));
+        tree.add_observation(exit(4));
+        tree.add_observation(enter(10, "bar"));
+        tree.add_observation(exit(11));
+        let spans = tree.query(&TimeSpan::from_seconds(0, 40), Duration::from_nanos(0));
+        assert_eq!(TimeStamp::from_seconds(4), spans[1].timespan.end);
+
+        // Calls which returned before the oldest event are forgotten, also
+        // inside calls which are still running:
+        tree.trim(Some(&TimeStamp::from_seconds(6)));
+        assert_eq!(
+            vec![("main".to_owned(), 0), ("bar".to_owned(), 1)],
+            names(&tree)
+        );
+        tree.trim(None);
+        assert_eq!(vec![("main".to_owned(), 0)], names(&tree));
     }
 }
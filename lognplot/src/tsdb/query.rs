@@ -2,13 +2,18 @@
 //!
 //! The database can be queried, and will give a `QueryResult` back.
 
-use crate::time::{Resolution, TimeSpan, TimeStamp};
+use super::{Grid, Interpolation};
+use crate::time::{Duration, Resolution, TimeSpan, TimeStamp};
 
 #[derive(Debug)]
 pub struct Query {
     pub interval: TimeSpan,
     pub resolution: Resolution,
     pub amount: usize,
+
+    /// When set, resample the data onto this grid.
+    pub grid: Option<Grid>,
+    pub interpolation: Interpolation,
 }
 
 impl Query {
@@ -21,6 +26,8 @@ impl Query {
             interval,
             resolution,
             amount,
+            grid: None,
+            interpolation: Interpolation::default(),
         }
     }
 }
@@ -29,6 +36,8 @@ pub struct QueryBuilder {
     start: Option<TimeStamp>,
     end: Option<TimeStamp>,
     amount: usize,
+    grid: Option<Grid>,
+    interpolation: Interpolation,
 }
 
 impl QueryBuilder {
@@ -37,6 +46,8 @@ impl QueryBuilder {
             start: None,
             end: None,
             amount: 10,
+            grid: None,
+            interpolation: Interpolation::default(),
         }
     }
 
@@ -65,11 +76,36 @@ impl QueryBuilder {
         self
     }
 
+    /// Resample the data onto a regular grid of `count` points,
+    /// starting at `start` and `step` apart.
+    ///
+    /// When no start and end are given, the query covers the grid.
+    pub fn resample(mut self, start: TimeStamp, step: Duration, count: usize) -> Self {
+        self.grid = Some(Grid::new(start, step, count));
+        self
+    }
+
+    /// Select how to determine values between observations when resampling.
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
     /// Finish building the query, and construct it!
     pub fn build(self) -> Query {
-        let start = self.start.expect("No 'start' value given for the query!");
-        let end = self.end.expect("No 'end' value given for the query!");
+        let grid_span = self.grid.as_ref().map(|grid| grid.timespan());
+        let start = self
+            .start
+            .or_else(|| grid_span.as_ref().map(|span| span.start.clone()))
+            .expect("No 'start' value given for the query!");
+        let end = self
+            .end
+            .or_else(|| grid_span.map(|span| span.end))
+            .expect("No 'end' value given for the query!");
         let interval = TimeSpan::new(start, end);
-        Query::new(interval, Resolution::NanoSeconds, self.amount)
+        let mut query = Query::new(interval, Resolution::NanoSeconds, self.amount);
+        query.grid = self.grid;
+        query.interpolation = self.interpolation;
+        query
     }
 }
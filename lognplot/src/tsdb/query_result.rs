@@ -1,6 +1,7 @@
 use super::metrics::Metrics;
 use super::{Aggregation, Observation};
-use super::{CountMetrics, ProfileEvent, Sample, SampleMetrics, Text};
+use super::{CountMetrics, ProfileEvent, Sample, SampleMetrics, Text, Vector, VectorMetrics};
+use super::{Event, EventMetrics, LogMetrics, LogRecord};
 
 /// This holds the result of a query to the database.
 /// The result can be several things, depending upon query type.
@@ -11,6 +12,9 @@ pub enum QueryResult {
     Value(RangeQueryResult<Sample, SampleMetrics>),
     Text(RangeQueryResult<Text, CountMetrics>),
     Profile(RangeQueryResult<ProfileEvent, CountMetrics>),
+    Vector(RangeQueryResult<Vector, VectorMetrics>),
+    Event(RangeQueryResult<Event, EventMetrics>),
+    Log(RangeQueryResult<LogRecord, LogMetrics>),
 }
 
 impl QueryResult {
@@ -19,6 +23,9 @@ impl QueryResult {
             QueryResult::Value(r) => r.len(),
             QueryResult::Text(r) => r.len(),
             QueryResult::Profile(r) => r.len(),
+            QueryResult::Vector(r) => r.len(),
+            QueryResult::Event(r) => r.len(),
+            QueryResult::Log(r) => r.len(),
         }
     }
 }
@@ -0,0 +1,231 @@
+//! Resampling of traces onto a regular grid of timestamps,
+//! and lookup of the value of a trace at a single point in time.
+//!
+//! This can be used to align several signals, for example to
+//! export them as a table.
+
+use super::{Event, LogRecord, Observation, ProfileEvent, Sample, Text, Vector};
+use crate::time::{Duration, TimeSpan, TimeStamp};
+
+/// A regular grid of timestamps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub start: TimeStamp,
+    pub step: Duration,
+    pub count: usize,
+}
+
+impl Grid {
+    pub fn new(start: TimeStamp, step: Duration, count: usize) -> Self {
+        Grid { start, step, count }
+    }
+
+    /// The timestamp of the grid point with the given index.
+    pub fn timestamp(&self, index: usize) -> TimeStamp {
+        self.start.clone() + self.step * index as i64
+    }
+
+    /// The time span from the first to the last grid point.
+    pub fn timespan(&self) -> TimeSpan {
+        TimeSpan::new(
+            self.start.clone(),
+            self.timestamp(self.count.saturating_sub(1)),
+        )
+    }
+}
+
+/// How to determine the value of a signal between observations.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    /// Hold the value of the previous observation.
+    #[default]
+    Previous,
+
+    /// Interpolate linearly between the surrounding observations.
+    ///
+    /// Values which cannot be interpolated, such as texts,
+    /// hold the previous value.
+    Linear,
+
+    /// Take the value of the nearest observation.
+    Nearest,
+}
+
+/// Which observation to use when looking up a value at a certain time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LookupMode {
+    /// The last observation at or before the time.
+    Previous,
+
+    /// The first observation at or after the time.
+    Next,
+
+    /// The observation closest to the time.
+    Nearest,
+
+    /// The value interpolated linearly between the surrounding observations.
+    Linear,
+}
+
+/// Values which can be interpolated between two observations.
+pub trait Interpolate: Clone {
+    /// Get the value at the given fraction between this value and the next.
+    ///
+    /// By default, this value is held.
+    fn interpolate(&self, _next: &Self, _fraction: f64) -> Self {
+        self.clone()
+    }
+}
+
+impl Interpolate for Sample {
+    fn interpolate(&self, next: &Self, fraction: f64) -> Self {
+        Sample::new(self.value + (next.value - self.value) * fraction)
+    }
+}
+
+impl Interpolate for Vector {
+    fn interpolate(&self, next: &Self, fraction: f64) -> Self {
+        if self.len() == next.len() {
+            let values = self
+                .values
+                .iter()
+                .zip(next.values.iter())
+                .map(|(a, b)| a + (b - a) * fraction)
+                .collect();
+            Vector::new(values)
+        } else {
+            self.clone()
+        }
+    }
+}
+
+impl Interpolate for Text {}
+impl Interpolate for ProfileEvent {}
+impl Interpolate for Event {}
+impl Interpolate for LogRecord {}
+
+/// Resample sorted observations onto the grid.
+///
+/// Grid points for which no value can be determined are left out:
+/// before the first observation, and for linear interpolation also
+/// after the last observation.
+pub fn resample<V>(
+    observations: &[Observation<V>],
+    grid: &Grid,
+    interpolation: Interpolation,
+) -> Vec<Observation<V>>
+where
+    V: Interpolate,
+{
+    let timestamps = (0..grid.count).map(|index| grid.timestamp(index));
+    resample_at(observations, timestamps, interpolation)
+}
+
+/// Resample sorted observations at the given increasing timestamps.
+///
+/// Like `resample`, timestamps for which no value can be determined
+/// are left out.
+pub fn resample_at<V, I>(
+    observations: &[Observation<V>],
+    timestamps: I,
+    interpolation: Interpolation,
+) -> Vec<Observation<V>>
+where
+    V: Interpolate,
+    I: Iterator<Item = TimeStamp>,
+{
+    let mut resampled = Vec::with_capacity(timestamps.size_hint().0);
+    // Index of the first observation after the timestamp:
+    let mut next = 0;
+
+    for timestamp in timestamps {
+        while next < observations.len() && observations[next].timestamp <= timestamp {
+            next += 1;
+        }
+        let before = next.checked_sub(1).map(|i| &observations[i]);
+        let after = observations.get(next);
+
+        let value = match (interpolation, before, after) {
+            (_, Some(before), _) if before.timestamp == timestamp => Some(before.value.clone()),
+            (Interpolation::Previous, before, _) => before.map(|o| o.value.clone()),
+            (Interpolation::Linear, Some(before), Some(after)) => {
+                let fraction = (&timestamp - &before.timestamp).as_secs_f64()
+                    / (&after.timestamp - &before.timestamp).as_secs_f64();
+                Some(before.value.interpolate(&after.value, fraction))
+            }
+            (Interpolation::Linear, _, _) => None,
+            (Interpolation::Nearest, Some(before), Some(after)) => {
+                if &timestamp - &before.timestamp <= &after.timestamp - &timestamp {
+                    Some(before.value.clone())
+                } else {
+                    Some(after.value.clone())
+                }
+            }
+            (Interpolation::Nearest, before, after) => before.or(after).map(|o| o.value.clone()),
+        };
+
+        if let Some(value) = value {
+            resampled.push(Observation::new(timestamp, value));
+        }
+    }
+
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, Interpolation};
+    use crate::time::{Duration, TimeStamp};
+    use crate::tsdb::{Observation, Query, RangeQueryResult, Sample, SampleMetrics, Trace};
+
+    fn resampled_values(
+        trace: &Trace<Sample, SampleMetrics>,
+        grid: &Grid,
+        interpolation: Interpolation,
+    ) -> Vec<(f64, f64)> {
+        let query = Query::create()
+            .resample(grid.start.clone(), grid.step, grid.count)
+            .interpolation(interpolation)
+            .build();
+        match trace.query(query) {
+            RangeQueryResult::Observations(observations) => observations
+                .into_iter()
+                .map(|o| (o.timestamp.to_seconds(), o.value.value))
+                .collect(),
+            RangeQueryResult::Aggregations(_) => panic!("Expected observations"),
+        }
+    }
+
+    #[test]
+    fn interpolations() {
+        let mut trace = Trace::default();
+        for t in &[0.0, 1.0, 2.0, 10.0] {
+            trace.add_observation(Observation::new(TimeStamp::new(*t), Sample::new(*t)));
+        }
+
+        let grid = Grid::new(TimeStamp::new(-0.5), Duration::from_millis(500), 6);
+        assert_eq!(
+            vec![(0.0, 0.0), (0.5, 0.0), (1.0, 1.0), (1.5, 1.0), (2.0, 2.0)],
+            resampled_values(&trace, &grid, Interpolation::Previous)
+        );
+        assert_eq!(
+            vec![(0.0, 0.0), (0.5, 0.5), (1.0, 1.0), (1.5, 1.5), (2.0, 2.0)],
+            resampled_values(&trace, &grid, Interpolation::Linear)
+        );
+
+        // Observations outside of the grid are used as well:
+        let grid = Grid::new(TimeStamp::new(4.0), Duration::from_secs(4), 3);
+        assert_eq!(
+            vec![(4.0, 2.0), (8.0, 2.0), (12.0, 10.0)],
+            resampled_values(&trace, &grid, Interpolation::Previous)
+        );
+        assert_eq!(
+            vec![(4.0, 4.0), (8.0, 8.0)],
+            resampled_values(&trace, &grid, Interpolation::Linear)
+        );
+        assert_eq!(
+            vec![(4.0, 2.0), (8.0, 10.0), (12.0, 10.0)],
+            resampled_values(&trace, &grid, Interpolation::Nearest)
+        );
+    }
+}
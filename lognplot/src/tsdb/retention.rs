@@ -0,0 +1,282 @@
+//! Retention policies, to limit the amount of data kept in memory.
+//!
+//! When a trace exceeds its policy, the oldest leaf nodes of the tree
+//! are dropped. The aggregated metrics of the dropped data are kept,
+//! so zoomed out views of the old data remain available.
+
+use super::SharedTrack;
+
+/// Limits on the amount of data to keep.
+///
+/// Each limit is optional, when no limit is set, data is kept forever.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Maximum age of observations in seconds, relative to the
+    /// most recent observation.
+    pub max_age: Option<f64>,
+
+    /// Maximum number of observations to keep.
+    pub max_samples: Option<usize>,
+
+    /// Maximum amount of memory in bytes used by traces.
+    ///
+    /// Next to the observations, this counts the data they refer to,
+    /// such as texts, and the nodes and metrics of the trees.
+    pub max_bytes: Option<usize>,
+}
+
+impl RetentionPolicy {
+    /// Create a policy which keeps everything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn max_age(mut self, seconds: f64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn max_samples(mut self, samples: usize) -> Self {
+        self.max_samples = Some(samples);
+        self
+    }
+
+    pub fn max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Test if this policy limits anything at all.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age.is_none() && self.max_samples.is_none() && self.max_bytes.is_none()
+    }
+}
+
+/// Retention to apply to traces, after the database was unlocked.
+///
+/// Dropping data write-locks a trace, which has to wait for running
+/// queries of that trace. The database collects the traces to prune,
+/// so that this waiting does not block access to the other traces.
+#[derive(Default)]
+pub struct PendingRetention {
+    /// A trace with a policy of its own.
+    trace: Option<(String, RetentionPolicy, SharedTrack)>,
+
+    /// All traces, when the global policy is to be enforced.
+    global: Option<(RetentionPolicy, Vec<(String, SharedTrack)>)>,
+}
+
+impl PendingRetention {
+    pub fn trace(mut self, name: &str, policy: RetentionPolicy, track: SharedTrack) -> Self {
+        self.trace = Some((name.to_owned(), policy, track));
+        self
+    }
+
+    pub fn global(mut self, policy: RetentionPolicy, tracks: Vec<(String, SharedTrack)>) -> Self {
+        self.global = Some((policy, tracks));
+        self
+    }
+
+    /// Drop old data, return the names of the traces which changed.
+    pub fn apply(self) -> Vec<String> {
+        let mut changed = vec![];
+
+        if let Some((name, policy, track)) = self.trace {
+            if track.write().unwrap().apply_retention(&policy) > 0 {
+                changed.push(name);
+            }
+        }
+
+        if let Some((policy, tracks)) = self.global {
+            apply_global(&policy, &tracks, &mut changed);
+        }
+
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+/// Apply a policy to all traces together.
+///
+/// The age limit is applied to each trace, for the sample and memory
+/// limits the oldest data of all traces is dropped first.
+fn apply_global(
+    retention: &RetentionPolicy,
+    tracks: &[(String, SharedTrack)],
+    changed: &mut Vec<String>,
+) {
+    if let Some(max_age) = retention.max_age {
+        let policy = RetentionPolicy::new().max_age(max_age);
+        for (name, track) in tracks {
+            if track.write().unwrap().apply_retention(&policy) > 0 {
+                changed.push(name.clone());
+            }
+        }
+    }
+
+    let max_samples = retention.max_samples.unwrap_or(usize::MAX);
+    let max_bytes = retention.max_bytes.unwrap_or(usize::MAX);
+    let mut samples: usize = tracks
+        .iter()
+        .map(|(_, t)| t.read().unwrap().retained())
+        .sum();
+    let mut bytes: usize = tracks
+        .iter()
+        .map(|(_, t)| t.read().unwrap().retained_bytes())
+        .sum();
+
+    while samples > max_samples || bytes > max_bytes {
+        // Drop the oldest data of all traces.
+        let oldest = tracks
+            .iter()
+            .filter_map(|(name, track)| {
+                Some((track.read().unwrap().oldest_retained()?, name, track))
+            })
+            .min_by(|a, b| a.0.cmp(&b.0));
+
+        if let Some((_, name, track)) = oldest {
+            let mut track = track.write().unwrap();
+            let old_bytes = track.retained_bytes();
+            samples -= track.drop_oldest();
+            bytes = bytes - old_bytes + track.retained_bytes();
+            changed.push(name.clone());
+        } else {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Fanout, Observation, ProfileEvent, Sample, Text, TsDb};
+    use super::RetentionPolicy;
+    use crate::time::{TimeSpan, TimeStamp};
+
+    fn add_samples(db: &mut TsDb, name: &str, range: std::ops::Range<isize>) {
+        for i in range {
+            let ts = TimeStamp::from_seconds(i);
+            db.add_value(name, Observation::new(ts, Sample::new(i as f64)));
+        }
+    }
+
+    fn retained(db: &TsDb, name: &str) -> usize {
+        db.get_raw_samples(name).unwrap().len()
+    }
+
+    fn add_texts(db: &mut TsDb, name: &str, range: std::ops::Range<isize>, length: usize) {
+        for i in range {
+            let ts = TimeStamp::from_seconds(i);
+            let text = Text::new("x".repeat(length));
+            db.add_text(name, Observation::new(ts, text));
+        }
+    }
+
+    #[test]
+    fn text_memory() {
+        let mut db = TsDb::default();
+        add_texts(&mut db, "short", 0..1000, 1);
+        add_texts(&mut db, "long", 0..1000, 1000);
+        let bytes = |name: &str| db.track(name).unwrap().read().unwrap().retained_bytes();
+
+        // The text of the observations is counted:
+        assert!(bytes("long") > 1000 * 1000);
+        assert!(bytes("long") - bytes("short") >= 1000 * 999);
+    }
+
+    #[test]
+    fn byte_retention() {
+        let mut db = TsDb::default();
+        db.set_trace_retention("foo", RetentionPolicy::new().max_bytes(100_000));
+        add_texts(&mut db, "foo", 0..1000, 1000);
+        let track = db.track("foo").unwrap();
+        let track = track.read().unwrap();
+        assert!(track.retained_bytes() <= 100_000);
+        assert!(track.retained() < 100);
+        assert!(track.retained() > 50);
+        drop(track);
+
+        // Compressed samples take less memory than their size:
+        db.set_trace_retention("bar", RetentionPolicy::new().max_bytes(16_000));
+        add_samples(&mut db, "bar", 0..10_000);
+        assert!(retained(&db, "bar") > 16_000 / 24);
+    }
+
+    #[test]
+    fn trace_retention() {
+        let mut db = TsDb::default();
+        db.set_trace_retention("foo", RetentionPolicy::new().max_samples(100));
+        db.set_trace_retention("bar", RetentionPolicy::new().max_age(50.0));
+        // Leaves are pruned as a whole, so keep them small:
+        db.set_trace_fanout("bar", Fanout::new(8, 4));
+        add_samples(&mut db, "foo", 0..1000);
+        add_samples(&mut db, "bar", 0..1000);
+        add_samples(&mut db, "baz", 0..1000);
+
+        assert!(retained(&db, "foo") <= 100);
+        assert!(retained(&db, "bar") <= 60);
+        assert_eq!(1000, retained(&db, "baz"));
+
+        // An age beyond the range of the timestamps keeps everything:
+        db.set_trace_retention("old", RetentionPolicy::new().max_age(1.0e12));
+        add_samples(&mut db, "old", -1000..0);
+        assert_eq!(1000, retained(&db, "old"));
+
+        // The dropped range is still summarized:
+        assert_eq!(1000, db.summary("foo", None).unwrap().count());
+        assert_eq!(1000, db.quick_summary("bar").unwrap().count);
+    }
+
+    #[test]
+    fn profile_retention() {
+        let mut db = TsDb::default();
+        db.set_trace_retention("prof", RetentionPolicy::new().max_samples(100));
+        db.set_trace_fanout("prof", Fanout::new(8, 4));
+        let mut add = |t: f64, event: ProfileEvent| {
+            db.add_profile_event("prof", Observation::new(TimeStamp::new(t), event));
+        };
+        add(
+            0.0,
+            ProfileEvent::FunctionEnter {
+                name: "main".to_owned(),
+            },
+        );
+        for i in 1..1000 {
+            let t = i as f64;
+            add(
+                t,
+                ProfileEvent::FunctionEnter {
+                    name: "step".to_owned(),
+                },
+            );
+            add(t + 0.5, ProfileEvent::FunctionExit);
+        }
+
+        // The running call stays, while the calls of dropped events go:
+        let timespan = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(1000.0));
+        let spans = db.query_calls("prof", &timespan, 100_000).unwrap();
+        assert_eq!(("main", 0), (&spans[0].name[..], spans[0].depth));
+        assert!(spans[1..].iter().all(|s| s.name == "step" && s.depth == 1));
+        assert!(spans.len() <= 100);
+        assert!(spans.len() > 40);
+    }
+
+    #[test]
+    fn global_retention() {
+        let mut db = TsDb::default();
+        add_samples(&mut db, "foo", 0..1000);
+        add_samples(&mut db, "bar", 500..1500);
+        db.set_retention(RetentionPolicy::new().max_samples(1000));
+
+        let foo = retained(&db, "foo");
+        let bar = retained(&db, "bar");
+        assert!(foo + bar <= 1000);
+
+        // The oldest data is dropped first:
+        assert!(foo < bar);
+
+        // The budget is kept while adding more data:
+        add_samples(&mut db, "foo", 1000..5000);
+        assert!(retained(&db, "foo") + retained(&db, "bar") <= 1000 + 1024);
+    }
+}
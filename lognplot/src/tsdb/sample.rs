@@ -3,9 +3,13 @@
 //! This module enables logging of scalar values.
 
 use super::Metrics;
+use super::QuantileSketch;
+use crate::time::TimeStamp;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// A simple scalar value.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Sample {
     pub value: f64,
 }
@@ -18,7 +22,10 @@ impl Sample {
 
 /// Metrics collected about a certain trace
 /// This can be used during query.
-#[derive(Debug, Clone)]
+///
+/// NaN and infinite values are counted, but left out of the other
+/// statistics, such as the minimum, maximum and mean.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SampleMetrics {
     /// The minimum value of all samples
     pub min: f64,
@@ -41,6 +48,24 @@ pub struct SampleMetrics {
     /// See also: https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Welford's_online_algorithm
     m2: f64,
 
+    /// The sum of all values
+    pub sum: f64,
+
+    /// The sum of the squares of all values, for the RMS value.
+    sum_squares: f64,
+
+    /// When the minimum value was first observed, if known.
+    pub min_timestamp: Option<TimeStamp>,
+
+    /// When the maximum value was first observed, if known.
+    pub max_timestamp: Option<TimeStamp>,
+
+    /// The number of NaN values
+    pub nan_count: usize,
+
+    /// The number of infinite values
+    pub inf_count: usize,
+
     /// The total number of samples
     pub count: usize,
 }
@@ -48,32 +73,56 @@ pub struct SampleMetrics {
 impl SampleMetrics {
     /// Create metrics from a single value!
     fn from_value(value: f64) -> Self {
-        SampleMetrics {
-            min: value,
-            max: value,
-            mean: value,
+        let mut metrics = SampleMetrics {
+            min: f64::NAN,
+            max: f64::NAN,
+            mean: 0.0,
             first: value,
             last: value,
             m2: 0.0,
-            count: 1,
-        }
+            sum: 0.0,
+            sum_squares: 0.0,
+            min_timestamp: None,
+            max_timestamp: None,
+            nan_count: 0,
+            inf_count: 0,
+            count: 0,
+        };
+        metrics.inject_value(value, None);
+        metrics
     }
 
     /// Include a single observation into the mix:
-    fn inject_value(&mut self, value: f64) {
-        // These updates are trivial:
-        self.min = self.min.min(value);
-        self.max = self.max.max(value);
-
+    fn inject_value(&mut self, value: f64, timestamp: Option<&TimeStamp>) {
         // Assume the value is appended:
         self.last = value;
 
         self.count += 1;
 
+        if value.is_nan() {
+            self.nan_count += 1;
+            return;
+        } else if value.is_infinite() {
+            self.inf_count += 1;
+            return;
+        }
+
+        // These updates are trivial:
+        if self.min.is_nan() || value < self.min {
+            self.min = value;
+            self.min_timestamp = timestamp.cloned();
+        }
+        if self.max.is_nan() || value > self.max {
+            self.max = value;
+            self.max_timestamp = timestamp.cloned();
+        }
+        self.sum += value;
+        self.sum_squares += value * value;
+
         // Less trivial update below.. statistical stuff!
 
         // Welford online algorithm:
-        let new_mean = self.mean + (value - self.mean) / self.count as f64;
+        let new_mean = self.mean + (value - self.mean) / self.finite_count() as f64;
         let delta = (value - self.mean) * (value - new_mean);
         self.m2 += delta;
         self.mean = new_mean;
@@ -87,27 +136,41 @@ impl SampleMetrics {
             let (first, rest) = values.split_first().unwrap();
             let mut metrics = SampleMetrics::from_value(*first);
             for value in rest {
-                metrics.inject_value(*value);
+                metrics.inject_value(*value, None);
             }
             Some(metrics)
         }
     }
 
+    /// The number of values which are not NaN or infinite.
+    pub fn finite_count(&self) -> usize {
+        self.count - self.nan_count - self.inf_count
+    }
+
     /// Calculate the mean value of this metrics.
     pub fn mean(&self) -> f64 {
-        self.mean
+        if self.finite_count() > 0 {
+            self.mean
+        } else {
+            f64::NAN
+        }
     }
 
     /// Retrieve the variance
     pub fn variance(&self) -> f64 {
         // Use population variance, since we have all samples!
-        self.m2 / self.count as f64
+        self.m2 / self.finite_count() as f64
     }
 
     /// Calculate the standard deviation
     pub fn stddev(&self) -> f64 {
         self.variance().sqrt()
     }
+
+    /// Calculate the root mean square of the values.
+    pub fn rms(&self) -> f64 {
+        (self.sum_squares / self.finite_count() as f64).sqrt()
+    }
 }
 
 impl From<Sample> for SampleMetrics {
@@ -121,35 +184,149 @@ impl Metrics<Sample> for SampleMetrics {
     /// This involves updating the min and max values
     /// as well as the count and the sum.
     fn update(&mut self, sample: &Sample) {
-        self.inject_value(sample.value);
+        self.inject_value(sample.value, None);
+    }
+
+    fn update_at(&mut self, sample: &Sample, timestamp: &TimeStamp) {
+        self.inject_value(sample.value, Some(timestamp));
+    }
+
+    fn created_at(&mut self, timestamp: &TimeStamp) {
+        if self.finite_count() > 0 {
+            self.min_timestamp = Some(timestamp.clone());
+            self.max_timestamp = Some(timestamp.clone());
+        }
     }
 
     /// Include other metrics into this metrics.
     fn include(&mut self, metrics: &SampleMetrics) {
-        self.min = self.min.min(metrics.min);
-        self.max = self.max.max(metrics.max);
+        if !metrics.min.is_nan() && (self.min.is_nan() || metrics.min < self.min) {
+            self.min = metrics.min;
+            self.min_timestamp = metrics.min_timestamp.clone();
+        }
+        if !metrics.max.is_nan() && (self.max.is_nan() || metrics.max > self.max) {
+            self.max = metrics.max;
+            self.max_timestamp = metrics.max_timestamp.clone();
+        }
 
         // Assume metrics are appended
         self.last = metrics.last;
 
-        let delta = metrics.mean - self.mean;
-        let new_count = self.count + metrics.count;
-        let new_mean = ((self.mean * self.count as f64) + (metrics.mean * metrics.count as f64))
-            / (new_count as f64);
-        let new_m2 = self.m2
-            + metrics.m2
-            + delta * delta * (self.count as f64 * metrics.count as f64) / new_count as f64;
+        let count = self.finite_count();
+        let other_count = metrics.finite_count();
+        let new_count = count + other_count;
+        if new_count > 0 {
+            let delta = metrics.mean - self.mean;
+            let new_mean = ((self.mean * count as f64) + (metrics.mean * other_count as f64))
+                / (new_count as f64);
+            let new_m2 = self.m2
+                + metrics.m2
+                + delta * delta * (count as f64 * other_count as f64) / new_count as f64;
+            self.mean = new_mean;
+            self.m2 = new_m2;
+        }
 
-        self.count = new_count;
-        self.mean = new_mean;
-        self.m2 = new_m2;
+        self.count += metrics.count;
+        self.nan_count += metrics.nan_count;
+        self.inf_count += metrics.inf_count;
+        self.sum += metrics.sum;
+        self.sum_squares += metrics.sum_squares;
+    }
+}
+
+/// A statistic of sample metrics, which can be selected for display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistic {
+    Min,
+    Max,
+    Mean,
+    StdDev,
+    Rms,
+    Sum,
+    Count,
+    NanCount,
+    InfCount,
+
+    /// Time of the minimum value, in seconds.
+    MinTime,
+
+    /// Time of the maximum value, in seconds.
+    MaxTime,
+
+    /// Approximate quantile, such as 0.95 for the 95th percentile.
+    ///
+    /// Quantiles cannot be determined from the metrics, they need
+    /// a sketch of the values.
+    Quantile(f64),
+}
+
+/// The statistics which can be selected for display.
+pub const STATISTICS: &[Statistic] = &[
+    Statistic::Min,
+    Statistic::Max,
+    Statistic::Mean,
+    Statistic::StdDev,
+    Statistic::Rms,
+    Statistic::Sum,
+    Statistic::Count,
+    Statistic::NanCount,
+    Statistic::InfCount,
+    Statistic::MinTime,
+    Statistic::MaxTime,
+    Statistic::Quantile(0.5),
+    Statistic::Quantile(0.95),
+    Statistic::Quantile(0.99),
+];
+
+impl Statistic {
+    /// Test if this statistic needs a sketch of the values.
+    pub fn needs_sketch(self) -> bool {
+        matches!(self, Statistic::Quantile(_))
+    }
+
+    /// Get this statistic from the metrics, or the sketch, if known.
+    pub fn value(self, metrics: &SampleMetrics, sketch: Option<&QuantileSketch>) -> Option<f64> {
+        match self {
+            Statistic::Min => Some(metrics.min),
+            Statistic::Max => Some(metrics.max),
+            Statistic::Mean => Some(metrics.mean()),
+            Statistic::StdDev => Some(metrics.stddev()),
+            Statistic::Rms => Some(metrics.rms()),
+            Statistic::Sum => Some(metrics.sum),
+            Statistic::Count => Some(metrics.count as f64),
+            Statistic::NanCount => Some(metrics.nan_count as f64),
+            Statistic::InfCount => Some(metrics.inf_count as f64),
+            Statistic::MinTime => metrics.min_timestamp.as_ref().map(TimeStamp::to_seconds),
+            Statistic::MaxTime => metrics.max_timestamp.as_ref().map(TimeStamp::to_seconds),
+            Statistic::Quantile(fraction) => sketch?.quantile(fraction),
+        }
+    }
+}
+
+impl fmt::Display for Statistic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statistic::Min => write!(f, "min"),
+            Statistic::Max => write!(f, "max"),
+            Statistic::Mean => write!(f, "mean"),
+            Statistic::StdDev => write!(f, "stddev"),
+            Statistic::Rms => write!(f, "rms"),
+            Statistic::Sum => write!(f, "sum"),
+            Statistic::Count => write!(f, "count"),
+            Statistic::NanCount => write!(f, "nan"),
+            Statistic::InfCount => write!(f, "inf"),
+            Statistic::MinTime => write!(f, "t(min)"),
+            Statistic::MaxTime => write!(f, "t(max)"),
+            Statistic::Quantile(fraction) => write!(f, "p{}", fraction * 100.0),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Metrics;
-    use super::{Sample, SampleMetrics};
+    use super::{Sample, SampleMetrics, Statistic};
+    use crate::time::TimeStamp;
 
     fn almost_equal(v1: f64, v2: f64, tolerance: f64) {
         assert!((v1 - v2).abs() < tolerance);
@@ -188,4 +365,36 @@ mod tests {
         assert_eq!(metrics.variance(), 2.0);
         almost_equal(metrics.stddev(), 1.414213562373, 1.0e-9);
     }
+
+    #[test]
+    fn extended_metrics() {
+        let values = [3.0, -4.0, f64::NAN, 4.0, f64::INFINITY, -3.0];
+        let mut metrics = SampleMetrics::from(Sample::new(values[0]));
+        metrics.created_at(&TimeStamp::new(0.0));
+        for (t, value) in values.iter().enumerate().skip(1) {
+            metrics.update_at(&Sample::new(*value), &TimeStamp::new(t as f64));
+        }
+
+        assert_eq!(metrics.count, 6);
+        assert_eq!(metrics.nan_count, 1);
+        assert_eq!(metrics.inf_count, 1);
+        assert_eq!(metrics.min, -4.0);
+        assert_eq!(metrics.max, 4.0);
+        assert_eq!(metrics.min_timestamp, Some(TimeStamp::new(1.0)));
+        assert_eq!(metrics.max_timestamp, Some(TimeStamp::new(3.0)));
+        assert_eq!(metrics.sum, 0.0);
+        assert_eq!(metrics.mean(), 0.0);
+        assert_eq!(metrics.rms(), 3.5355339059327378);
+        assert_eq!(Statistic::MaxTime.value(&metrics, None), Some(3.0));
+        assert_eq!(Statistic::Quantile(0.5).value(&metrics, None), None);
+
+        // Combining metrics gives the same result as updating them:
+        let mut first = SampleMetrics::from_values(&values[..3]).unwrap();
+        first.include(&SampleMetrics::from_values(&values[3..]).unwrap());
+        assert_eq!(first.count, 6);
+        assert_eq!(first.nan_count, 1);
+        assert_eq!(first.mean(), 0.0);
+        assert_eq!(first.variance(), metrics.variance());
+        assert_eq!(first.rms(), metrics.rms());
+    }
 }
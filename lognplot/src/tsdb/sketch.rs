@@ -0,0 +1,224 @@
+//! Approximate quantiles of a set of values.
+//!
+//! Values are counted in buckets with logarithmically growing sizes,
+//! as in the DDSketch algorithm. This gives quantiles with a bounded
+//! relative error, and two sketches can be merged by adding up the
+//! counts of their buckets.
+//!
+//! Sketches are too big to keep one in each node of the b-tree, so they
+//! are filled on demand from the observations in a range of time.
+
+use super::btree::RangeVisitor;
+use super::{Aggregation, Observation, Sample, SampleMetrics};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Relative accuracy of the estimated quantiles.
+const ACCURACY: f64 = 0.01;
+
+/// Limit the number of buckets for positive and for negative values.
+///
+/// When there are more buckets, the buckets closest to zero are merged.
+const MAX_BUCKETS: usize = 512;
+
+/// Values closer to zero than this are counted as zero.
+const MIN_VALUE: f64 = 1.0e-12;
+
+/// A mergeable summary of the distribution of values.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QuantileSketch {
+    /// Counts of positive values, by bucket index.
+    positive: BTreeMap<i32, usize>,
+
+    /// Counts of negative values, by bucket index of their magnitude.
+    negative: BTreeMap<i32, usize>,
+
+    zero: usize,
+    count: usize,
+}
+
+impl QuantileSketch {
+    /// Add a finite value to the sketch.
+    pub fn insert(&mut self, value: f64) {
+        self.insert_many(value, 1);
+    }
+
+    /// Add a finite value to the sketch, the given amount of times.
+    fn insert_many(&mut self, value: f64, count: usize) {
+        if value >= MIN_VALUE {
+            add_to_bucket(&mut self.positive, bucket_index(value), count);
+        } else if value <= -MIN_VALUE {
+            add_to_bucket(&mut self.negative, bucket_index(-value), count);
+        } else {
+            self.zero += count;
+        }
+        self.count += count;
+    }
+
+    /// The number of values in the sketch.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Merge another sketch into this one.
+    pub fn include(&mut self, other: &QuantileSketch) {
+        for (index, count) in &other.positive {
+            add_to_bucket(&mut self.positive, *index, *count);
+        }
+        for (index, count) in &other.negative {
+            add_to_bucket(&mut self.negative, *index, *count);
+        }
+        self.zero += other.zero;
+        self.count += other.count;
+    }
+
+    /// Estimate the value below which the given fraction of the values lies.
+    ///
+    /// The fraction is clamped between 0 and 1.
+    pub fn quantile(&self, fraction: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = (fraction.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as usize;
+        let mut seen = 0;
+
+        // From the most negative value up to the most positive value:
+        for (index, count) in self.negative.iter().rev() {
+            seen += count;
+            if seen > rank {
+                return Some(-bucket_value(*index));
+            }
+        }
+        seen += self.zero;
+        if seen > rank {
+            return Some(0.0);
+        }
+        for (index, count) in &self.positive {
+            seen += count;
+            if seen > rank {
+                return Some(bucket_value(*index));
+            }
+        }
+
+        None
+    }
+}
+
+impl RangeVisitor<Sample, SampleMetrics> for QuantileSketch {
+    /// Add all values at once, when they are all the same.
+    fn aggregation(&mut self, aggregation: &Aggregation<Sample, SampleMetrics>) -> bool {
+        let metrics = aggregation.metrics();
+        let finite = metrics.finite_count();
+        if finite == 0 {
+            true
+        } else if metrics.min == metrics.max {
+            self.insert_many(metrics.min, finite);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The dropped values are unknown, so count them as their mean value.
+    fn pruned(&mut self, aggregation: &Aggregation<Sample, SampleMetrics>) {
+        if !self.aggregation(aggregation) {
+            let metrics = aggregation.metrics();
+            self.insert_many(metrics.mean(), metrics.finite_count());
+        }
+    }
+
+    fn observation(&mut self, observation: &Observation<Sample>) {
+        if observation.value.value.is_finite() {
+            self.insert(observation.value.value);
+        }
+    }
+}
+
+fn gamma() -> f64 {
+    (1.0 + ACCURACY) / (1.0 - ACCURACY)
+}
+
+/// The bucket index for a positive value.
+fn bucket_index(value: f64) -> i32 {
+    (value.ln() / gamma().ln()).ceil() as i32
+}
+
+/// The value which represents a bucket, with the smallest relative error.
+fn bucket_value(index: i32) -> f64 {
+    2.0 * gamma().powi(index) / (gamma() + 1.0)
+}
+
+fn add_to_bucket(buckets: &mut BTreeMap<i32, usize>, index: i32, count: usize) {
+    *buckets.entry(index).or_insert(0) += count;
+
+    while buckets.len() > MAX_BUCKETS {
+        let lowest_index = *buckets.keys().next().unwrap();
+        let lowest = buckets.remove(&lowest_index).unwrap();
+        *buckets.values_mut().next().unwrap() += lowest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuantileSketch, ACCURACY};
+    use crate::time::{TimeSpan, TimeStamp};
+    use crate::tsdb::{Observation, Sample, SampleMetrics, Trace};
+
+    fn assert_close(expected: f64, actual: Option<f64>) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() <= expected.abs() * ACCURACY,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn quantiles() {
+        let mut sketch = QuantileSketch::default();
+        assert_eq!(None, sketch.quantile(0.5));
+
+        for value in 1..=1000 {
+            sketch.insert(value as f64);
+        }
+        assert_close(1.0, sketch.quantile(0.0));
+        assert_close(500.0, sketch.quantile(0.5));
+        assert_close(990.0, sketch.quantile(0.99));
+        assert_close(1000.0, sketch.quantile(1.0));
+    }
+
+    #[test]
+    fn merge_sketches() {
+        let mut negative = QuantileSketch::default();
+        let mut positive = QuantileSketch::default();
+        for value in 1..=100 {
+            negative.insert(-value as f64);
+            positive.insert(value as f64);
+        }
+        positive.insert(0.0);
+
+        negative.include(&positive);
+        assert_close(-100.0, negative.quantile(0.0));
+        assert_eq!(Some(0.0), negative.quantile(0.5));
+        assert_close(50.0, negative.quantile(0.75));
+    }
+
+    #[test]
+    fn sketch_trace() {
+        let mut trace: Trace<Sample, SampleMetrics> = Trace::default();
+        for i in 0..10_000 {
+            // Long runs of equal values are taken as a whole:
+            let value = (i / 1000) as f64;
+            let observation = Observation::new(TimeStamp::new(i as f64), Sample::new(value));
+            trace.add_observation(observation);
+        }
+        let timespan = TimeSpan::new(TimeStamp::new(2000.0), TimeStamp::new(9999.0));
+        let sketch = trace.quantile_sketch(&timespan);
+        assert_eq!(8000, sketch.count());
+        assert_close(2.0, sketch.quantile(0.0));
+        assert_close(5.0, sketch.quantile(0.45));
+        assert_close(9.0, sketch.quantile(1.0));
+    }
+}
@@ -0,0 +1,173 @@
+//! Read-only views of the database at a point in time.
+//!
+//! A snapshot holds a copy of each trace. Copies of traces share
+//! the nodes of their trees with the live traces, so taking a snapshot
+//! is cheap. New data added to the database afterwards is not visible
+//! in the snapshot.
+
+use super::derived::Signals;
+use super::namespace::{is_in_namespace, SignalTree};
+use super::{Bins, CallSpan, CountMetrics, Histogram, LookupMode, Metadata};
+use super::{LastValue, SharedTrack, Track};
+use super::{Observation, ProfileEvent, Query, QueryResult, QuickSummary, Sample, Summary, Text};
+use super::{QuantileSketch, RangeQueryResult};
+use crate::time::{TimeSpan, TimeStamp};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// A consistent, read-only view of all signals in a database.
+///
+/// Each trace in the snapshot contains whole batches of observations,
+/// as they were added to the database. Cloning a snapshot is cheap.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    tracks: HashMap<String, SharedTrack>,
+    metadata: HashMap<String, Metadata>,
+}
+
+impl Snapshot {
+    pub fn new(tracks: HashMap<String, Track>, metadata: HashMap<String, Metadata>) -> Self {
+        let tracks = tracks
+            .into_iter()
+            .map(|(name, track)| (name, Arc::new(RwLock::new(track))))
+            .collect();
+        Snapshot { tracks, metadata }
+    }
+
+    /// Take a snapshot of the given signals.
+    ///
+    /// Derived signals are evaluated when required.
+    pub fn from_signals(signals: &Signals, metadata: HashMap<String, Metadata>) -> Self {
+        let tracks = signals
+            .names()
+            .into_iter()
+            .filter_map(|name| {
+                let track = signals.track(&name)?.read().unwrap().clone();
+                Some((name, track))
+            })
+            .collect();
+        Snapshot::new(tracks, metadata)
+    }
+
+    pub fn get_signal_names(&self) -> Vec<String> {
+        self.tracks.keys().cloned().collect()
+    }
+
+    /// Get the signal names, arranged as a tree of namespaces.
+    pub fn get_signal_tree(&self) -> SignalTree {
+        SignalTree::from_names(self.tracks.keys())
+    }
+
+    /// Get the names of the signals in a namespace.
+    pub fn get_signal_names_in(&self, namespace: &str) -> Vec<String> {
+        self.tracks
+            .keys()
+            .filter(|name| is_in_namespace(name, namespace))
+            .cloned()
+            .collect()
+    }
+
+    /// Get the trace with the given name.
+    ///
+    /// The trace is never changed, since it is only part of this snapshot.
+    pub fn track(&self, name: &str) -> Option<SharedTrack> {
+        self.tracks.get(name).cloned()
+    }
+
+    pub fn metadata(&self, name: &str) -> Option<Metadata> {
+        self.metadata.get(name).cloned()
+    }
+
+    /// Get the units of several signals, for those which have one.
+    pub fn units(&self, names: &[&str]) -> Vec<String> {
+        names
+            .iter()
+            .filter_map(|name| self.metadata.get(*name)?.unit.clone())
+            .collect()
+    }
+
+    pub fn query(&self, name: &str, query: Query) -> Option<QueryResult> {
+        Some(self.track(name)?.read().unwrap().query(query))
+    }
+
+    pub fn query_text(
+        &self,
+        name: &str,
+        query: Query,
+    ) -> Option<RangeQueryResult<Text, CountMetrics>> {
+        self.track(name)?.read().unwrap().query_text(query)
+    }
+
+    pub fn query_profile(
+        &self,
+        name: &str,
+        query: Query,
+    ) -> Option<RangeQueryResult<ProfileEvent, CountMetrics>> {
+        self.track(name)?.read().unwrap().query_profile(query)
+    }
+
+    pub fn query_calls(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+        amount: usize,
+    ) -> Option<Vec<CallSpan>> {
+        self.track(name)?
+            .read()
+            .unwrap()
+            .query_calls(timespan, amount)
+    }
+
+    pub fn observation_at(
+        &self,
+        name: &str,
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Option<LastValue> {
+        self.track(name)?
+            .read()
+            .unwrap()
+            .observation_at(timestamp, mode)
+    }
+
+    pub fn value_at(
+        &self,
+        name: &str,
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Option<Observation<Sample>> {
+        self.track(name)?.read().unwrap().value_at(timestamp, mode)
+    }
+
+    pub fn values_at(
+        &self,
+        names: &[&str],
+        timestamp: &TimeStamp,
+        mode: LookupMode,
+    ) -> Vec<Option<Observation<Sample>>> {
+        names
+            .iter()
+            .map(|name| self.value_at(name, timestamp, mode))
+            .collect()
+    }
+
+    pub fn histogram(&self, name: &str, timespan: &TimeSpan, bins: &Bins) -> Option<Histogram> {
+        self.track(name)?.read().unwrap().histogram(timespan, bins)
+    }
+
+    pub fn quantile_sketch(&self, name: &str, timespan: &TimeSpan) -> Option<QuantileSketch> {
+        self.track(name)?.read().unwrap().quantile_sketch(timespan)
+    }
+
+    pub fn get_raw_samples(&self, name: &str) -> Option<Vec<Observation<Sample>>> {
+        Some(self.track(name)?.read().unwrap().to_vec())
+    }
+
+    pub fn quick_summary(&self, name: &str) -> Option<QuickSummary> {
+        self.track(name)?.read().unwrap().quick_summary()
+    }
+
+    pub fn summary(&self, name: &str, timespan: Option<&TimeSpan>) -> Option<Summary> {
+        self.track(name)?.read().unwrap().summary(timespan)
+    }
+}
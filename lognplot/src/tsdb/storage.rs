@@ -0,0 +1,633 @@
+//! Persistent storage of the database on disk.
+//!
+//! The database is stored in a directory. Each trace has its own
+//! append-only segment file, which starts with a header, followed by
+//! length prefixed CBOR records holding batches of observations.
+//!
+//! Next to each segment, a checkpoint file can be stored with an image
+//! of the b-tree, including all aggregated metrics. When reopening the
+//! database, this image is used to restore the tree without re-calculating
+//! the metrics of all observations.
+//!
+//! The metadata of all signals, such as their units, is kept in a
+//! single file, which is rewritten on each change.
+
+use super::btree::{Fanout, NodeImage};
+use super::heap_size::HeapSize;
+use super::trace::Trace;
+use super::{CallTree, Compress, Metadata, Metrics, Observation, Track, TrackType};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Pending writes are flushed to disk at least this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SEGMENT_EXTENSION: &str = "seg";
+const CHECKPOINT_EXTENSION: &str = "idx";
+const METADATA_FILE: &str = "metadata.cbor";
+const FORMAT_VERSION: u32 = 2;
+
+/// The first record in each segment file.
+#[derive(Serialize, Deserialize)]
+struct SegmentHeader {
+    version: u32,
+    track_type: TrackType,
+
+    /// The component names of a vector trace.
+    #[serde(default)]
+    components: Vec<String>,
+
+    /// The fanout of the tree, which the checkpoint images fit.
+    #[serde(default)]
+    fanout: Fanout,
+}
+
+/// A directory with segment files, one for each trace.
+#[derive(Debug)]
+pub struct Storage {
+    path: PathBuf,
+    segments: HashMap<String, BufWriter<File>>,
+    last_flush: Instant,
+}
+
+impl Storage {
+    /// Open storage in the given directory, and load all traces stored in it.
+    ///
+    /// The directory is created when it does not exist yet. Segments which
+    /// cannot be loaded, for example because they are empty or of another
+    /// format version, are skipped, so the other traces remain available.
+    pub fn open(path: &Path) -> io::Result<(Self, Vec<(String, Track)>)> {
+        fs::create_dir_all(path)?;
+        let mut storage = Storage {
+            path: path.to_owned(),
+            segments: HashMap::new(),
+            last_flush: Instant::now(),
+        };
+
+        let mut tracks = vec![];
+        for entry in fs::read_dir(path)? {
+            let segment_path = entry?.path();
+            if segment_path.extension() != Some(SEGMENT_EXTENSION.as_ref()) {
+                continue;
+            }
+
+            let name = segment_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(decode_name);
+            if let Some(name) = name {
+                match storage.load_segment(&name) {
+                    Ok(track) => {
+                        debug!("Loaded trace {} from {:?}", name, segment_path);
+                        tracks.push((name, track));
+                    }
+                    Err(err) => {
+                        warn!("Skipping segment {:?}: {}", segment_path, err);
+                    }
+                }
+            } else {
+                warn!("Skipping segment with invalid name: {:?}", segment_path);
+            }
+        }
+
+        Ok((storage, tracks))
+    }
+
+    /// The directory in which the data is stored.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load all data from a segment, and prepare it for appending.
+    fn load_segment(&mut self, name: &str) -> io::Result<Track> {
+        let segment_path = self.segment_path(name);
+        let checkpoint_path = self.checkpoint_path(name);
+        let file = File::open(&segment_path)?;
+        let mut remaining = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let header = read_blob(&mut reader, &mut remaining)?
+            .ok_or_else(|| invalid_data("Missing header"))?;
+        let header_size = (4 + header.len()) as u64;
+        let header: SegmentHeader = serde_cbor::from_slice(&header).map_err(invalid_data)?;
+        if header.version != FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported segment version {}",
+                header.version
+            )));
+        }
+
+        let (track, records_size) = match header.track_type {
+            TrackType::Value => {
+                let (trace, size) = load_trace(reader, remaining, &checkpoint_path, header.fanout)?;
+                (Track::Value(trace), size)
+            }
+            TrackType::Text => {
+                let (trace, size) = load_trace(reader, remaining, &checkpoint_path, header.fanout)?;
+                (Track::Text(trace), size)
+            }
+            TrackType::Profile => {
+                let (trace, size) = load_trace(reader, remaining, &checkpoint_path, header.fanout)?;
+                let calls = CallTree::from_observations(trace.to_vec());
+                (Track::Profile(trace, calls), size)
+            }
+            TrackType::Vector => {
+                let (trace, size) = load_trace(reader, remaining, &checkpoint_path, header.fanout)?;
+                (Track::Vector(trace, header.components), size)
+            }
+            TrackType::Event => {
+                let (trace, size) = load_trace(reader, remaining, &checkpoint_path, header.fanout)?;
+                (Track::Event(trace), size)
+            }
+            TrackType::Log => {
+                let (trace, size) = load_trace(reader, remaining, &checkpoint_path, header.fanout)?;
+                (Track::Log(trace), size)
+            }
+        };
+
+        // Cut off a partially written record, so we can continue appending:
+        let file = OpenOptions::new().append(true).open(&segment_path)?;
+        file.set_len(header_size + records_size)?;
+        self.segments.insert(name.to_owned(), BufWriter::new(file));
+
+        Ok(track)
+    }
+
+    /// Start a new, empty segment for the given trace.
+    pub fn create_segment(&mut self, name: &str, track: &Track) -> io::Result<()> {
+        let header = SegmentHeader {
+            version: FORMAT_VERSION,
+            track_type: track.get_type(),
+            components: track.components().to_vec(),
+            fanout: track.fanout(),
+        };
+        let header = serde_cbor::to_vec(&header).map_err(invalid_data)?;
+
+        let mut writer = BufWriter::new(File::create(self.segment_path(name))?);
+        write_blob(&mut writer, &header)?;
+
+        // A segment without header cannot be loaded, so write it right away:
+        writer.flush()?;
+        remove_if_present(&self.checkpoint_path(name))?;
+        self.segments.insert(name.to_owned(), writer);
+        Ok(())
+    }
+
+    /// Append a batch of observations to the segment of a trace.
+    pub fn append<V>(&mut self, name: &str, observations: &[Observation<V>]) -> io::Result<()>
+    where
+        V: Serialize,
+    {
+        let writer = self
+            .segments
+            .get_mut(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No segment for trace"))?;
+        let data = serde_cbor::to_vec(&observations).map_err(invalid_data)?;
+        write_blob(writer, &data)?;
+
+        if self.last_flush.elapsed() > FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Store the data of a trace under a new name.
+    pub fn rename(&mut self, name: &str, new_name: &str) -> io::Result<()> {
+        if let Some(mut writer) = self.segments.remove(name) {
+            writer.flush()?;
+        }
+
+        fs::rename(self.segment_path(name), self.segment_path(new_name))?;
+        if self.checkpoint_path(name).exists() {
+            fs::rename(self.checkpoint_path(name), self.checkpoint_path(new_name))?;
+        }
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(self.segment_path(new_name))?;
+        self.segments
+            .insert(new_name.to_owned(), BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Remove all stored data of a trace.
+    pub fn remove(&mut self, name: &str) -> io::Result<()> {
+        self.segments.remove(name);
+        remove_if_present(&self.segment_path(name))?;
+        remove_if_present(&self.checkpoint_path(name))
+    }
+
+    /// Remove the data of all traces.
+    pub fn remove_all(&mut self) -> io::Result<()> {
+        let names: Vec<String> = self.segments.keys().cloned().collect();
+        for name in names {
+            self.remove(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Write a checkpoint with the aggregates of the given track.
+    ///
+    /// The segment is flushed first, so that the checkpoint never refers
+    /// to observations which are not on disk yet.
+    pub fn checkpoint(&mut self, name: &str, track: &Track) -> io::Result<()> {
+        if let Some(writer) = self.segments.get_mut(name) {
+            writer.flush()?;
+        }
+
+        let data = match track {
+            Track::Value(trace) => serde_cbor::to_vec(&trace.image()),
+            Track::Text(trace) => serde_cbor::to_vec(&trace.image()),
+            Track::Profile(trace, _) => serde_cbor::to_vec(&trace.image()),
+            Track::Vector(trace, _) => serde_cbor::to_vec(&trace.image()),
+            Track::Event(trace) => serde_cbor::to_vec(&trace.image()),
+            Track::Log(trace) => serde_cbor::to_vec(&trace.image()),
+        }
+        .map_err(invalid_data)?;
+
+        // Write to a temporary file first, to never leave a half written checkpoint.
+        let checkpoint_path = self.checkpoint_path(name);
+        let temporary_path = checkpoint_path.with_extension("tmp");
+        fs::write(&temporary_path, data)?;
+        fs::rename(temporary_path, checkpoint_path)
+    }
+
+    /// Load the metadata of all signals.
+    ///
+    /// Missing or broken metadata is not fatal, the signals are
+    /// loaded without their metadata.
+    pub fn load_metadata(&self) -> HashMap<String, Metadata> {
+        let path = self.path.join(METADATA_FILE);
+        match fs::read(&path) {
+            Ok(data) => serde_cbor::from_slice(&data).unwrap_or_else(|err| {
+                warn!("Ignoring broken metadata {:?}: {}", path, err);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    /// Replace the stored metadata of all signals.
+    pub fn save_metadata(&self, metadata: &HashMap<String, Metadata>) -> io::Result<()> {
+        let data = serde_cbor::to_vec(metadata).map_err(invalid_data)?;
+        let path = self.path.join(METADATA_FILE);
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, data)?;
+        fs::rename(temporary_path, path)
+    }
+
+    /// Flush all pending writes to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        for writer in self.segments.values_mut() {
+            writer.flush()?;
+        }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    fn segment_path(&self, name: &str) -> PathBuf {
+        self.path
+            .join(encode_name(name))
+            .with_extension(SEGMENT_EXTENSION)
+    }
+
+    fn checkpoint_path(&self, name: &str) -> PathBuf {
+        self.path
+            .join(encode_name(name))
+            .with_extension(CHECKPOINT_EXTENSION)
+    }
+}
+
+/// Read all observation records from a segment, and restore the trace.
+///
+/// Returns the trace and the size of all valid records.
+fn load_trace<V, M, R>(
+    mut reader: R,
+    mut remaining: u64,
+    checkpoint_path: &Path,
+    fanout: Fanout,
+) -> io::Result<(Trace<V, M>, u64)>
+where
+    V: Clone + Compress + HeapSize + DeserializeOwned,
+    M: Metrics<V> + From<V> + Clone + HeapSize + DeserializeOwned,
+    R: Read,
+{
+    let mut observations: Vec<Observation<V>> = vec![];
+    let mut size: u64 = 0;
+
+    while let Some(blob) = read_blob(&mut reader, &mut remaining)? {
+        match serde_cbor::from_slice::<Vec<Observation<V>>>(&blob) {
+            Ok(batch) => {
+                observations.extend(batch);
+                size += (4 + blob.len()) as u64;
+            }
+            Err(err) => {
+                warn!("Dropping corrupt record from segment: {}", err);
+                break;
+            }
+        }
+    }
+
+    let image = read_checkpoint(checkpoint_path);
+    Ok((Trace::restore(observations, image, fanout), size))
+}
+
+/// Read a checkpoint file, if any.
+///
+/// A missing or broken checkpoint is not a problem, since we can
+/// always re-calculate the aggregates from the observations.
+fn read_checkpoint<V, M>(path: &Path) -> Option<NodeImage<V, M>>
+where
+    M: Metrics<V> + From<V> + DeserializeOwned,
+    V: DeserializeOwned,
+{
+    let data = fs::read(path).ok()?;
+    match serde_cbor::from_slice(&data) {
+        Ok(image) => Some(image),
+        Err(err) => {
+            warn!("Ignoring broken checkpoint {:?}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Write a length prefixed blob of data.
+fn write_blob<W: Write>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    let size = data.len() as u32;
+    writer.write_all(&size.to_be_bytes())?;
+    writer.write_all(data)
+}
+
+/// Read a length prefixed blob of data, from the remaining bytes of a file.
+///
+/// Returns None at the end of the file, or when the last blob was
+/// only partially written. A length which does not fit in the rest
+/// of the file is treated as a partially written blob, so that a
+/// corrupt length never leads to a huge allocation.
+fn read_blob<R: Read>(reader: &mut R, remaining: &mut u64) -> io::Result<Option<Vec<u8>>> {
+    let mut header: [u8; 4] = [0; 4];
+    if let Err(err) = reader.read_exact(&mut header) {
+        return end_of_blobs(err);
+    }
+
+    let size = u64::from(u32::from_be_bytes(header));
+    if 4 + size > *remaining {
+        return Ok(None);
+    }
+
+    let mut data = vec![0; size as usize];
+    if let Err(err) = reader.read_exact(&mut data) {
+        return end_of_blobs(err);
+    }
+
+    *remaining -= 4 + size;
+    Ok(Some(data))
+}
+
+fn end_of_blobs(err: io::Error) -> io::Result<Option<Vec<u8>>> {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        Ok(None)
+    } else {
+        Err(err)
+    }
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Turn a signal name into a safe file name.
+///
+/// All characters, except for ascii letters, digits, '-' and '_',
+/// are percent encoded.
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Inverse of `encode_name`.
+fn decode_name(encoded: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut chars = encoded.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let high = (chars.next()? as char).to_digit(16)?;
+            let low = (chars.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_name, encode_name, read_blob, write_blob};
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Fanout, Metadata, Observation, Sample, Text, TsDb};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("lognplot_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn name_encoding() {
+        let name = "robot/arm.joint3 [°]";
+        let encoded = encode_name(name);
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '%'));
+        assert_eq!(Some(name.to_owned()), decode_name(&encoded));
+    }
+
+    #[test]
+    fn blob_lengths() {
+        let mut data = vec![];
+        write_blob(&mut data, &[1, 2, 3]).unwrap();
+        let mut reader = &data[..];
+        let mut remaining = data.len() as u64;
+        assert_eq!(
+            Some(vec![1, 2, 3]),
+            read_blob(&mut reader, &mut remaining).unwrap()
+        );
+        assert_eq!(0, remaining);
+
+        // A corrupt length beyond the end of the file:
+        data.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 1, 2]);
+        let mut reader = &data[7..];
+        let mut remaining = 6;
+        assert_eq!(None, read_blob(&mut reader, &mut remaining).unwrap());
+    }
+
+    #[test]
+    fn reopen_and_append() {
+        let path = test_dir("reopen");
+        let add_samples = |db: &mut TsDb, range: std::ops::Range<isize>| {
+            for i in range {
+                let ts = TimeStamp::from_seconds(i);
+                db.add_value("foo", Observation::new(ts, Sample::new(i as f64)));
+            }
+        };
+
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            add_samples(&mut db, 0..100);
+            let text = Observation::new(TimeStamp::from_seconds(3), Text::new("bar".to_owned()));
+            db.add_text("log", text);
+        }
+        let old_checkpoint = std::fs::read(path.join("foo.idx")).unwrap();
+
+        // Reopen, and continue appending:
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            assert_eq!(100, db.quick_summary("foo").unwrap().count);
+            assert_eq!(1, db.quick_summary("log").unwrap().count);
+            add_samples(&mut db, 100..110);
+        }
+
+        // Simulate an outdated checkpoint and a partially written record:
+        std::fs::write(path.join("foo.idx"), old_checkpoint).unwrap();
+        let mut segment = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path.join("foo.seg"))
+            .unwrap();
+        segment.write_all(&[0, 0, 0, 50, 1, 2]).unwrap();
+
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            assert_eq!(110, db.quick_summary("foo").unwrap().count);
+            assert_eq!(110, db.summary("foo", None).unwrap().count());
+            add_samples(&mut db, 110..111);
+            db.flush().unwrap();
+
+            // A late sample after the checkpoint:
+            let late = Observation::new(TimeStamp::new(50.5), Sample::new(0.0));
+            db.add_value("foo", late);
+        }
+
+        let db = TsDb::open(&path).unwrap();
+        let samples = db.get_raw_samples("foo").unwrap();
+        assert_eq!(112, samples.len());
+        assert_eq!(TimeStamp::new(50.5), samples[51].timestamp);
+        assert_eq!(TimeStamp::from_seconds(110), samples[111].timestamp);
+        drop(db);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn skip_broken_segments() {
+        let path = test_dir("broken");
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            let ts = TimeStamp::from_seconds(1);
+            db.add_value("foo", Observation::new(ts, Sample::new(2.0)));
+        }
+
+        // A segment of which the header was never written:
+        std::fs::write(path.join("bar.seg"), []).unwrap();
+
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            assert_eq!(1, db.quick_summary("foo").unwrap().count);
+            assert!(db.quick_summary("bar").is_none());
+
+            // The broken segment is replaced by new data:
+            let ts = TimeStamp::from_seconds(2);
+            db.add_value("bar", Observation::new(ts, Sample::new(3.0)));
+
+            // The header of the new segment is on disk right away:
+            assert!(std::fs::metadata(path.join("bar.seg")).unwrap().len() > 0);
+        }
+
+        let db = TsDb::open(&path).unwrap();
+        assert_eq!(1, db.quick_summary("bar").unwrap().count);
+        drop(db);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn reopen_metadata() {
+        let path = test_dir("metadata");
+        let metadata = Metadata {
+            unit: Some("m/s".to_owned()),
+            ..Metadata::default()
+        };
+
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            let ts = TimeStamp::from_seconds(1);
+            db.add_value("speed", Observation::new(ts, Sample::new(2.0)));
+            db.set_metadata("speed", metadata.clone());
+            db.set_metadata("unused", metadata.clone());
+            db.delete("unused");
+        }
+
+        let db = TsDb::open(&path).unwrap();
+        assert_eq!(Some(metadata), db.metadata("speed"));
+        assert_eq!(None, db.metadata("unused"));
+        drop(db);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn reopen_with_fanout() {
+        let path = test_dir("fanout");
+        let fanout = Fanout::new(64, 16);
+        let samples = |range: std::ops::Range<isize>| {
+            range
+                .map(|i| Observation::new(TimeStamp::from_seconds(i), Sample::new(i as f64)))
+                .collect()
+        };
+
+        {
+            let mut db = TsDb::open(&path).unwrap();
+            db.set_trace_fanout("foo", fanout);
+            db.add_values("foo", samples(0..1000));
+            db.flush().unwrap();
+        }
+
+        let mut db = TsDb::open(&path).unwrap();
+        let track = db.track("foo").unwrap();
+        assert_eq!(fanout, track.read().unwrap().fanout());
+        assert_eq!(1000, db.summary("foo", None).unwrap().count());
+
+        // A new trace under the same name gets the same fanout:
+        let text = Observation::new(TimeStamp::from_seconds(3), Text::new("bar".to_owned()));
+        db.add_text("foo", text);
+        let track = db.track("foo").unwrap();
+        assert_eq!(fanout, track.read().unwrap().fanout());
+        drop(db);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
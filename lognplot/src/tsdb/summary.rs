@@ -1,12 +1,17 @@
 use super::{Aggregation, Observation};
-use super::{CountMetrics, ProfileEvent, Sample, SampleMetrics, Text};
+use super::{CountMetrics, ProfileEvent, Sample, SampleMetrics, Text, Vector, VectorMetrics};
+use super::{Event, EventMetrics, LogMetrics, LogRecord};
 use crate::time::{TimeSpan, TimeStamp};
+use std::fmt;
 
 /// Data summary
 pub enum Summary {
     Value(Aggregation<Sample, SampleMetrics>),
     Text(Aggregation<Text, CountMetrics>),
     Profile(Aggregation<ProfileEvent, CountMetrics>),
+    Vector(Aggregation<Vector, VectorMetrics>),
+    Event(Aggregation<Event, EventMetrics>),
+    Log(Aggregation<LogRecord, LogMetrics>),
 }
 
 impl Summary {
@@ -15,6 +20,9 @@ impl Summary {
             Summary::Value(summary) => summary.count,
             Summary::Text(summary) => summary.count,
             Summary::Profile(summary) => summary.count,
+            Summary::Vector(summary) => summary.count,
+            Summary::Event(summary) => summary.count,
+            Summary::Log(summary) => summary.count,
         }
     }
 
@@ -23,6 +31,9 @@ impl Summary {
             Summary::Value(summary) => &summary.timespan,
             Summary::Text(summary) => &summary.timespan,
             Summary::Profile(summary) => &summary.timespan,
+            Summary::Vector(summary) => &summary.timespan,
+            Summary::Event(summary) => &summary.timespan,
+            Summary::Log(summary) => &summary.timespan,
         }
     }
 }
@@ -56,26 +67,69 @@ impl QuickSummary {
         }
     }
 
-    pub fn last_timestamp(&self) -> &TimeStamp {
-        match &self.last {
-            LastValue::Value(last) => &last.timestamp,
-            LastValue::Text(last) => &last.timestamp,
-            LastValue::Profile(last) => &last.timestamp,
+    pub fn new_vector(count: usize, last: Observation<Vector>) -> Self {
+        QuickSummary {
+            count,
+            last: LastValue::Vector(last),
         }
     }
 
-    pub fn last_value(&self) -> String {
-        match &self.last {
-            LastValue::Value(last) => last.value.value.to_string(),
-            LastValue::Text(last) => last.value.text.clone(),
-            LastValue::Profile(last) => last.value.to_string(),
+    pub fn new_event(count: usize, last: Observation<Event>) -> Self {
+        QuickSummary {
+            count,
+            last: LastValue::Event(last),
         }
     }
+
+    pub fn new_log(count: usize, last: Observation<LogRecord>) -> Self {
+        QuickSummary {
+            count,
+            last: LastValue::Log(last),
+        }
+    }
+
+    pub fn last_timestamp(&self) -> &TimeStamp {
+        self.last.timestamp()
+    }
+
+    pub fn last_value(&self) -> String {
+        self.last.to_string()
+    }
 }
 
+/// An observation of any kind of trace.
 #[derive(Debug, Clone)]
 pub enum LastValue {
     Value(Observation<Sample>),
     Text(Observation<Text>),
     Profile(Observation<ProfileEvent>),
+    Vector(Observation<Vector>),
+    Event(Observation<Event>),
+    Log(Observation<LogRecord>),
+}
+
+impl LastValue {
+    pub fn timestamp(&self) -> &TimeStamp {
+        match self {
+            LastValue::Value(last) => &last.timestamp,
+            LastValue::Text(last) => &last.timestamp,
+            LastValue::Profile(last) => &last.timestamp,
+            LastValue::Vector(last) => &last.timestamp,
+            LastValue::Event(last) => &last.timestamp,
+            LastValue::Log(last) => &last.timestamp,
+        }
+    }
+}
+
+impl fmt::Display for LastValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LastValue::Value(last) => write!(f, "{}", last.value.value),
+            LastValue::Text(last) => write!(f, "{}", last.value.text),
+            LastValue::Profile(last) => write!(f, "{}", last.value),
+            LastValue::Vector(last) => write!(f, "{:?}", last.value.values),
+            LastValue::Event(last) => write!(f, "{}", last.value),
+            LastValue::Log(last) => write!(f, "{}", last.value),
+        }
+    }
 }
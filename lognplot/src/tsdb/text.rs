@@ -1,6 +1,7 @@
 use super::CountMetrics;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Text {
     pub text: String,
 }
@@ -4,49 +4,170 @@
 //! or leaf chunks, with real data.
 //! Also: keep track of certain metrics, such as min, max and sum.
 
-use super::{Aggregation, Btree, Metrics, Observation, Query, RangeQueryResult};
-use crate::time::TimeSpan;
+use super::btree::{Fanout, NodeImage, RangeVisitor};
+use super::heap_size::HeapSize;
+use super::resample::resample;
+use super::retention::RetentionPolicy;
+use super::{Aggregation, Btree, Compress, Metrics, Observation, Query, RangeQueryResult};
+use super::{Bins, Histogram, QuantileSketch, Sample, SampleMetrics};
+use super::{Grid, Interpolate, Interpolation, LookupMode};
+use crate::time::{Duration, TimeSpan, TimeStamp};
 
 /// A trace is a single signal with a history in time.
-#[derive(Debug)]
+///
+/// Cloning a trace is cheap, since the clone shares the nodes of the
+/// tree with the original, until either of them changes.
+#[derive(Clone, Debug)]
 pub struct Trace<V, M>
 where
     M: Metrics<V> + From<V>,
 {
     tree: Btree<V, M>,
     count: usize,
+
+    /// The number of observations still in memory, the others
+    /// were dropped due to a retention policy.
+    retained: usize,
     last: Option<Observation<V>>,
 }
 
 impl<V, M> Trace<V, M>
 where
-    V: Clone,
-    M: Metrics<V> + From<V> + Clone,
+    V: Clone + Compress + HeapSize,
+    M: Metrics<V> + From<V> + Clone + HeapSize,
 {
+    /// Create an empty trace, with the given fanout of its tree.
+    pub fn with_fanout(fanout: Fanout) -> Self {
+        Self {
+            tree: Btree::new(fanout),
+            count: 0,
+            retained: 0,
+            last: None,
+        }
+    }
+
+    pub fn fanout(&self) -> Fanout {
+        self.tree.fanout()
+    }
+
     /// Add a vector of values to this trace.
+    ///
+    /// A batch which is sorted by time, and not older than the last
+    /// observation, is appended to the tree at once.
     pub fn add_observations(&mut self, observations: Vec<Observation<V>>) {
-        if !observations.is_empty() {
+        let sorted = observations
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp);
+        let in_order = match (&self.last, observations.first()) {
+            (Some(last), Some(first)) => first.timestamp >= last.timestamp,
+            _ => true,
+        };
+
+        if sorted && in_order {
+            if let Some(last) = observations.last() {
+                self.last = Some(last.clone());
+            }
             self.count += observations.len();
-            self.last = Some(
-                observations
-                    .last()
-                    .expect("At least a single sample.")
-                    .clone(),
-            );
+            self.retained += observations.len();
             self.tree.append_samples(observations);
+        } else {
+            for observation in observations {
+                self.add_observation(observation);
+            }
         }
     }
 
     /// Add a single observation.
+    ///
+    /// Observations older than the last observation are inserted
+    /// at the proper place in the tree.
     pub fn add_observation(&mut self, observation: Observation<V>) {
         self.count += 1;
-        self.last = Some(observation.clone());
-        self.tree.append_sample(observation);
+        let in_order = self
+            .last
+            .as_ref()
+            .map_or(true, |last| observation.timestamp >= last.timestamp);
+        if in_order {
+            self.retained += 1;
+            self.last = Some(observation.clone());
+            self.tree.append_sample(observation);
+        } else if self.tree.insert_sample(observation) {
+            self.retained += 1;
+        }
     }
 
     /// Query this trace for some data.
-    pub fn query(&self, query: Query) -> RangeQueryResult<V, M> {
-        self.tree.query_range(&query.interval, query.amount)
+    ///
+    /// When the query has a grid, the observations are resampled onto it.
+    pub fn query(&self, query: Query) -> RangeQueryResult<V, M>
+    where
+        V: Interpolate,
+    {
+        if let Some(grid) = &query.grid {
+            RangeQueryResult::Observations(self.resample(grid, query.interpolation))
+        } else {
+            self.tree.query_range(&query.interval, query.amount)
+        }
+    }
+
+    /// Resample this trace onto a regular grid.
+    ///
+    /// The observations right before and after the grid are used
+    /// as well, to determine the values at the edges of the grid.
+    pub fn resample(&self, grid: &Grid, interpolation: Interpolation) -> Vec<Observation<V>>
+    where
+        V: Interpolate,
+    {
+        let timespan = grid.timespan();
+        let mut observations = vec![];
+        observations.extend(self.tree.last_until(&timespan.start));
+        if let Some(first) = self.tree.first_retained_timestamp() {
+            let start = std::cmp::max(first, timespan.start.clone());
+            if let RangeQueryResult::Observations(in_range) = self
+                .tree
+                .query_range(&TimeSpan::new(start, timespan.end.clone()), usize::MAX)
+            {
+                observations.extend(in_range);
+            }
+        }
+        observations.extend(self.tree.first_from(&timespan.end));
+        resample(&observations, grid, interpolation)
+    }
+
+    /// Get the value of this trace at the given time.
+    pub fn value_at(&self, timestamp: &TimeStamp, mode: LookupMode) -> Option<Observation<V>>
+    where
+        V: Interpolate,
+    {
+        let before = self.tree.last_until(timestamp);
+        let after = || self.tree.first_from(timestamp);
+        let observation = match mode {
+            LookupMode::Previous => before?,
+            LookupMode::Next => after()?,
+            LookupMode::Nearest => match (before, after()) {
+                (Some(before), Some(after)) => {
+                    if timestamp - &before.timestamp <= &after.timestamp - timestamp {
+                        before
+                    } else {
+                        after
+                    }
+                }
+                (before, after) => before.or(after)?,
+            },
+            LookupMode::Linear => {
+                let before = before?;
+                if before.timestamp == *timestamp {
+                    before
+                } else {
+                    let after = after()?;
+                    let fraction = (timestamp - &before.timestamp).as_secs_f64()
+                        / (&after.timestamp - &before.timestamp).as_secs_f64();
+                    let value = before.value.interpolate(&after.value, fraction);
+                    Observation::new(timestamp.clone(), value)
+                }
+            }
+        };
+        Some(observation)
     }
 
     pub fn quick_summary(&self) -> Option<(usize, Observation<V>)> {
@@ -68,20 +189,173 @@ where
     pub fn to_vec(&self) -> Vec<Observation<V>> {
         self.tree.to_vec()
     }
+
+    /// Get the retained observations at or after the given time.
+    pub fn observations_from(&self, timestamp: &TimeStamp) -> Vec<Observation<V>> {
+        let mut observations = Observations(vec![]);
+        if let Some(last) = &self.last {
+            let timespan = TimeSpan::new(timestamp.clone(), last.timestamp.clone());
+            self.tree.visit_range(&timespan, &mut observations);
+        }
+        observations.0
+    }
+
+    /// The number of observations still in memory.
+    pub fn retained(&self) -> usize {
+        self.retained
+    }
+
+    /// The amount of memory used by this trace.
+    ///
+    /// This includes the data of the observations on the heap, the
+    /// nodes of the tree and the metrics kept for dropped observations.
+    pub fn retained_bytes(&self) -> usize {
+        self.tree.memory()
+    }
+
+    /// Get the start of the oldest data which can still be dropped.
+    pub fn oldest_retained(&self) -> Option<TimeStamp> {
+        self.tree.first_leaf_metrics().map(|m| m.timespan.start)
+    }
+
+    /// Get the time of the oldest observation which was not dropped.
+    pub fn first_retained(&self) -> Option<TimeStamp> {
+        self.tree.first_retained_timestamp()
+    }
+
+    /// Drop the oldest chunk of observations, keeping only its metrics.
+    ///
+    /// Returns the amount of observations dropped.
+    pub fn drop_oldest(&mut self) -> usize {
+        let dropped = self.tree.prune_first_leaf();
+        self.retained -= dropped;
+        dropped
+    }
+
+    /// Drop old data until this trace fits the given policy.
+    ///
+    /// Returns the amount of observations dropped.
+    pub fn apply_retention(&mut self, policy: &RetentionPolicy) -> usize {
+        let horizon = match (policy.max_age, &self.last) {
+            (Some(max_age), Some(last)) => {
+                Some(last.timestamp.clone() - Duration::from_secs_f64(max_age))
+            }
+            _ => None,
+        };
+
+        let mut dropped = 0;
+        while let Some(metrics) = self.tree.first_leaf_metrics() {
+            let too_many = policy
+                .max_samples
+                .map_or(false, |limit| self.retained > limit)
+                || policy
+                    .max_bytes
+                    .map_or(false, |limit| self.retained_bytes() > limit);
+            let too_old = horizon
+                .as_ref()
+                .map_or(false, |horizon| metrics.timespan.end < *horizon);
+            if too_many || too_old {
+                dropped += self.drop_oldest();
+            } else {
+                break;
+            }
+        }
+        dropped
+    }
+
+    /// Take an image of the tree, to be stored next to the observations.
+    pub fn image(&self) -> NodeImage<V, M> {
+        self.tree.image()
+    }
+
+    /// Restore a trace from previously stored observations.
+    ///
+    /// The observations are given in order of arrival. When an image of
+    /// the tree is given, and it matches the observations and the fanout,
+    /// the stored metrics are used instead of re-calculating them.
+    pub fn restore(
+        mut observations: Vec<Observation<V>>,
+        image: Option<NodeImage<V, M>>,
+        fanout: Fanout,
+    ) -> Self {
+        let mut trace = Self::with_fanout(fanout);
+
+        if let Some(image) = image.filter(|i| i.len() <= observations.len()) {
+            // The image refers to the observations which arrived before it
+            // was taken, ordered by time:
+            let size = image.len();
+            let pruned = image.pruned_len();
+            let mut head = observations[..size].to_vec();
+            head.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+            let last = head.last().cloned();
+
+            if let Some(tree) = Btree::from_image(image, &mut head.into_iter(), fanout) {
+                trace.tree = tree;
+                trace.count = size;
+                trace.retained = size - pruned;
+                trace.last = last;
+                observations.drain(..size);
+            }
+        }
+
+        // Add observations which were stored after the image was taken:
+        trace.add_observations(observations);
+        trace
+    }
 }
 
 impl<V, M> Default for Trace<V, M>
 where
-    V: Clone,
-    M: Metrics<V> + From<V> + Clone,
+    V: Clone + Compress + HeapSize,
+    M: Metrics<V> + From<V> + Clone + HeapSize,
 {
     fn default() -> Self {
-        let tree = Default::default();
+        Self::with_fanout(Fanout::default())
+    }
+}
 
-        Self {
-            tree,
-            count: 0,
-            last: None,
-        }
+impl Trace<Sample, SampleMetrics> {
+    /// Count how the values in the given time span are distributed.
+    ///
+    /// Returns None when the bins are invalid, or when bins of equal
+    /// width are requested but there are no values in the time span.
+    pub fn histogram(&self, timespan: &TimeSpan, bins: &Bins) -> Option<Histogram> {
+        let mut histogram = match bins {
+            Bins::Count(count) => {
+                let summary = self.tree.range_summary(timespan)?;
+                let metrics = summary.metrics();
+                Histogram::with_equal_bins(metrics.min, metrics.max, *count)?
+            }
+            Bins::Edges(edges) => Histogram::new(edges.clone())?,
+        };
+        self.tree.visit_range(timespan, &mut histogram);
+        Some(histogram)
+    }
+
+    /// Sketch the distribution of the values in the given time span,
+    /// to estimate quantiles.
+    pub fn quantile_sketch(&self, timespan: &TimeSpan) -> QuantileSketch {
+        let mut sketch = QuantileSketch::default();
+        self.tree.visit_range(timespan, &mut sketch);
+        sketch
+    }
+}
+
+/// Collects the retained observations in a time span.
+struct Observations<V>(Vec<Observation<V>>);
+
+impl<V, M> RangeVisitor<V, M> for Observations<V>
+where
+    V: Clone,
+    M: Metrics<V> + From<V>,
+{
+    fn aggregation(&mut self, _aggregation: &Aggregation<V, M>) -> bool {
+        false
+    }
+
+    fn pruned(&mut self, _aggregation: &Aggregation<V, M>) {}
+
+    fn observation(&mut self, observation: &Observation<V>) {
+        self.0.push(observation.clone());
     }
 }
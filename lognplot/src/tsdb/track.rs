@@ -1,23 +1,57 @@
+use super::btree::Fanout;
+use super::retention::RetentionPolicy;
 use super::trace::Trace;
 use super::Observation;
 use super::TrackType;
-use super::{CountMetrics, ProfileEvent, Text};
-use super::{Query, QueryResult, QuickSummary, Sample, SampleMetrics, Summary};
-use crate::time::TimeSpan;
+use super::{Bins, Histogram, LogMetrics, LogRecord, LookupMode, QuantileSketch};
+use super::{CallSpan, CallTree, CountMetrics, HeapSize, ProfileEvent, Text};
+use super::{Event, EventMetrics, Vector, VectorMetrics};
+use super::{LastValue, Query, QueryResult, QuickSummary, RangeQueryResult};
+use super::{Sample, SampleMetrics, Summary};
+use crate::time::{Duration, TimeSpan, TimeStamp};
+use std::sync::{Arc, RwLock};
 
-#[derive(Debug)]
+/// A track which can be shared between threads.
+///
+/// Each track has its own lock, so that a track can be queried
+/// while observations are added to other tracks.
+pub type SharedTrack = Arc<RwLock<Track>>;
+
+#[derive(Clone, Debug)]
 pub enum Track {
     Value(Trace<Sample, SampleMetrics>),
     Text(Trace<Text, CountMetrics>),
-    Profile(Trace<ProfileEvent, CountMetrics>),
+
+    /// A trace of profile events, with the calls made by them.
+    Profile(Trace<ProfileEvent, CountMetrics>, CallTree),
+
+    /// A trace of vectors, with the names of the vector components.
+    Vector(Trace<Vector, VectorMetrics>, Vec<String>),
+    Event(Trace<Event, EventMetrics>),
+    Log(Trace<LogRecord, LogMetrics>),
 }
 
 impl Track {
     pub fn new_with_type(typ: TrackType) -> Self {
+        Track::with_fanout(typ, vec![], Fanout::default())
+    }
+
+    /// Create a new vector track with the given component names.
+    pub fn new_vector(components: Vec<String>) -> Self {
+        Track::with_fanout(TrackType::Vector, components, Fanout::default())
+    }
+
+    /// Create a new track with the given fanout of its tree.
+    ///
+    /// The component names are only used by vector tracks.
+    pub fn with_fanout(typ: TrackType, components: Vec<String>, fanout: Fanout) -> Self {
         match typ {
-            TrackType::Value => Track::Value(Default::default()),
-            TrackType::Text => Track::Text(Default::default()),
-            TrackType::Profile => Track::Profile(Default::default()),
+            TrackType::Value => Track::Value(Trace::with_fanout(fanout)),
+            TrackType::Text => Track::Text(Trace::with_fanout(fanout)),
+            TrackType::Profile => Track::Profile(Trace::with_fanout(fanout), CallTree::default()),
+            TrackType::Vector => Track::Vector(Trace::with_fanout(fanout), components),
+            TrackType::Event => Track::Event(Trace::with_fanout(fanout)),
+            TrackType::Log => Track::Log(Trace::with_fanout(fanout)),
         }
     }
 
@@ -26,6 +60,32 @@ impl Track {
             Track::Value(..) => TrackType::Value,
             Track::Text(..) => TrackType::Text,
             Track::Profile(..) => TrackType::Profile,
+            Track::Vector(..) => TrackType::Vector,
+            Track::Event(..) => TrackType::Event,
+            Track::Log(..) => TrackType::Log,
+        }
+    }
+
+    /// The fanout of the tree of this track.
+    pub fn fanout(&self) -> Fanout {
+        match self {
+            Track::Value(trace) => trace.fanout(),
+            Track::Text(trace) => trace.fanout(),
+            Track::Profile(trace, _) => trace.fanout(),
+            Track::Vector(trace, _) => trace.fanout(),
+            Track::Event(trace) => trace.fanout(),
+            Track::Log(trace) => trace.fanout(),
+        }
+    }
+
+    /// The names of the components of a vector track.
+    ///
+    /// Other tracks have no components.
+    pub fn components(&self) -> &[String] {
+        if let Track::Vector(_, components) = self {
+            components
+        } else {
+            &[]
         }
     }
 
@@ -53,19 +113,183 @@ impl Track {
         }
     }
 
+    pub fn add_text_observations(&mut self, observations: Vec<Observation<Text>>) {
+        for observation in observations {
+            self.add_text_observation(observation);
+        }
+    }
+
     pub fn add_profile_observation(&mut self, observation: Observation<ProfileEvent>) {
-        if let Track::Profile(trace) = self {
-            trace.add_observation(observation)
+        if let Track::Profile(trace, calls) = self {
+            let late = calls
+                .last()
+                .map_or(false, |last| observation.timestamp < *last);
+            if late {
+                // Replay the events from the late one on, so that it ends
+                // up in the right call. Events which fall into dropped data
+                // are left out, since the calls around them are gone.
+                let timestamp = observation.timestamp.clone();
+                trace.add_observation(observation);
+                let retained = trace
+                    .first_retained()
+                    .map_or(false, |first| first <= timestamp);
+                if retained {
+                    calls.rewind(&timestamp);
+                    for observation in trace.observations_from(&timestamp) {
+                        calls.add_observation(observation);
+                    }
+                }
+            } else {
+                calls.add_observation(observation.clone());
+                trace.add_observation(observation);
+            }
         } else {
             panic!("Cannot add profile observation to non-profile track")
         }
     }
 
+    pub fn add_profile_observations(&mut self, observations: Vec<Observation<ProfileEvent>>) {
+        for observation in observations {
+            self.add_profile_observation(observation);
+        }
+    }
+
+    pub fn add_vector_observations(&mut self, observations: Vec<Observation<Vector>>) {
+        if let Track::Vector(trace, _) = self {
+            trace.add_observations(observations)
+        } else {
+            panic!("Cannot add vector observations to non-vector track")
+        }
+    }
+
+    pub fn add_event_observation(&mut self, observation: Observation<Event>) {
+        if let Track::Event(trace) = self {
+            trace.add_observation(observation)
+        } else {
+            panic!("Cannot add event observation to non-event track")
+        }
+    }
+
+    pub fn add_event_observations(&mut self, observations: Vec<Observation<Event>>) {
+        for observation in observations {
+            self.add_event_observation(observation);
+        }
+    }
+
+    pub fn add_log_observation(&mut self, observation: Observation<LogRecord>) {
+        if let Track::Log(trace) = self {
+            trace.add_observation(observation)
+        } else {
+            panic!("Cannot add log observation to non-log track")
+        }
+    }
+
+    pub fn add_log_observations(&mut self, observations: Vec<Observation<LogRecord>>) {
+        for observation in observations {
+            self.add_log_observation(observation);
+        }
+    }
+
     pub fn query(&self, query: Query) -> QueryResult {
         match self {
             Track::Value(trace) => QueryResult::Value(trace.query(query)),
             Track::Text(trace) => QueryResult::Text(trace.query(query)),
-            Track::Profile(trace) => QueryResult::Profile(trace.query(query)),
+            Track::Profile(trace, _) => QueryResult::Profile(trace.query(query)),
+            Track::Vector(trace, _) => QueryResult::Vector(trace.query(query)),
+            Track::Event(trace) => QueryResult::Event(trace.query(query)),
+            Track::Log(trace) => QueryResult::Log(trace.query(query)),
+        }
+    }
+
+    /// Query a text track, or None for other tracks.
+    pub fn query_text(&self, query: Query) -> Option<RangeQueryResult<Text, CountMetrics>> {
+        if let Track::Text(trace) = self {
+            Some(trace.query(query))
+        } else {
+            None
+        }
+    }
+
+    /// Query a profile track, or None for other tracks.
+    pub fn query_profile(
+        &self,
+        query: Query,
+    ) -> Option<RangeQueryResult<ProfileEvent, CountMetrics>> {
+        if let Track::Profile(trace, _) = self {
+            Some(trace.query(query))
+        } else {
+            None
+        }
+    }
+
+    /// Get the calls of a profile track, which overlap the given timespan.
+    ///
+    /// Calls which are too short to be seen when showing the
+    /// timespan with the given amount of points are merged.
+    ///
+    /// The calls are kept up to date while events are added, so
+    /// querying them does not go through all events again.
+    pub fn query_calls(&self, timespan: &TimeSpan, amount: usize) -> Option<Vec<CallSpan>> {
+        if let Track::Profile(_, calls) = self {
+            let duration = &timespan.end - &timespan.start;
+            let resolution = Duration::from_nanos(duration.as_nanos() / amount.max(1) as i64);
+            Some(calls.query(timespan, resolution))
+        } else {
+            None
+        }
+    }
+
+    /// Get the value of a value track at the given time.
+    ///
+    /// Returns None for other tracks, or when there is no such value.
+    pub fn value_at(&self, timestamp: &TimeStamp, mode: LookupMode) -> Option<Observation<Sample>> {
+        if let Track::Value(trace) = self {
+            trace.value_at(timestamp, mode)
+        } else {
+            None
+        }
+    }
+
+    /// Get the observation of any track at the given time.
+    ///
+    /// Observations which cannot be interpolated, such as texts,
+    /// hold the previous value when linear interpolation is asked for.
+    pub fn observation_at(&self, timestamp: &TimeStamp, mode: LookupMode) -> Option<LastValue> {
+        let hold_mode = if mode == LookupMode::Linear {
+            LookupMode::Previous
+        } else {
+            mode
+        };
+        let value = match self {
+            Track::Value(trace) => LastValue::Value(trace.value_at(timestamp, mode)?),
+            Track::Vector(trace, _) => LastValue::Vector(trace.value_at(timestamp, mode)?),
+            Track::Text(trace) => LastValue::Text(trace.value_at(timestamp, hold_mode)?),
+            Track::Profile(trace, _) => LastValue::Profile(trace.value_at(timestamp, hold_mode)?),
+            Track::Event(trace) => LastValue::Event(trace.value_at(timestamp, hold_mode)?),
+            Track::Log(trace) => LastValue::Log(trace.value_at(timestamp, hold_mode)?),
+        };
+        Some(value)
+    }
+
+    /// Count how the values of a value track are distributed.
+    ///
+    /// Returns None for other tracks.
+    pub fn histogram(&self, timespan: &TimeSpan, bins: &Bins) -> Option<Histogram> {
+        if let Track::Value(trace) = self {
+            trace.histogram(timespan, bins)
+        } else {
+            None
+        }
+    }
+
+    /// Sketch the distribution of the values of a value track.
+    ///
+    /// Returns None for other tracks.
+    pub fn quantile_sketch(&self, timespan: &TimeSpan) -> Option<QuantileSketch> {
+        if let Track::Value(trace) = self {
+            Some(trace.quantile_sketch(timespan))
+        } else {
+            None
         }
     }
 
@@ -79,10 +303,22 @@ impl Track {
                 let (count, last) = trace.quick_summary()?;
                 Some(QuickSummary::new_text(count, last))
             }
-            Track::Profile(trace) => {
+            Track::Profile(trace, _) => {
                 let (count, last) = trace.quick_summary()?;
                 Some(QuickSummary::new_profile(count, last))
             }
+            Track::Vector(trace, _) => {
+                let (count, last) = trace.quick_summary()?;
+                Some(QuickSummary::new_vector(count, last))
+            }
+            Track::Event(trace) => {
+                let (count, last) = trace.quick_summary()?;
+                Some(QuickSummary::new_event(count, last))
+            }
+            Track::Log(trace) => {
+                let (count, last) = trace.quick_summary()?;
+                Some(QuickSummary::new_log(count, last))
+            }
         }
     }
 
@@ -90,7 +326,75 @@ impl Track {
         match self {
             Track::Value(trace) => Some(Summary::Value(trace.summary(timespan)?)),
             Track::Text(trace) => Some(Summary::Text(trace.summary(timespan)?)),
-            Track::Profile(trace) => Some(Summary::Profile(trace.summary(timespan)?)),
+            Track::Profile(trace, _) => Some(Summary::Profile(trace.summary(timespan)?)),
+            Track::Vector(trace, _) => Some(Summary::Vector(trace.summary(timespan)?)),
+            Track::Event(trace) => Some(Summary::Event(trace.summary(timespan)?)),
+            Track::Log(trace) => Some(Summary::Log(trace.summary(timespan)?)),
+        }
+    }
+
+    /// The number of observations in memory.
+    pub fn retained(&self) -> usize {
+        match self {
+            Track::Value(trace) => trace.retained(),
+            Track::Text(trace) => trace.retained(),
+            Track::Profile(trace, _) => trace.retained(),
+            Track::Vector(trace, _) => trace.retained(),
+            Track::Event(trace) => trace.retained(),
+            Track::Log(trace) => trace.retained(),
+        }
+    }
+
+    /// The amount of memory used by observations in this track.
+    pub fn retained_bytes(&self) -> usize {
+        match self {
+            Track::Value(trace) => trace.retained_bytes(),
+            Track::Text(trace) => trace.retained_bytes(),
+            Track::Profile(trace, calls) => trace.retained_bytes() + calls.heap_size(),
+            Track::Vector(trace, _) => trace.retained_bytes(),
+            Track::Event(trace) => trace.retained_bytes(),
+            Track::Log(trace) => trace.retained_bytes(),
+        }
+    }
+
+    pub fn oldest_retained(&self) -> Option<TimeStamp> {
+        match self {
+            Track::Value(trace) => trace.oldest_retained(),
+            Track::Text(trace) => trace.oldest_retained(),
+            Track::Profile(trace, _) => trace.oldest_retained(),
+            Track::Vector(trace, _) => trace.oldest_retained(),
+            Track::Event(trace) => trace.oldest_retained(),
+            Track::Log(trace) => trace.oldest_retained(),
+        }
+    }
+
+    pub fn drop_oldest(&mut self) -> usize {
+        match self {
+            Track::Value(trace) => trace.drop_oldest(),
+            Track::Text(trace) => trace.drop_oldest(),
+            Track::Profile(trace, calls) => {
+                let dropped = trace.drop_oldest();
+                calls.trim(trace.first_retained().as_ref());
+                dropped
+            }
+            Track::Vector(trace, _) => trace.drop_oldest(),
+            Track::Event(trace) => trace.drop_oldest(),
+            Track::Log(trace) => trace.drop_oldest(),
+        }
+    }
+
+    pub fn apply_retention(&mut self, policy: &RetentionPolicy) -> usize {
+        match self {
+            Track::Value(trace) => trace.apply_retention(policy),
+            Track::Text(trace) => trace.apply_retention(policy),
+            Track::Profile(trace, calls) => {
+                let dropped = trace.apply_retention(policy);
+                calls.trim(trace.first_retained().as_ref());
+                dropped
+            }
+            Track::Vector(trace, _) => trace.apply_retention(policy),
+            Track::Event(trace) => trace.apply_retention(policy),
+            Track::Log(trace) => trace.apply_retention(policy),
         }
     }
 
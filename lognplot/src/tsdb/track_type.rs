@@ -1,6 +1,11 @@
-#[derive(PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum TrackType {
     Value,
     Text,
     Profile,
+    Vector,
+    Event,
+    Log,
 }
@@ -0,0 +1,393 @@
+//! Triggers, which fire on conditions in signals, like on an oscilloscope.
+//!
+//! A trigger watches a single signal while data is added to the
+//! database. When its condition is met, a capture starts, spanning
+//! some time before and after the moment of triggering. The trigger
+//! event is reported once data up to the end of the capture arrived,
+//! on the watched signal or on any other signal.
+//! After that, and after an optional holdoff time, the trigger is
+//! armed again.
+
+use super::{Event, LogRecord, Observation, ProfileEvent, Sample, Text, Vector};
+use crate::time::{Duration, TimeSpan, TimeStamp};
+
+/// Condition on which a trigger fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerCondition {
+    /// The value rises through the level.
+    RisingEdge(f64),
+
+    /// The value falls through the level.
+    FallingEdge(f64),
+
+    /// The value enters the range between the low and high level.
+    EnterWindow { low: f64, high: f64 },
+
+    /// The value leaves the range between the low and high level.
+    LeaveWindow { low: f64, high: f64 },
+
+    /// A pulse above the level ends, and lasted between the minimum
+    /// and maximum width in seconds.
+    PulseWidth {
+        level: f64,
+        min_width: Option<f64>,
+        max_width: Option<f64>,
+    },
+
+    /// A text contains the given pattern.
+    TextMatch(String),
+}
+
+/// Definition of a trigger on a signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    /// Name of the signal to watch.
+    pub signal: String,
+
+    pub condition: TriggerCondition,
+
+    /// Seconds of data to capture before the moment of triggering.
+    pub pre_trigger: f64,
+
+    /// Seconds of data to capture after the moment of triggering.
+    pub post_trigger: f64,
+
+    /// Seconds to wait after a capture before the trigger is armed again.
+    pub holdoff: f64,
+}
+
+impl Trigger {
+    pub fn new(signal: &str, condition: TriggerCondition) -> Self {
+        Trigger {
+            signal: signal.to_owned(),
+            condition,
+            pre_trigger: 0.0,
+            post_trigger: 0.0,
+            holdoff: 0.0,
+        }
+    }
+
+    pub fn pre_trigger(mut self, seconds: f64) -> Self {
+        self.pre_trigger = seconds;
+        self
+    }
+
+    pub fn post_trigger(mut self, seconds: f64) -> Self {
+        self.post_trigger = seconds;
+        self
+    }
+
+    pub fn holdoff(mut self, seconds: f64) -> Self {
+        self.holdoff = seconds;
+        self
+    }
+}
+
+/// A single firing of a trigger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerEvent {
+    /// Name of the trigger which fired.
+    pub trigger: String,
+
+    /// Moment at which the condition was met.
+    pub timestamp: TimeStamp,
+
+    /// Captured time span, around the moment of triggering.
+    pub timespan: TimeSpan,
+}
+
+/// Values which triggers can look at.
+///
+/// Types of which the values cannot trigger anything can use
+/// the default implementation.
+pub trait TriggerInput {
+    /// Level to compare against edges, windows and pulses.
+    fn level(&self) -> Option<f64> {
+        None
+    }
+
+    /// Text to match against patterns.
+    fn text(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl TriggerInput for Sample {
+    fn level(&self) -> Option<f64> {
+        Some(self.value)
+    }
+}
+
+impl TriggerInput for Text {
+    fn text(&self) -> Option<&str> {
+        Some(&self.text)
+    }
+}
+
+impl TriggerInput for LogRecord {
+    fn text(&self) -> Option<&str> {
+        Some(&self.message)
+    }
+}
+
+impl TriggerInput for Vector {}
+impl TriggerInput for ProfileEvent {}
+impl TriggerInput for Event {}
+
+/// A trigger, with the state required to evaluate it on new data.
+#[derive(Debug)]
+pub struct ArmedTrigger {
+    trigger: Trigger,
+
+    /// Last level seen, to detect edges.
+    previous: Option<f64>,
+
+    /// Time at which the current pulse started.
+    pulse_start: Option<TimeStamp>,
+
+    /// Capture waiting for the data after the trigger moment.
+    capture: Option<TriggerEvent>,
+
+    /// Time from which the trigger may fire again.
+    armed_from: Option<TimeStamp>,
+
+    /// Time of the last observation seen.
+    last: Option<TimeStamp>,
+}
+
+impl ArmedTrigger {
+    pub fn new(trigger: Trigger) -> Self {
+        ArmedTrigger {
+            trigger,
+            previous: None,
+            pulse_start: None,
+            capture: None,
+            armed_from: None,
+            last: None,
+        }
+    }
+
+    pub fn trigger(&self) -> &Trigger {
+        &self.trigger
+    }
+
+    /// Evaluate the trigger on new observations of its signal.
+    ///
+    /// Returns the captures which are complete.
+    pub fn process<V: TriggerInput>(
+        &mut self,
+        name: &str,
+        observations: &[Observation<V>],
+    ) -> Vec<TriggerEvent> {
+        let mut events = vec![];
+        for observation in observations {
+            let timestamp = &observation.timestamp;
+
+            // Observations from the past cannot trigger anything.
+            if self.last.as_ref().map_or(false, |last| timestamp < last) {
+                continue;
+            }
+            self.last = Some(timestamp.clone());
+
+            if let Some(event) = self.complete_capture(timestamp) {
+                events.push(event);
+            }
+
+            if self.condition_met(&observation.value, timestamp) && self.is_armed(timestamp) {
+                self.capture = Some(self.start_capture(name, timestamp));
+                if let Some(event) = self.complete_capture(timestamp) {
+                    events.push(event);
+                }
+            }
+        }
+        events
+    }
+
+    /// Let time pass, as seen on other signals than the one of the trigger.
+    ///
+    /// This completes a pending capture, also when its own signal
+    /// stops after triggering, like a signal with a single event.
+    pub fn advance(&mut self, timestamp: &TimeStamp) -> Option<TriggerEvent> {
+        self.complete_capture(timestamp)
+    }
+
+    fn is_armed(&self, timestamp: &TimeStamp) -> bool {
+        self.capture.is_none()
+            && self
+                .armed_from
+                .as_ref()
+                .map_or(true, |armed_from| timestamp >= armed_from)
+    }
+
+    fn start_capture(&self, name: &str, timestamp: &TimeStamp) -> TriggerEvent {
+        let start = timestamp.clone() - Duration::from_secs_f64(self.trigger.pre_trigger);
+        let end = timestamp.clone() + Duration::from_secs_f64(self.trigger.post_trigger);
+        TriggerEvent {
+            trigger: name.to_owned(),
+            timestamp: timestamp.clone(),
+            timespan: TimeSpan::new(start, end),
+        }
+    }
+
+    /// Finish the pending capture when data up to its end has arrived.
+    fn complete_capture(&mut self, timestamp: &TimeStamp) -> Option<TriggerEvent> {
+        if self.capture.as_ref()?.timespan.end <= *timestamp {
+            let event = self.capture.take().unwrap();
+            self.armed_from =
+                Some(event.timespan.end.clone() + Duration::from_secs_f64(self.trigger.holdoff));
+            Some(event)
+        } else {
+            None
+        }
+    }
+
+    /// Check the condition, and remember the state needed for the next value.
+    fn condition_met<V: TriggerInput>(&mut self, value: &V, timestamp: &TimeStamp) -> bool {
+        if let TriggerCondition::TextMatch(pattern) = &self.trigger.condition {
+            return value.text().map_or(false, |text| text.contains(pattern));
+        }
+
+        let level = match value.level() {
+            Some(level) if !level.is_nan() => level,
+            _ => return false,
+        };
+        let previous = self.previous.replace(level);
+
+        match &self.trigger.condition {
+            TriggerCondition::RisingEdge(threshold) => {
+                previous.map_or(false, |p| p < *threshold) && level >= *threshold
+            }
+            TriggerCondition::FallingEdge(threshold) => {
+                previous.map_or(false, |p| p > *threshold) && level <= *threshold
+            }
+            TriggerCondition::EnterWindow { low, high } => {
+                let inside = |v: f64| *low <= v && v <= *high;
+                previous.map_or(false, |p| !inside(p)) && inside(level)
+            }
+            TriggerCondition::LeaveWindow { low, high } => {
+                let inside = |v: f64| *low <= v && v <= *high;
+                previous.map_or(false, inside) && !inside(level)
+            }
+            TriggerCondition::PulseWidth {
+                level: threshold,
+                min_width,
+                max_width,
+            } => {
+                if level > *threshold {
+                    if previous.map_or(false, |p| p <= *threshold) {
+                        self.pulse_start = Some(timestamp.clone());
+                    }
+                    false
+                } else if let Some(start) = self.pulse_start.take() {
+                    let width = (timestamp - &start).as_secs_f64();
+                    min_width.map_or(true, |min| width >= min)
+                        && max_width.map_or(true, |max| width <= max)
+                } else {
+                    false
+                }
+            }
+            TriggerCondition::TextMatch(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArmedTrigger, Trigger, TriggerCondition};
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Observation, Sample, Text};
+
+    fn samples(values: &[f64]) -> Vec<Observation<Sample>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Observation::new(TimeStamp::new(i as f64), Sample::new(*v)))
+            .collect()
+    }
+
+    fn trigger_times(trigger: Trigger, values: &[f64]) -> Vec<f64> {
+        ArmedTrigger::new(trigger)
+            .process("t", &samples(values))
+            .into_iter()
+            .map(|e| e.timestamp.to_seconds())
+            .collect()
+    }
+
+    #[test]
+    fn edges() {
+        let values = [0.0, 2.0, 0.0, 1.0, 3.0, 0.0];
+        let rising = Trigger::new("x", TriggerCondition::RisingEdge(1.0));
+        assert_eq!(vec![1.0, 3.0], trigger_times(rising, &values));
+        let falling = Trigger::new("x", TriggerCondition::FallingEdge(1.0));
+        assert_eq!(vec![2.0, 5.0], trigger_times(falling, &values));
+    }
+
+    #[test]
+    fn windows() {
+        let values = [0.0, 2.0, 2.5, 5.0, 2.0];
+        let enter = TriggerCondition::EnterWindow {
+            low: 1.0,
+            high: 3.0,
+        };
+        assert_eq!(
+            vec![1.0, 4.0],
+            trigger_times(Trigger::new("x", enter), &values)
+        );
+        let leave = TriggerCondition::LeaveWindow {
+            low: 1.0,
+            high: 3.0,
+        };
+        assert_eq!(vec![3.0], trigger_times(Trigger::new("x", leave), &values));
+    }
+
+    #[test]
+    fn pulse_width() {
+        // Pulses of 1, 3 and 2 seconds:
+        let values = [0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+        let condition = TriggerCondition::PulseWidth {
+            level: 0.5,
+            min_width: Some(1.5),
+            max_width: Some(2.5),
+        };
+        assert_eq!(
+            vec![9.0],
+            trigger_times(Trigger::new("x", condition), &values)
+        );
+    }
+
+    #[test]
+    fn capture_and_holdoff() {
+        // Rising edges at 1, 3, 5, 7 and 9 seconds:
+        let values = [0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0];
+        let trigger = Trigger::new("x", TriggerCondition::RisingEdge(0.5))
+            .pre_trigger(0.5)
+            .post_trigger(1.0)
+            .holdoff(2.0);
+        let mut armed = ArmedTrigger::new(trigger);
+
+        // The capture is only complete after one more second of data:
+        let observations = samples(&values);
+        assert!(armed.process("t", &observations[..2]).is_empty());
+        let events = armed.process("t", &observations[2..]);
+
+        // Edges during a capture or the holdoff are ignored:
+        let times: Vec<f64> = events.iter().map(|e| e.timestamp.to_seconds()).collect();
+        assert_eq!(vec![1.0, 5.0, 9.0], times);
+        assert_eq!(0.5, events[0].timespan.start.to_seconds());
+        assert_eq!(2.0, events[0].timespan.end.to_seconds());
+        assert_eq!("t", events[0].trigger);
+    }
+
+    #[test]
+    fn text_match() {
+        let observations: Vec<Observation<Text>> = ["boot", "error: disk full", "ok"]
+            .iter()
+            .enumerate()
+            .map(|(i, t)| Observation::new(TimeStamp::new(i as f64), Text::new(t.to_string())))
+            .collect();
+        let trigger = Trigger::new("log", TriggerCondition::TextMatch("error".to_owned()));
+        let events = ArmedTrigger::new(trigger).process("t", &observations);
+        assert_eq!(1, events.len());
+        assert_eq!(1.0, events[0].timestamp.to_seconds());
+    }
+}
@@ -0,0 +1,193 @@
+//! Log samples of vector values.
+//!
+//! A vector value consists of several components, such as the
+//! three axis of an accelerometer, which are observed at the same time.
+
+use super::heap_size::HeapSize;
+use super::{Aggregation, Metrics, Observation, RangeQueryResult, Sample, SampleMetrics};
+use crate::time::TimeStamp;
+use serde::{Deserialize, Serialize};
+
+/// A vector of scalar values, sharing a single timestamp.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Vector {
+    pub values: Vec<f64>,
+}
+
+impl Vector {
+    pub fn new(values: Vec<f64>) -> Self {
+        Vector { values }
+    }
+
+    /// The number of components in this vector.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Get a single component of this vector as a scalar sample.
+    pub fn component(&self, index: usize) -> Option<Sample> {
+        self.values.get(index).map(|value| Sample::new(*value))
+    }
+}
+
+/// Metrics of each component of a vector trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorMetrics {
+    components: Vec<SampleMetrics>,
+}
+
+impl VectorMetrics {
+    /// The number of components in these metrics.
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Get the metrics of a single component.
+    pub fn component(&self, index: usize) -> Option<&SampleMetrics> {
+        self.components.get(index)
+    }
+}
+
+impl From<Vector> for VectorMetrics {
+    fn from(vector: Vector) -> Self {
+        let components = vector
+            .values
+            .into_iter()
+            .map(|value| SampleMetrics::from(Sample::new(value)))
+            .collect();
+        VectorMetrics { components }
+    }
+}
+
+impl HeapSize for VectorMetrics {
+    fn heap_size(&self) -> usize {
+        self.components.heap_size()
+    }
+}
+
+impl Metrics<Vector> for VectorMetrics {
+    /// Integrate a single vector into the metrics of each component.
+    ///
+    /// Components which were not seen before start with this value.
+    fn update(&mut self, vector: &Vector) {
+        for (index, value) in vector.values.iter().enumerate() {
+            let sample = Sample::new(*value);
+            if let Some(metrics) = self.components.get_mut(index) {
+                metrics.update(&sample);
+            } else {
+                self.components.push(SampleMetrics::from(sample));
+            }
+        }
+    }
+
+    fn update_at(&mut self, vector: &Vector, timestamp: &TimeStamp) {
+        for (index, value) in vector.values.iter().enumerate() {
+            let sample = Sample::new(*value);
+            if let Some(metrics) = self.components.get_mut(index) {
+                metrics.update_at(&sample, timestamp);
+            } else {
+                let mut metrics = SampleMetrics::from(sample);
+                metrics.created_at(timestamp);
+                self.components.push(metrics);
+            }
+        }
+    }
+
+    fn created_at(&mut self, timestamp: &TimeStamp) {
+        for metrics in &mut self.components {
+            metrics.created_at(timestamp);
+        }
+    }
+
+    fn include(&mut self, metrics: &VectorMetrics) {
+        for (index, other) in metrics.components.iter().enumerate() {
+            if let Some(metrics) = self.components.get_mut(index) {
+                metrics.include(other);
+            } else {
+                self.components.push(other.clone());
+            }
+        }
+    }
+}
+
+impl Aggregation<Vector, VectorMetrics> {
+    /// Get the aggregation of a single component.
+    pub fn component(&self, index: usize) -> Option<Aggregation<Sample, SampleMetrics>> {
+        let metrics = self.metrics().component(index)?.clone();
+        let count = metrics.count;
+        Some(Aggregation::new(self.timespan.clone(), metrics, count))
+    }
+}
+
+impl RangeQueryResult<Vector, VectorMetrics> {
+    /// The number of components in this result.
+    pub fn components(&self) -> usize {
+        match self {
+            RangeQueryResult::Observations(observations) => {
+                observations.iter().map(|o| o.value.len()).max()
+            }
+            RangeQueryResult::Aggregations(aggregations) => {
+                aggregations.iter().map(|a| a.metrics().len()).max()
+            }
+        }
+        .unwrap_or(0)
+    }
+
+    /// Select a single component from this result, as if it
+    /// were the result of a query on a scalar trace.
+    pub fn component(&self, index: usize) -> RangeQueryResult<Sample, SampleMetrics> {
+        match self {
+            RangeQueryResult::Observations(observations) => RangeQueryResult::Observations(
+                observations
+                    .iter()
+                    .filter_map(|o| {
+                        let sample = o.value.component(index)?;
+                        Some(Observation::new(o.timestamp.clone(), sample))
+                    })
+                    .collect(),
+            ),
+            RangeQueryResult::Aggregations(aggregations) => RangeQueryResult::Aggregations(
+                aggregations
+                    .iter()
+                    .filter_map(|a| a.component(index))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Vector, VectorMetrics};
+    use crate::tsdb::Metrics;
+
+    #[test]
+    fn metrics_per_component() {
+        let mut metrics = VectorMetrics::from(Vector::new(vec![1.0, -1.0]));
+        metrics.update(&Vector::new(vec![3.0, -3.0]));
+        metrics.update(&Vector::new(vec![2.0, -2.0, 7.0]));
+
+        assert_eq!(3, metrics.len());
+        let x = metrics.component(0).unwrap();
+        assert_eq!(1.0, x.min);
+        assert_eq!(3.0, x.max);
+        assert_eq!(2.0, x.mean());
+        let y = metrics.component(1).unwrap();
+        assert_eq!(-3.0, y.min);
+        assert_eq!(-1.0, y.max);
+        assert_eq!(3, y.count);
+        assert_eq!(1, metrics.component(2).unwrap().count);
+
+        metrics.include(&metrics.clone());
+        assert_eq!(6, metrics.component(0).unwrap().count);
+        assert_eq!(2.0, metrics.component(0).unwrap().mean());
+    }
+}
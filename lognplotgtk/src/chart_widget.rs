@@ -3,20 +3,23 @@
 use gtk::prelude::*;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::time::Instant;
 
-use crate::session::DashBoardItem;
+use crate::session::{CurveItem, DashBoardItem};
 use crate::state::GuiStateHandle;
 use crate::time_tracker::TimeTracker;
-use lognplot::chart::{Chart, Curve, CurveData};
+use lognplot::chart::{range_statistics_columns, Chart, Curve, CurveData};
 use lognplot::geometry::Size;
-use lognplot::render::{draw_chart, CairoCanvas, ChartLayout, ChartOptions};
+use lognplot::render::{draw_chart_with_data, ChartData, ChartDataRequest};
 use lognplot::render::{x_pixel_to_domain, x_pixels_to_domain, y_pixel_to_domain};
+use lognplot::render::{CairoCanvas, ChartLayout, ChartOptions};
+use lognplot::style::CATEGORY10_COLORS;
 use lognplot::time::{TimeSpan, TimeStamp};
 use lognplot::tracer::{AnyTracer, Tracer};
-use lognplot::tsdb::DataChangeEvent;
-use lognplot::tsdb::TsDbHandle;
+use lognplot::tsdb::{AsyncQuery, QuerySlot, Statistic, TsDbHandle};
+use lognplot::tsdb::{DataChangeEvent, Trigger, TriggerCondition, TriggerEvent};
 use std::sync::Arc;
 
 pub struct ChartState {
@@ -33,15 +36,40 @@ pub struct ChartState {
     draw_area: gtk::DrawingArea,
     id: String,
     time_estimator: TimeTracker,
+    trigger_mode: TriggerMode,
+
+    /// Latest query results, drawn until newer results arrive.
+    chart_data: Rc<RefCell<ChartData>>,
+
+    /// Pending query of each curve.
+    curve_queries: HashMap<String, QuerySlot>,
+
+    /// Pending query of each text track.
+    text_track_queries: HashMap<String, QuerySlot>,
+
+    cursor_query: QuerySlot,
+    range_query: QuerySlot,
+    units_query: QuerySlot,
+
+    /// What the latest queries were for, if still valid.
+    chart_data_request: Option<ChartDataRequest>,
+
+    /// Show the next statistics between the cursors in the table.
+    show_range_statistics: bool,
 }
 
-/// category10 color wheel
-///
-/// See also: https://matplotlib.org/users/dflt_style_changes.html#colors-in-default-property-cycle
-pub const CATEGORY10_COLORS: &[&str] = &[
-    "#1F77B4", "#FF7F0E", "#2CA02C", "#D62728", "#9467BD", "#8C564B", "#E377C2", "#7F7F7F",
-    "#BCBD22", "#17BECF",
-];
+/// What a chart does when its trigger fires.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TriggerMode {
+    Off,
+
+    /// Show the capture of each trigger event.
+    Align,
+
+    /// Show the capture of a trigger event, and pause this chart until
+    /// resumed. Other charts keep showing new data.
+    Freeze,
+}
 
 impl ChartState {
     pub fn new(
@@ -72,6 +100,15 @@ impl ChartState {
             draw_area,
             id: id.to_owned(),
             time_estimator: TimeTracker::new(perf_tracer, id),
+            trigger_mode: TriggerMode::Off,
+            chart_data: Rc::new(RefCell::new(ChartData::default())),
+            curve_queries: HashMap::new(),
+            text_track_queries: HashMap::new(),
+            cursor_query: QuerySlot::default(),
+            range_query: QuerySlot::default(),
+            units_query: QuerySlot::default(),
+            chart_data_request: None,
+            show_range_statistics: false,
         }
     }
 
@@ -83,15 +120,26 @@ impl ChartState {
         &self.id
     }
 
-    pub fn add_curve(&mut self, name: &str) {
-        // self.chart.add_curve(Curve::new());
-        if !self.chart.has_signal(name) {
-            let tsdb_data = CurveData::trace(name, self.db.clone());
+    /// Add a curve of a signal, or of a single component of a vector signal.
+    pub fn add_curve(&mut self, item: &CurveItem) {
+        let name = item.name();
+        let component = item.component();
+        let shown = self
+            .chart
+            .curves
+            .iter()
+            .any(|c| c.name() == name && c.data.component() == component);
+        if !shown {
+            let tsdb_data = match component {
+                Some(component) => CurveData::trace_component(name, component, self.db.clone()),
+                None => CurveData::trace(name, self.db.clone()),
+            };
             let color = self.next_color();
             let curve2 = Curve::new(tsdb_data, &color);
 
             self.chart.add_curve(curve2);
             self.chart.autoscale();
+            self.chart_data_request = None;
             self.repaint();
         } else {
             info!("Signal {} is already shown", name);
@@ -111,21 +159,62 @@ impl ChartState {
         debug!("Kill all signals!");
         self.disable_tailing();
         self.chart.clear_curves();
+        for slot in self
+            .curve_queries
+            .values_mut()
+            .chain(self.text_track_queries.values_mut())
+        {
+            slot.cancel();
+        }
+        self.curve_queries.clear();
+        self.text_track_queries.clear();
+        *self.chart_data.borrow_mut() = ChartData::default();
+        self.chart_data_request = None;
+        self.update_range_statistics();
         self.repaint();
     }
 
     pub fn set_cursor1(&mut self) {
         debug!("set cursor 1!");
         self.chart.set_cursor1();
+        self.update_range_statistics();
         self.repaint();
     }
 
     pub fn set_cursor2(&mut self) {
         debug!("set cursor 2!");
         self.chart.set_cursor2();
+        self.update_range_statistics();
         self.repaint();
     }
 
+    /// Select the statistics shown at the cursor and in the table.
+    pub fn set_statistics(&mut self, statistics: &[Statistic]) {
+        self.chart.statistics = statistics.to_vec();
+        if self.chart.cursor_timespan().is_some() {
+            self.update_range_statistics();
+        }
+        self.repaint();
+    }
+
+    /// Show the statistics between the cursors of this chart in the table,
+    /// once they are queried.
+    fn update_range_statistics(&mut self) {
+        self.show_range_statistics = true;
+    }
+
+    /// Remove the curve or text track of a signal, and its data.
+    fn remove_curve(&mut self, name: &str) {
+        self.chart.remove_curve(name);
+        if let Some(mut slot) = self.curve_queries.remove(name) {
+            slot.cancel();
+        }
+        if let Some(mut slot) = self.text_track_queries.remove(name) {
+            slot.cancel();
+        }
+        self.chart_data.borrow_mut().remove(name);
+    }
+
     pub fn get_session_item(&self) -> DashBoardItem {
         (&self.chart).into()
     }
@@ -141,18 +230,119 @@ impl ChartState {
 
     /// Handle data change event from database.
     pub fn handle_event(&mut self, event: &DataChangeEvent) {
-        // Check if we must update the chart:
-        let update = event.delete_all
-            || event
-                .changed_signals
-                .iter()
-                .any(|n| self.chart.has_signal(n));
-        if update {
+        // Drop curves of deleted signals:
+        let mut deleted = false;
+        for name in &event.deleted_signals {
+            if self.chart.has_signal(name) {
+                debug!("Signal {} was deleted, removing it from chart", name);
+                self.remove_curve(name);
+                deleted = true;
+            }
+        }
+
+        let changed = event
+            .changed_signals
+            .iter()
+            .any(|n| self.chart.has_signal(n));
+        if changed {
             if let Some(last_time) = self.chart.get_last_timestamp() {
-                self.time_estimator.update(last_time.amount);
+                self.time_estimator.update(last_time.to_seconds());
             }
+        }
+
+        // Only repaint when something changed in view, which is never
+        // the case for new data while paused:
+        let view = self.chart.x_axis.timespan();
+        let update = deleted
+            || event.delete_all
+            || (!self.chart.is_paused()
+                && event
+                    .changed_signals
+                    .iter()
+                    .any(|n| self.chart.has_signal(n) && event.changed_in(n, &view)));
+        if update {
+            self.chart_data_request = None;
             self.repaint();
         }
+
+        let trigger_name = self.trigger_name();
+        for trigger_event in &event.triggers {
+            if trigger_event.trigger == trigger_name {
+                self.on_trigger(trigger_event);
+            }
+        }
+    }
+
+    fn trigger_name(&self) -> String {
+        format!("{}.trigger", self.id)
+    }
+
+    /// Switch between the trigger modes: off, align and freeze.
+    ///
+    /// The trigger fires on a rising edge of the first curve through the
+    /// level of the mouse cursor. The capture has the width of the
+    /// current view, with the trigger moment in the middle.
+    fn cycle_trigger_mode(&mut self) {
+        self.trigger_mode = match self.trigger_mode {
+            TriggerMode::Off => {
+                if let Some(trigger) = self.create_trigger() {
+                    info!("Trigger on {:?}", trigger);
+                    self.db.add_trigger(&self.trigger_name(), trigger);
+                    TriggerMode::Align
+                } else {
+                    info!("Need a curve and a cursor level to trigger on");
+                    TriggerMode::Off
+                }
+            }
+            TriggerMode::Align => TriggerMode::Freeze,
+            TriggerMode::Freeze => {
+                self.db.remove_trigger(&self.trigger_name());
+                if self.chart.is_paused() {
+                    self.chart.resume();
+                    self.chart_data_request = None;
+                    self.repaint();
+                }
+                TriggerMode::Off
+            }
+        };
+        info!("Trigger mode: {:?}", self.trigger_mode);
+    }
+
+    fn create_trigger(&self) -> Option<Trigger> {
+        let curve = self.chart.curves.first()?;
+        let (_, level) = self.chart.cursor.clone()?;
+        let half_width = self.chart.x_axis.domain().as_secs_f64() / 2.0;
+        let trigger = Trigger::new(&curve.name(), TriggerCondition::RisingEdge(level))
+            .pre_trigger(half_width)
+            .post_trigger(half_width);
+        Some(trigger)
+    }
+
+    fn on_trigger(&mut self, event: &TriggerEvent) {
+        match self.trigger_mode {
+            TriggerMode::Off => {}
+            TriggerMode::Align => {
+                self.show_capture(event);
+                self.app_state.borrow().sync_x_axis(&self);
+            }
+            TriggerMode::Freeze => {
+                // Keep showing the first capture, until resumed. Only this
+                // chart is paused, so the other charts are left alone:
+                if !self.chart.is_paused() {
+                    self.chart.pause(self.db.snapshot());
+                    self.show_capture(event);
+                }
+            }
+        }
+    }
+
+    fn show_capture(&mut self, event: &TriggerEvent) {
+        debug!("Trigger at {:?}", event.timestamp);
+        self.disable_tailing();
+        self.chart.fit_x_axis_to_timespan(&event.timespan);
+        self.chart.fit_y_axis();
+        self.chart_data_request = None;
+        self.repaint();
     }
 
     fn repaint(&self) {
@@ -232,7 +422,6 @@ impl ChartState {
         if let Some((pixel_x, pixel_y)) = loc {
             let timestamp = x_pixel_to_domain(pixel_x, &self.chart.x_axis, &self.chart_layout);
             let value = y_pixel_to_domain(pixel_y, &self.chart.y_axis, &self.chart_layout);
-            let timestamp = TimeStamp::new(timestamp);
             self.chart.cursor = Some((timestamp, value));
         } else {
             self.chart.cursor = None;
@@ -296,6 +485,19 @@ impl ChartState {
         self.tailing = None;
     }
 
+    /// Freeze the data shown in this chart, while data is still received.
+    fn toggle_pause(&mut self) {
+        if self.chart.is_paused() {
+            info!("Resume showing new data");
+            self.chart.resume();
+        } else {
+            info!("Pause showing new data");
+            self.chart.pause(self.db.snapshot());
+        }
+        self.chart_data_request = None;
+        self.repaint();
+    }
+
     fn do_tailing(&mut self) {
         if let Some(x) = self.tailing {
             self.zoom_to_last(x);
@@ -363,6 +565,129 @@ impl ChartState {
         self.chart_layout.resize(width, height);
     }
 
+    /// Query the data of the chart in the background, when the view,
+    /// the cursors or the data changed.
+    ///
+    /// Only the parts of which the request changed are queried again.
+    /// Each query supersedes the previous query of the same part. Until
+    /// its result arrives, the previous result is drawn.
+    fn request_chart_data(&mut self) {
+        let request = ChartDataRequest::new(&self.chart, &self.chart_layout);
+        let previous = self.chart_data_request.replace(request.clone());
+        if previous.as_ref() == Some(&request) && !self.show_range_statistics {
+            return;
+        }
+        let changed = |f: fn(&ChartDataRequest, &ChartDataRequest) -> bool| {
+            previous
+                .as_ref()
+                .map_or(true, |previous| f(&request, previous))
+        };
+        let view_changed = changed(ChartDataRequest::view_changed);
+        let cursor_changed = changed(ChartDataRequest::cursor_changed);
+        let range_changed = changed(ChartDataRequest::range_changed);
+        let request = Arc::new(request);
+
+        if view_changed {
+            for curve in &self.chart.curves {
+                let name = curve.name();
+                let query = {
+                    let (curve, request) = (curve.clone(), request.clone());
+                    self.db.run_async(move || request.query_curve(&curve))
+                };
+                self.curve_queries
+                    .entry(name.clone())
+                    .or_default()
+                    .supersede(&query);
+                self.spawn_query(query, move |data, result| {
+                    data.curves.insert(name, result);
+                });
+            }
+
+            for track in &self.chart.text_tracks {
+                let name = track.name().to_owned();
+                let query = {
+                    let (track, request) = (track.clone(), request.clone());
+                    self.db.run_async(move || request.query_text_track(&track))
+                };
+                self.text_track_queries
+                    .entry(name.clone())
+                    .or_default()
+                    .supersede(&query);
+                self.spawn_query(query, move |data, result| {
+                    data.text_tracks.insert(name, result);
+                });
+            }
+        }
+
+        if cursor_changed {
+            let query = if request.cursor.is_some() {
+                let (curves, request) = (self.chart.curves.clone(), request.clone());
+                self.db
+                    .run_async(move || Some(request.query_cursor_values(&curves)))
+            } else {
+                AsyncQuery::ready(Some(vec![]))
+            };
+            self.cursor_query.supersede(&query);
+            self.spawn_query(query, |data, values| {
+                data.cursor_values = values;
+            });
+        }
+
+        if range_changed || self.show_range_statistics {
+            let query = if request.range.is_some() {
+                let (curves, request) = (self.chart.curves.clone(), request.clone());
+                self.db
+                    .run_async(move || Some(request.query_range_statistics(&curves)))
+            } else {
+                AsyncQuery::ready(Some(vec![]))
+            };
+            let app_state = self.app_state.clone();
+            let columns = range_statistics_columns(&request.statistics);
+            let show = std::mem::replace(&mut self.show_range_statistics, false);
+            self.range_query.supersede(&query);
+            self.spawn_query(query, move |data, statistics| {
+                // Skipped when the app state is being changed, for example
+                // when all charts are cleared at once.
+                if show {
+                    if let Ok(app_state) = app_state.try_borrow() {
+                        app_state.show_range_statistics(columns, &statistics);
+                    }
+                }
+                data.range_statistics = statistics;
+            });
+        }
+
+        // The units only change with the curves, which invalidates the
+        // whole request.
+        if previous.is_none() {
+            let curves = self.chart.curves.clone();
+            let query = self
+                .db
+                .run_async(move || Some(ChartDataRequest::query_units(&curves)));
+            self.units_query.supersede(&query);
+            self.spawn_query(query, |data, units| {
+                data.units = units;
+            });
+        }
+    }
+
+    /// Store the result of a query in the chart data when it arrives,
+    /// and draw it.
+    fn spawn_query<T, F>(&self, query: AsyncQuery<T>, store: F)
+    where
+        T: 'static,
+        F: FnOnce(&mut ChartData, T) + 'static,
+    {
+        let chart_data = self.chart_data.clone();
+        let draw_area = self.draw_area.clone();
+        glib::MainContext::default().spawn_local(async move {
+            if let Some(result) = query.await {
+                store(&mut chart_data.borrow_mut(), result);
+                draw_area.queue_draw();
+            }
+        });
+    }
+
     fn draw_on_canvas(&mut self, canvas: &cairo::Context) -> Inhibit {
         let size = get_size(&self.draw_area);
 
@@ -372,11 +697,13 @@ impl ChartState {
 
         let t1 = Instant::now();
 
-        draw_chart(
+        self.request_chart_data();
+        draw_chart_with_data(
             &self.chart,
             &mut canvas2,
             &mut self.chart_layout,
             &self.chart_options,
+            &self.chart_data.borrow(),
         );
 
         let t2 = Instant::now();
@@ -457,6 +784,12 @@ impl ChartState {
             gdk::enums::key::_2 => {
                 self.set_cursor2();
             }
+            gdk::enums::key::p => {
+                self.toggle_pause();
+            }
+            gdk::enums::key::t => {
+                self.cycle_trigger_mode();
+            }
 
             x => {
                 println!("Key! {:?}", x);
@@ -501,12 +834,12 @@ pub fn setup_drawing_area(
     draw_area.connect_drag_data_received(
         clone!(@strong chart_state => move |w, _dc, _x, _y, data, _info, _time| {
             let mime_payload: String = data.get_text().expect("Must work!!").to_string();
-            if let Ok(signal_names) = serde_json::from_str::<Vec<String>>(&mime_payload) {
-                info!("DROP {:?}", signal_names);
-                for signal_name in signal_names {
+            if let Ok(curves) = serde_json::from_str::<Vec<CurveItem>>(&mime_payload) {
+                info!("DROP {:?}", curves);
+                for curve in curves {
                     chart_state
                     .borrow_mut()
-                    .add_curve(&signal_name);
+                    .add_curve(&curve);
                 }
                 w.grab_focus();
             } else {
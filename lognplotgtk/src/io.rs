@@ -3,10 +3,15 @@
 use super::error_dialog::show_error;
 use super::GuiStateHandle;
 use gtk::prelude::*;
+use hdf5::types::VarLenUnicode;
 use lognplot::time::TimeStamp;
-use lognplot::tsdb::{Observation, Sample, TsDbHandle};
+use lognplot::tsdb::{Metadata, Observation, Sample, TsDbHandle};
+use std::collections::HashMap;
 use std::path::Path;
 
+/// Dataset with the metadata of all signals, as JSON text.
+const METADATA_DATASET: &str = "metadata";
+
 /// Popup a dialog and export data as HDF5 format.
 pub fn save_data_as_hdf5(top_level: &gtk::Window, app_state: &GuiStateHandle) {
     let dialog = gtk::FileChooserDialog::with_buttons(
@@ -68,9 +73,16 @@ pub fn export_data(db: TsDbHandle, filename: &Path) -> hdf5::Result<()> {
 
 /// Export all signals from the database into a HDF5 file.
 fn export_db(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
+    // Export a consistent view, while new data keeps arriving:
+    let db = db.snapshot();
     let group = file.create_group("my_datorz")?;
 
     let signal_names = db.get_signal_names();
+    let metadata: HashMap<String, Metadata> = signal_names
+        .iter()
+        .filter_map(|name| Some((name.clone(), db.metadata(name)?)))
+        .collect();
+
     for signal_name in signal_names {
         // db
         if let Some(data) = db.get_raw_samples(&signal_name) {
@@ -82,7 +94,7 @@ fn export_db(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
             // Create f64 data:
             let data: Vec<[f64; 2]> = data
                 .iter()
-                .map(|o| [o.timestamp.amount, o.value.value])
+                .map(|o| [o.timestamp.to_seconds(), o.value.value])
                 .collect();
 
             // Construct ndarray:
@@ -96,6 +108,12 @@ fn export_db(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
         }
     }
 
+    let metadata: VarLenUnicode = serde_json::to_string(&metadata).unwrap().parse().unwrap();
+    let dataset = file
+        .new_dataset::<VarLenUnicode>()
+        .create(METADATA_DATASET, 1)?;
+    dataset.write_raw(&[metadata])?;
+
     Ok(())
 }
 
@@ -134,6 +152,22 @@ fn import_data_inner(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
         }
     }
 
+    // Files without metadata are fine:
+    if let Ok(dataset) = file.dataset(METADATA_DATASET) {
+        for text in dataset.read_raw::<VarLenUnicode>()? {
+            match serde_json::from_str::<HashMap<String, Metadata>>(text.as_str()) {
+                Ok(metadata) => {
+                    for (name, metadata) in metadata {
+                        db.set_metadata(&name, metadata);
+                    }
+                }
+                Err(err) => {
+                    warn!("Ignoring invalid metadata: {}", err);
+                }
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -141,7 +175,7 @@ fn import_data_inner(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
 mod tests {
     use super::{export_db, import_data_inner};
     use lognplot::time::TimeStamp;
-    use lognplot::tsdb::{Observation, Sample, TsDb};
+    use lognplot::tsdb::{Metadata, Observation, Sample, TsDb};
 
     #[test]
     fn export_test() -> hdf5::Result<()> {
@@ -156,6 +190,7 @@ mod tests {
             let observation = Observation::new(ts.clone(), sample);
             db.add_value(trace_name, observation);
         }
+        db.set_metadata(trace_name, Metadata::with_unit("m"));
 
         let db_handle = db.into_handle();
 
@@ -169,6 +204,10 @@ mod tests {
 
         assert_eq!(vec![trace_name], db2_handle.get_signal_names());
         assert_eq!(7, db2_handle.quick_summary(trace_name).unwrap().count);
+        assert_eq!(
+            Some(Metadata::with_unit("m")),
+            db2_handle.metadata(trace_name)
+        );
         Ok(())
     }
 }
@@ -16,11 +16,12 @@ mod resources;
 mod session;
 mod signal_repository;
 mod state;
+mod statistics_table;
 mod time_tracker;
 
 use lognplot::net::run_server;
 use lognplot::tracer::AnyTracer;
-use lognplot::tsdb::TsDb;
+use lognplot::tsdb::{RetentionPolicy, TsDb};
 use std::sync::Arc;
 
 pub use state::{GuiState, GuiStateHandle};
@@ -54,6 +55,30 @@ fn main() {
                 .help("Port to listen on")
                 .default_value("12345"),
         )
+        .arg(
+            clap::Arg::with_name("database")
+                .long("database")
+                .takes_value(true)
+                .help("Store data in the given directory, and load data stored there before."),
+        )
+        .arg(
+            clap::Arg::with_name("memory-budget")
+                .long("memory-budget")
+                .takes_value(true)
+                .help("Drop the oldest samples when using more than this amount of megabytes."),
+        )
+        .arg(
+            clap::Arg::with_name("max-age")
+                .long("max-age")
+                .takes_value(true)
+                .help("Drop samples older than this amount of seconds."),
+        )
+        .arg(
+            clap::Arg::with_name("backup-on-rewind")
+                .long("backup-on-rewind")
+                .takes_value(true)
+                .help("Move a signal into a backup when samples arrive more than this amount of seconds late."),
+        )
         .arg(
             clap::Arg::with_name("meta-trace")
                 .long("meta-trace")
@@ -87,7 +112,40 @@ fn main() {
 
     info!("Starting lognplot GUI tool");
 
-    let db = TsDb::default();
+    let mut db = if let Some(path) = matches.value_of("database") {
+        info!("Opening database at {}", path);
+        match TsDb::open(path) {
+            Ok(db) => db,
+            Err(err) => {
+                error!("Error opening database at {}: {}", path, err);
+                return;
+            }
+        }
+    } else {
+        TsDb::default()
+    };
+    let mut retention = RetentionPolicy::new();
+    if let Some(budget) = matches.value_of("memory-budget") {
+        match usize::from_str(budget) {
+            Ok(megabytes) => retention = retention.max_bytes(megabytes * 1024 * 1024),
+            Err(err) => error!("Invalid memory budget {}: {}", budget, err),
+        }
+    }
+    if let Some(max_age) = matches.value_of("max-age") {
+        match f64::from_str(max_age) {
+            Ok(seconds) => retention = retention.max_age(seconds),
+            Err(err) => error!("Invalid maximum age {}: {}", max_age, err),
+        }
+    }
+    db.set_retention(retention);
+
+    if let Some(window) = matches.value_of("backup-on-rewind") {
+        match f64::from_str(window) {
+            Ok(seconds) => db.set_backup_on_rewind(Some(seconds)),
+            Err(err) => error!("Invalid rewind window {}: {}", window, err),
+        }
+    }
+
     let db_handle = db.into_handle();
 
     let perf_tracer = if matches.is_present("meta-trace-remote") {
@@ -109,6 +167,10 @@ fn main() {
     };
 
     let stop_token = run_server(db_handle.clone(), port, perf_tracer.clone());
-    mainwindow::open_gui(db_handle, perf_tracer);
+    mainwindow::open_gui(db_handle.clone(), perf_tracer);
     stop_token.stop();
+
+    if let Err(err) = db_handle.flush() {
+        error!("Error writing data to disk: {}", err);
+    }
 }
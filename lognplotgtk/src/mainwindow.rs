@@ -2,12 +2,14 @@ use super::chart_widget::setup_drawing_area;
 use super::io::{load_data_from_hdf5, save_data_as_hdf5};
 use super::session::{load_session, save_session};
 use super::signal_repository::setup_signal_repository;
+use super::statistics_table::setup_statistics_table;
 use super::{GuiState, GuiStateHandle};
 use gio::prelude::*;
 use gtk::prelude::*;
 use gtk::Application;
+use lognplot::chart::Chart;
 use lognplot::tracer::AnyTracer;
-use lognplot::tsdb::TsDbHandle;
+use lognplot::tsdb::{Statistic, TsDbHandle, STATISTICS};
 use std::sync::Arc;
 
 pub fn open_gui(db_handle: TsDbHandle, perf_tracer: Arc<AnyTracer>) {
@@ -33,6 +35,11 @@ fn build_ui(app: &gtk::Application, app_state: GuiStateHandle) {
     // Connect the data set tree:
     setup_signal_repository(&builder, app_state.clone());
 
+    let statistics_table = setup_statistics_table(&builder);
+    app_state
+        .borrow_mut()
+        .set_statistics_table(statistics_table);
+
     setup_chart_area(&builder, app_state.clone());
     setup_menus(&builder, app_state.clone());
     setup_toolbar_buttons(&builder, app_state.clone());
@@ -310,6 +317,7 @@ fn setup_toolbar_buttons(builder: &gtk::Builder, app_state: GuiStateHandle) {
     }
 
     setup_zoom_to_options(builder, app_state.clone());
+    setup_statistics_options(builder, app_state.clone());
 
     {
         let tb_link_x_axis: gtk::ToggleToolButton = builder.get_object("tb_link_x_axis").unwrap();
@@ -351,6 +359,43 @@ fn setup_zoom_to_options(builder: &gtk::Builder, app_state: GuiStateHandle) {
     }
 }
 
+/// Setup statistics button and popover with a check button per statistic
+fn setup_statistics_options(builder: &gtk::Builder, app_state: GuiStateHandle) {
+    let tb_statistics: gtk::ToolButton = builder.get_object("tb_statistics").unwrap();
+    let pop_over = gtk::Popover::new(Some(&tb_statistics));
+    let check_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    pop_over.add(&check_box);
+
+    tb_statistics.connect_clicked(clone!(@strong pop_over => move |_tb| {
+        pop_over.show_all();
+    }));
+
+    let selected = Chart::default().statistics;
+    let check_buttons: Vec<(Statistic, gtk::CheckButton)> = STATISTICS
+        .iter()
+        .map(|statistic| {
+            let check_button = gtk::CheckButton::with_label(&statistic.to_string());
+            check_button.set_active(selected.contains(statistic));
+            check_box.pack_start(&check_button, false, false, 0);
+            (*statistic, check_button)
+        })
+        .collect();
+
+    for (_, check_button) in &check_buttons {
+        check_button.connect_toggled(
+            clone!(@strong app_state, @strong check_buttons => move |_cb| {
+                let statistics: Vec<Statistic> = check_buttons
+                    .iter()
+                    .filter(|(_, check_button)| check_button.get_active())
+                    .map(|(statistic, _)| *statistic)
+                    .collect();
+                info!("Show statistics {:?}", statistics);
+                app_state.borrow().set_statistics(&statistics);
+            }),
+        );
+    }
+}
+
 /// Subscribe to database changes and redraw correct things.
 fn setup_notify_change(app_state: GuiStateHandle) {
     let mut receiver = app_state.borrow().db.new_notify_queue();
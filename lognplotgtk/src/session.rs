@@ -30,15 +30,56 @@ struct DashBoard {
 #[serde(tag = "type")]
 pub enum DashBoardItem {
     #[serde(rename = "graph")]
-    Graph { curves: Vec<String> },
+    Graph { curves: Vec<CurveItem> },
 
     #[serde(rename = "empty")]
     Empty,
 }
 
+/// A curve in a graph.
+///
+/// This is either a whole signal, or a single component of a vector signal.
+/// It is also the payload of signals dragged onto a chart.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum CurveItem {
+    Signal(String),
+    Component { name: String, component: usize },
+}
+
+impl CurveItem {
+    pub fn new(name: &str, component: Option<usize>) -> Self {
+        match component {
+            Some(component) => CurveItem::Component {
+                name: name.to_owned(),
+                component,
+            },
+            None => CurveItem::Signal(name.to_owned()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            CurveItem::Signal(name) => name,
+            CurveItem::Component { name, .. } => name,
+        }
+    }
+
+    pub fn component(&self) -> Option<usize> {
+        match self {
+            CurveItem::Signal(_) => None,
+            CurveItem::Component { component, .. } => Some(*component),
+        }
+    }
+}
+
 impl From<&Chart> for DashBoardItem {
     fn from(chart: &Chart) -> Self {
-        let curves: Vec<String> = chart.curves.iter().map(|c| c.name()).collect();
+        let curves: Vec<CurveItem> = chart
+            .curves
+            .iter()
+            .map(|c| CurveItem::new(&c.name(), c.data.component()))
+            .collect();
         DashBoardItem::Graph { curves }
     }
 }
@@ -105,7 +146,7 @@ pub fn load_session(top_level: &gtk::Window, app_state: &GuiStateHandle) {
 
 #[cfg(test)]
 mod tests {
-    use super::{DashBoardItem, Session};
+    use super::{CurveItem, DashBoardItem, Session};
 
     #[test]
     fn test_session_decode() {
@@ -116,7 +157,11 @@ mod tests {
                     "type": "graph",
                     "curves": [
                         "C3",
-                        "C5"
+                        "C5",
+                        {
+                            "name": "imu",
+                            "component": 1
+                        }
                     ]
                 },
                 {
@@ -136,7 +181,11 @@ mod tests {
 
         assert_eq!(
             DashBoardItem::Graph {
-                curves: vec!["C3".to_owned(), "C5".to_owned()]
+                curves: vec![
+                    CurveItem::new("C3", None),
+                    CurveItem::new("C5", None),
+                    CurveItem::new("imu", Some(1))
+                ]
             },
             session.dashboard[0]
         );
@@ -3,15 +3,34 @@ use std::collections::HashMap;
 
 // TODO
 // use crate::error_dialog::show_error;
+use crate::session::CurveItem;
 use crate::state::GuiStateHandle;
-use lognplot::tsdb::{DataChangeEvent, TsDbHandle};
-
+use lognplot::tsdb::{base_name, namespaces, DataChangeEvent, TsDbHandle};
+
+/// Columns of the signal model.
+const COLUMN_BASE_NAME: u32 = 0;
+const COLUMN_SIZE: u32 = 1;
+const COLUMN_LAST_VALUE: u32 = 2;
+const COLUMN_UNIT: u32 = 3;
+const COLUMN_DESCRIPTION: u32 = 4;
+const COLUMN_NAME: u32 = 5;
+const COLUMN_IS_SIGNAL: u32 = 6;
+const COLUMN_COMPONENT: u32 = 7;
+
+/// Value of the component column in rows which are not a vector component.
+const NO_COMPONENT: i32 = -1;
+
+/// Browser of the signals in the database, arranged by namespace.
+///
+/// A signal named `robot/arm/pos` is shown as `pos`, below the
+/// rows of the `robot` and `robot/arm` namespaces. The components
+/// of a vector signal are shown below the row of the signal.
 pub struct SignalBrowser {
     model: gtk::TreeStore,
     db: TsDbHandle,
 
-    // Mapping from signal name to row:
-    model_map: HashMap<String, i32>,
+    // Mapping from signal or namespace name to row:
+    rows: HashMap<String, gtk::TreeIter>,
 }
 
 impl SignalBrowser {
@@ -20,6 +39,7 @@ impl SignalBrowser {
         if event.delete_all {
             self.delete_all();
         }
+        self.delete_signals(event.deleted_signals.iter());
         self.add_new_signals(event.new_signals.iter()).await;
         self.update_signals(event.changed_signals.iter()).await;
     }
@@ -31,11 +51,13 @@ impl SignalBrowser {
     {
         let mut updates = 0;
         for signal_name in new_signals {
-            let iter = self.model.append(None);
-            let row = self.model_map.len() as i32;
-            self.model_map.insert(signal_name.clone(), row);
-            self.model
-                .set(&iter, &[0, 1, 2], &[&signal_name, &"-", &"-"]);
+            let iter = self.get_or_add_row(signal_name);
+            self.model.set(
+                &iter,
+                &[COLUMN_SIZE, COLUMN_LAST_VALUE, COLUMN_IS_SIGNAL],
+                &[&"-", &"-", &true],
+            );
+            self.add_component_rows(&iter, signal_name);
 
             updates += 1;
             if updates > 50 {
@@ -47,6 +69,89 @@ impl SignalBrowser {
         }
     }
 
+    /// Find the row of a signal or namespace.
+    ///
+    /// The row is added when it does not exist yet, and so are
+    /// the rows of its namespaces.
+    fn get_or_add_row(&mut self, name: &str) -> gtk::TreeIter {
+        if let Some(iter) = self.rows.get(name) {
+            return iter.clone();
+        }
+
+        let parent = namespaces(name)
+            .last()
+            .map(|namespace| self.get_or_add_row(namespace));
+        let iter = self.model.append(parent.as_ref());
+        self.model.set(
+            &iter,
+            &[
+                COLUMN_BASE_NAME,
+                COLUMN_SIZE,
+                COLUMN_LAST_VALUE,
+                COLUMN_UNIT,
+                COLUMN_DESCRIPTION,
+                COLUMN_NAME,
+                COLUMN_IS_SIGNAL,
+                COLUMN_COMPONENT,
+            ],
+            &[
+                &base_name(name),
+                &"",
+                &"",
+                &"",
+                &"",
+                &name,
+                &false,
+                &NO_COMPONENT,
+            ],
+        );
+        self.rows.insert(name.to_owned(), iter.clone());
+        iter
+    }
+
+    /// Add a row for each component of a vector signal.
+    fn add_component_rows(&self, parent: &gtk::TreeIter, name: &str) {
+        self.remove_component_rows(parent);
+        for (index, component) in self
+            .db
+            .components(name)
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+        {
+            let iter = self.model.append(Some(parent));
+            self.model.set(
+                &iter,
+                &[
+                    COLUMN_BASE_NAME,
+                    COLUMN_SIZE,
+                    COLUMN_LAST_VALUE,
+                    COLUMN_UNIT,
+                    COLUMN_DESCRIPTION,
+                    COLUMN_NAME,
+                    COLUMN_IS_SIGNAL,
+                    COLUMN_COMPONENT,
+                ],
+                &[component, &"", &"", &"", &"", &name, &true, &(index as i32)],
+            );
+        }
+    }
+
+    fn remove_component_rows(&self, parent: &gtk::TreeIter) {
+        if let Some(child) = self.model.iter_children(Some(parent)) {
+            loop {
+                let more = if get_component(self.model.upcast_ref(), &child).is_some() {
+                    self.model.remove(&child)
+                } else {
+                    self.model.iter_next(&child)
+                };
+                if !more {
+                    break;
+                }
+            }
+        }
+    }
+
     /// Update existing signals in the model
     async fn update_signals<'a, I>(&self, changed_signals: I)
     where
@@ -54,14 +159,20 @@ impl SignalBrowser {
     {
         let mut updates = 0;
         for signal_name in changed_signals {
-            if let Some(summary) = self.db.quick_summary(&signal_name) {
-                let row = self.model_map[signal_name];
-                let path: gtk::TreePath = gtk::TreePath::new_from_indicesv(&[row]);
-                if let Some(iter2) = self.model.get_iter(&path) {
-                    self.model
-                        .set_value(&iter2, 1, &summary.count.to_string().to_value());
+            if let (Some(summary), Some(iter)) = (
+                self.db.quick_summary(&signal_name),
+                self.rows.get(signal_name),
+            ) {
+                self.model
+                    .set_value(iter, COLUMN_SIZE, &summary.count.to_string().to_value());
+                self.model
+                    .set_value(iter, COLUMN_LAST_VALUE, &summary.last_value().to_value());
+                if let Some(metadata) = self.db.metadata(&signal_name) {
+                    let unit = metadata.unit.unwrap_or_default();
+                    let description = metadata.description.unwrap_or_default();
+                    self.model.set_value(iter, COLUMN_UNIT, &unit.to_value());
                     self.model
-                        .set_value(&iter2, 2, &summary.last_value().to_value());
+                        .set_value(iter, COLUMN_DESCRIPTION, &description.to_value());
                 }
                 updates += 1;
                 if updates > 50 {
@@ -76,10 +187,57 @@ impl SignalBrowser {
         debug!("Updates: {}", updates);
     }
 
+    /// Remove deleted signals from the model
+    fn delete_signals<'a, I>(&mut self, deleted_signals: I)
+    where
+        I: Iterator<Item = &'a String>,
+    {
+        for signal_name in deleted_signals {
+            self.delete_row(signal_name);
+        }
+    }
+
+    /// Remove the row of a signal or namespace.
+    ///
+    /// Rows with signals below them are kept, and namespaces without
+    /// signals are removed as well.
+    fn delete_row(&mut self, name: &str) {
+        if let Some(iter) = self.rows.get(name).cloned() {
+            self.remove_component_rows(&iter);
+            if self.model.iter_has_child(&iter) {
+                self.model.set(
+                    &iter,
+                    &[
+                        COLUMN_SIZE,
+                        COLUMN_LAST_VALUE,
+                        COLUMN_UNIT,
+                        COLUMN_DESCRIPTION,
+                        COLUMN_IS_SIGNAL,
+                    ],
+                    &[&"", &"", &"", &"", &false],
+                );
+            } else {
+                self.model.remove(&iter);
+                self.rows.remove(name);
+                if let Some(namespace) = namespaces(name).last() {
+                    if !self.is_signal(namespace) {
+                        self.delete_row(namespace);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_signal(&self, name: &str) -> bool {
+        self.rows
+            .get(name)
+            .map_or(false, |iter| is_signal_row(self.model.upcast_ref(), iter))
+    }
+
     /// Delete all signals from the model
     fn delete_all(&mut self) {
         self.model.clear();
-        self.model_map.clear();
+        self.rows.clear();
     }
 }
 
@@ -89,6 +247,11 @@ pub fn setup_signal_repository(builder: &gtk::Builder, app_state: GuiStateHandle
         String::static_type(),
         String::static_type(),
         String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        bool::static_type(),
+        i32::static_type(),
     ]);
 
     setup_columns(builder);
@@ -104,7 +267,7 @@ pub fn setup_signal_repository(builder: &gtk::Builder, app_state: GuiStateHandle
     let signal_browser = SignalBrowser {
         model,
         db,
-        model_map: HashMap::new(),
+        rows: HashMap::new(),
     };
 
     setup_notify_change(signal_browser);
@@ -135,18 +298,23 @@ fn setup_columns(builder: &gtk::Builder) {
     let name_column: gtk::TreeViewColumn = builder.get_object("column_name").unwrap();
     let size_column: gtk::TreeViewColumn = builder.get_object("column_size").unwrap();
     let last_value_column: gtk::TreeViewColumn = builder.get_object("column_last_value").unwrap();
+    let unit_column: gtk::TreeViewColumn = builder.get_object("column_unit").unwrap();
 
     let cell = gtk::CellRendererText::new();
     name_column.pack_start(&cell, true);
-    name_column.add_attribute(&cell, "text", 0);
+    name_column.add_attribute(&cell, "text", COLUMN_BASE_NAME as i32);
 
     let cell = gtk::CellRendererText::new();
     size_column.pack_start(&cell, true);
-    size_column.add_attribute(&cell, "text", 1);
+    size_column.add_attribute(&cell, "text", COLUMN_SIZE as i32);
 
     let cell = gtk::CellRendererText::new();
     last_value_column.pack_start(&cell, true);
-    last_value_column.add_attribute(&cell, "text", 2);
+    last_value_column.add_attribute(&cell, "text", COLUMN_LAST_VALUE as i32);
+
+    let cell = gtk::CellRendererText::new();
+    unit_column.pack_start(&cell, true);
+    unit_column.add_attribute(&cell, "text", COLUMN_UNIT as i32);
 }
 
 fn setup_filter_model(builder: &gtk::Builder, model: &gtk::TreeStore) {
@@ -161,7 +329,7 @@ fn setup_filter_model(builder: &gtk::Builder, model: &gtk::TreeStore) {
 
     filter_model.set_visible_func(clone!(@strong filter_edit => move |m, i| {
         let txt = filter_edit.get_text().unwrap().to_string();
-        signal_filter_func(m, i, txt)
+        signal_filter_func(m, i, &txt)
     }));
 
     tree_view.set_model(Some(&filter_model));
@@ -171,13 +339,32 @@ fn setup_filter_model(builder: &gtk::Builder, model: &gtk::TreeStore) {
     });
 }
 
-fn signal_filter_func(model: &gtk::TreeModel, iter: &gtk::TreeIter, filter_txt: String) -> bool {
-    let optional_name = model.get_value(&iter, 0).get::<String>().unwrap();
-    if let Some(name) = optional_name {
-        filter_txt.is_empty() || name.contains(&filter_txt)
+/// Show rows of which the full name matches, and namespaces with matching signals.
+fn signal_filter_func(model: &gtk::TreeModel, iter: &gtk::TreeIter, filter_txt: &str) -> bool {
+    let optional_name = model
+        .get_value(&iter, COLUMN_NAME as i32)
+        .get::<String>()
+        .unwrap();
+    let matches = if let Some(name) = optional_name {
+        filter_txt.is_empty() || name.contains(filter_txt)
     } else {
         true
+    };
+
+    if matches {
+        return true;
     }
+    if let Some(child) = model.iter_children(Some(iter)) {
+        loop {
+            if signal_filter_func(model, &child, filter_txt) {
+                return true;
+            }
+            if !model.iter_next(&child) {
+                break;
+            }
+        }
+    }
+    false
 }
 
 /// Connect drag signal.
@@ -196,8 +383,8 @@ fn setup_drag_drop(tree_view: &gtk::TreeView) {
         gdk::DragAction::COPY,
     );
     tree_view.connect_drag_data_get(|w, _, data, info, _| {
-        let selected_names = get_selected_signal_names(w);
-        let mime_payload: String = serde_json::to_string(&selected_names).unwrap();
+        let selected_curves = get_selected_curves(w);
+        let mime_payload: String = serde_json::to_string(&selected_curves).unwrap();
         let r = data.set_text(&mime_payload);
         if !r {
             error!("Drag data get transfer failed");
@@ -247,14 +434,32 @@ fn handle_drop_uri(uri: String, app_state: &GuiStateHandle) -> Result<(), String
     }
 }
 
-fn get_selected_signal_names(w: &gtk::TreeView) -> Vec<String> {
+/// Get the selected signals and vector components, leaving out namespaces.
+fn get_selected_curves(w: &gtk::TreeView) -> Vec<CurveItem> {
     let selector = w.get_selection();
     let (selected_rows, tree_model) = selector.get_selected_rows();
-    let mut selected_names: Vec<String> = vec![];
+    let mut selected_curves = vec![];
+    for selected_row in selected_rows {
+        if let Some(tree_iter) = tree_model.get_iter(&selected_row) {
+            if is_signal_row(&tree_model, &tree_iter) {
+                let name = get_signal_name(&tree_model, &tree_iter);
+                let component = get_component(&tree_model, &tree_iter);
+                selected_curves.push(CurveItem::new(&name, component));
+            }
+        }
+    }
+    selected_curves
+}
+
+/// Get the names of the selected rows, and whether they are signals.
+fn get_selected_names(w: &gtk::TreeView) -> Vec<(String, bool)> {
+    let selector = w.get_selection();
+    let (selected_rows, tree_model) = selector.get_selected_rows();
+    let mut selected_names = vec![];
     for selected_row in selected_rows {
         if let Some(tree_iter) = tree_model.get_iter(&selected_row) {
             let value = get_signal_name(&tree_model, &tree_iter);
-            selected_names.push(value);
+            selected_names.push((value, is_signal_row(&tree_model, &tree_iter)));
         }
     }
     selected_names
@@ -266,15 +471,23 @@ fn setup_activate(tree_view: &gtk::TreeView, app_state: GuiStateHandle) {
         let iter = model.get_iter(path).unwrap();
         let value = get_signal_name(&model, &iter);
 
-        debug!("Signal activated: {}, adding to chart.", value);
-        // Add activated signal to plot:
-        app_state.borrow().add_curve(&value, None);
+        if is_signal_row(&model, &iter) {
+            debug!("Signal activated: {}, adding to chart.", value);
+            // Add activated signal to plot:
+            let curve = CurveItem::new(&value, get_component(&model, &iter));
+            app_state.borrow().add_curve(&curve, None);
+        } else if tv.row_expanded(path) {
+            tv.collapse_row(path);
+        } else {
+            tv.expand_row(path, false);
+        }
     });
 }
 
 fn setup_key_press_handler(tree_view: &gtk::TreeView, app_state: GuiStateHandle) {
     tree_view.connect_key_press_event(move |tv, key| {
-        let selected_signals = get_selected_signal_names(&tv);
+        let selected_rows = get_selected_names(&tv);
+        let selected_curves = get_selected_curves(&tv);
         let chart_target = match key.get_keyval() {
             gdk::enums::key::_1 => Some(1),
             gdk::enums::key::_2 => Some(2),
@@ -291,23 +504,60 @@ fn setup_key_press_handler(tree_view: &gtk::TreeView, app_state: GuiStateHandle)
             gdk::enums::key::D => Some(13),
             gdk::enums::key::E => Some(14),
             gdk::enums::key::F => Some(15),
+            gdk::enums::key::Delete => {
+                for (name, is_signal) in &selected_rows {
+                    if *is_signal {
+                        app_state.borrow().delete_signal(name);
+                    } else {
+                        app_state.borrow().delete_namespace(name);
+                    }
+                }
+                None
+            }
             _ => None,
         };
         if chart_target.is_some() {
-            for signal_name in selected_signals {
+            for curve in selected_curves {
                 debug!(
-                    "Signal activated: {}, adding to chart {}.",
-                    signal_name,
+                    "Signal activated: {:?}, adding to chart {}.",
+                    curve,
                     chart_target.expect("some value")
                 );
-                app_state.borrow().add_curve(&signal_name, chart_target);
+                app_state.borrow().add_curve(&curve, chart_target);
             }
         }
         Inhibit(false)
     });
 }
 
-/// Given a model and an iterator get the signal name.
+/// Given a model and an iterator get the full signal or namespace name.
 fn get_signal_name(model: &gtk::TreeModel, iter: &gtk::TreeIter) -> String {
-    model.get_value(iter, 0).get::<String>().unwrap().unwrap()
+    model
+        .get_value(iter, COLUMN_NAME as i32)
+        .get::<String>()
+        .unwrap()
+        .unwrap()
+}
+
+/// Test if a row is a signal, instead of only a namespace.
+fn is_signal_row(model: &gtk::TreeModel, iter: &gtk::TreeIter) -> bool {
+    model
+        .get_value(iter, COLUMN_IS_SIGNAL as i32)
+        .get::<bool>()
+        .unwrap()
+        .unwrap_or(false)
+}
+
+/// The component of a vector signal shown in a row, if any.
+fn get_component(model: &gtk::TreeModel, iter: &gtk::TreeIter) -> Option<usize> {
+    let component = model
+        .get_value(iter, COLUMN_COMPONENT as i32)
+        .get::<i32>()
+        .unwrap()
+        .unwrap_or(NO_COMPONENT);
+    if component < 0 {
+        None
+    } else {
+        Some(component as usize)
+    }
 }
@@ -1,7 +1,9 @@
 use crate::chart_widget::{ChartState, ChartStateHandle};
 use crate::session;
+use crate::statistics_table::StatisticsTable;
+use lognplot::chart::{range_statistics_columns, RangeStatistics};
 use lognplot::tracer::AnyTracer;
-use lognplot::tsdb::{DataChangeEvent, TsDbHandle};
+use lognplot::tsdb::{DataChangeEvent, Statistic, TsDbHandle};
 use std::cell::RefCell;
 use std::path::Path;
 use std::rc::Rc;
@@ -13,6 +15,7 @@ pub struct GuiState {
     perf_tracer: Arc<AnyTracer>,
     charts: Vec<ChartStateHandle>,
     link_x_axis: bool,
+    statistics_table: Option<StatisticsTable>,
 }
 
 impl GuiState {
@@ -23,6 +26,7 @@ impl GuiState {
             perf_tracer,
             charts: vec![],
             link_x_axis: false,
+            statistics_table: None,
         }
     }
 
@@ -39,6 +43,16 @@ impl GuiState {
         self.db.delete_all();
     }
 
+    pub fn delete_signal(&self, name: &str) {
+        info!("Delete signal {} from database", name);
+        self.db.delete(name);
+    }
+
+    pub fn delete_namespace(&self, namespace: &str) {
+        info!("Delete signals in {} from database", namespace);
+        self.db.delete_namespace(namespace);
+    }
+
     #[cfg(feature = "hdf5")]
     pub fn save(&self, filename: &Path) -> Result<(), String> {
         info!("Save data to {:?}", filename);
@@ -74,6 +88,27 @@ impl GuiState {
         Ok(())
     }
 
+    pub fn set_statistics_table(&mut self, statistics_table: StatisticsTable) {
+        self.statistics_table = Some(statistics_table);
+    }
+
+    /// Show the statistics between the cursors of a chart,
+    /// with the given column titles.
+    pub fn show_range_statistics(&self, columns: Vec<String>, statistics: &[RangeStatistics]) {
+        if let Some(statistics_table) = &self.statistics_table {
+            statistics_table.show(columns, statistics);
+        }
+    }
+
+    /// Select the statistics shown in all charts and in the table.
+    pub fn set_statistics(&self, statistics: &[Statistic]) {
+        // Charts with both cursors set fill in the new columns:
+        self.show_range_statistics(range_statistics_columns(statistics), &[]);
+        for chart in &self.charts {
+            chart.borrow_mut().set_statistics(statistics);
+        }
+    }
+
     pub fn add_chart(&mut self, chart: ChartStateHandle) {
         self.charts.push(chart);
     }
@@ -87,16 +122,16 @@ impl GuiState {
         self.charts.len()
     }
 
-    /// Add a curve with the given name to the first chart.
+    /// Add a curve to the given chart, or to the first chart.
     ///
     /// Handy for double click / enter press on a signal.
-    pub fn add_curve(&self, name: &str, chart_index: Option<usize>) {
+    pub fn add_curve(&self, curve: &session::CurveItem, chart_index: Option<usize>) {
         if let Some(index) = chart_index {
             if index > 0 && index <= self.charts.len() {
-                self.charts[index - 1].borrow_mut().add_curve(name);
+                self.charts[index - 1].borrow_mut().add_curve(curve);
             }
         } else {
-            self.charts.first().unwrap().borrow_mut().add_curve(name);
+            self.charts.first().unwrap().borrow_mut().add_curve(curve);
         };
     }
 
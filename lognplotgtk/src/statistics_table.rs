@@ -0,0 +1,83 @@
+//! Table with statistics of the curves between the cursors of a chart.
+
+use gtk::prelude::*;
+use lognplot::chart::{range_statistics_columns, Chart, RangeStatistics};
+use std::cell::RefCell;
+
+pub struct StatisticsTable {
+    tree_view: gtk::TreeView,
+    model: RefCell<gtk::ListStore>,
+
+    /// Titles of the shown columns, which follow the selected statistics.
+    columns: RefCell<Vec<String>>,
+}
+
+impl StatisticsTable {
+    /// Show the statistics, replacing the previous statistics.
+    pub fn show(&self, columns: Vec<String>, statistics: &[RangeStatistics]) {
+        if *self.columns.borrow() != columns {
+            self.set_columns(columns);
+        }
+
+        let model = self.model.borrow();
+        model.clear();
+        let color_column = self.columns.borrow().len() as u32;
+        let columns: Vec<u32> = (0..=color_column).collect();
+        for row in statistics {
+            let color = format!(
+                "#{:02X}{:02X}{:02X}",
+                row.color.r(),
+                row.color.g(),
+                row.color.b()
+            );
+            let mut values = row.to_row();
+            values.push(color);
+            let values: Vec<&dyn ToValue> = values.iter().map(|v| v as &dyn ToValue).collect();
+            let iter = model.append();
+            model.set(&iter, &columns, &values);
+        }
+    }
+
+    /// Replace the columns of the table, and its model.
+    fn set_columns(&self, titles: Vec<String>) {
+        for column in self.tree_view.get_columns() {
+            self.tree_view.remove_column(&column);
+        }
+
+        // The last model column holds the color of the curve:
+        let color_column = titles.len() as i32;
+        let types = vec![String::static_type(); titles.len() + 1];
+        let model = gtk::ListStore::new(&types);
+        self.tree_view.set_model(Some(&model));
+
+        for (index, title) in titles.iter().enumerate() {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            let cell = gtk::CellRendererText::new();
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", index as i32);
+            if index == 0 {
+                // Show the signal in the color of its curve:
+                column.add_attribute(&cell, "foreground", color_column);
+            }
+            self.tree_view.append_column(&column);
+        }
+
+        self.model.replace(model);
+        self.columns.replace(titles);
+    }
+}
+
+/// Prepare the table with statistics between cursors.
+pub fn setup_statistics_table(builder: &gtk::Builder) -> StatisticsTable {
+    let tree_view: gtk::TreeView = builder.get_object("statistics_view").unwrap();
+    let table = StatisticsTable {
+        tree_view,
+        model: RefCell::new(gtk::ListStore::new(&[])),
+        columns: RefCell::new(vec![]),
+    };
+    let columns = range_statistics_columns(&Chart::default().statistics);
+    table.set_columns(columns);
+    table
+}
@@ -71,6 +71,54 @@ class LognplotTcpClient:
         timestamp = coerce_timestamp(timestamp)
         self._send_dict({"name": name, "t": timestamp, "type": "text", "text": text})
 
+    def send_log(self, name, timestamp, level, message, target="", fields=None):
+        """ Emit a log record.
+
+        The level is one of error, warn, info, debug or trace.
+        """
+        timestamp = coerce_timestamp(timestamp)
+        self._send_dict(
+            {
+                "name": name,
+                "t": timestamp,
+                "type": "log",
+                "level": level,
+                "target": target,
+                "message": message,
+                "fields": fields or {},
+            }
+        )
+
+    def send_vector(self, name, timestamp, values):
+        """ Emit a vector of values at a single point in time.
+
+        Values are given as a dictionary of component names and values.
+        """
+        timestamp = coerce_timestamp(timestamp)
+        self._send_dict(
+            {
+                "name": name,
+                "t": timestamp,
+                "type": "vector",
+                "components": list(values.keys()),
+                "values": [float(v) for v in values.values()],
+            }
+        )
+
+    def send_metadata(self, name, unit=None, description=None, source=None, attributes=None):
+        """ Set information about a signal, such as its unit.
+
+        Information which is not given keeps its previous value.
+        """
+        data = {"name": name, "type": "metadata", "attributes": attributes or {}}
+        if unit is not None:
+            data["unit"] = unit
+        if description is not None:
+            data["description"] = description
+        if source is not None:
+            data["source"] = source
+        self._send_dict(data)
+
     def send_function_enter(self, name, timestamp, function_name):
         """ Trace function entry. """
         timestamp = coerce_timestamp(timestamp)